@@ -61,14 +61,26 @@ fn parse_any_element(parser: &mut Parser) -> Option<CompletedMarker> {
     parse_any_twig(parser, parse_any_element).or_else(|| parse_any_html(parser))
 }
 
+/// Tokens which start a ludtwig directive inside a twig or html comment
+const LUDTWIG_DIRECTIVE_TOKENS: &[SyntaxKind] = &[
+    T!["ludtwig-ignore-file"],
+    T!["ludtwig-ignore"],
+    T!["ludtwig-disable"],
+    T!["ludtwig-enable"],
+];
+
 fn parse_ludtwig_directive(
     parser: &mut Parser,
     outer: Marker,
     closing_kind: SyntaxKind,
 ) -> CompletedMarker {
-    debug_assert!(parser.at_set(&[T!["ludtwig-ignore-file"], T!["ludtwig-ignore"]]));
-    let ignore_kind = if parser.at(T!["ludtwig-ignore-file"]) {
+    debug_assert!(parser.at_set(LUDTWIG_DIRECTIVE_TOKENS));
+    let directive_kind = if parser.at(T!["ludtwig-ignore-file"]) {
         SyntaxKind::LUDTWIG_DIRECTIVE_FILE_IGNORE
+    } else if parser.at(T!["ludtwig-disable"]) {
+        SyntaxKind::LUDTWIG_DIRECTIVE_DISABLE
+    } else if parser.at(T!["ludtwig-enable"]) {
+        SyntaxKind::LUDTWIG_DIRECTIVE_ENABLE
     } else {
         SyntaxKind::LUDTWIG_DIRECTIVE_IGNORE
     };
@@ -77,7 +89,7 @@ fn parse_ludtwig_directive(
     let rule_list_m = parser.start();
     parse_many(
         parser,
-        |p| p.at(closing_kind),
+        |p| p.at(closing_kind) || at_ludtwig_directive_reason(p),
         |p| {
             p.expect(T![word], &[T![","], closing_kind]);
             if p.at(T![","]) {
@@ -87,8 +99,27 @@ fn parse_ludtwig_directive(
     );
     parser.complete(rule_list_m, SyntaxKind::LUDTWIG_DIRECTIVE_RULE_LIST);
 
+    if at_ludtwig_directive_reason(parser) {
+        // everything after '--' is a free text reason for the directive
+        let reason_m = parser.start();
+        parser.bump();
+        parser.bump();
+        parse_many(
+            parser,
+            |p| p.at(closing_kind),
+            |p| {
+                p.bump();
+            },
+        );
+        parser.complete(reason_m, SyntaxKind::LUDTWIG_DIRECTIVE_REASON);
+    }
+
     parser.expect(closing_kind, &[]);
-    parser.complete(outer, ignore_kind)
+    parser.complete(outer, directive_kind)
+}
+
+fn at_ludtwig_directive_reason(parser: &mut Parser) -> bool {
+    parser.at(T!["-"]) && parser.peek_nth_token(1).is_some_and(|t| t.kind == T!["-"])
 }
 
 #[cfg(test)]
@@ -259,4 +290,94 @@ mod tests {
                     TK_MINUS_MINUS_GREATER_THAN@71..74 "-->""#]],
         );
     }
+
+    #[test]
+    fn parse_twig_comment_ludtwig_directive_ignore_with_reason() {
+        check_parse(
+            "{# ludtwig-ignore indentation -- generated markup #}",
+            expect![[r##"
+                ROOT@0..52
+                  LUDTWIG_DIRECTIVE_IGNORE@0..52
+                    TK_OPEN_CURLY_HASHTAG@0..2 "{#"
+                    TK_WHITESPACE@2..3 " "
+                    TK_LUDTWIG_IGNORE@3..17 "ludtwig-ignore"
+                    LUDTWIG_DIRECTIVE_RULE_LIST@17..29
+                      TK_WHITESPACE@17..18 " "
+                      TK_WORD@18..29 "indentation"
+                    LUDTWIG_DIRECTIVE_REASON@29..49
+                      TK_WHITESPACE@29..30 " "
+                      TK_MINUS@30..31 "-"
+                      TK_MINUS@31..32 "-"
+                      TK_WHITESPACE@32..33 " "
+                      TK_WORD@33..42 "generated"
+                      TK_WHITESPACE@42..43 " "
+                      TK_WORD@43..49 "markup"
+                    TK_WHITESPACE@49..50 " "
+                    TK_HASHTAG_CLOSE_CURLY@50..52 "#}""##]],
+        );
+    }
+
+    #[test]
+    fn parse_twig_comment_ludtwig_directive_disable() {
+        check_parse(
+            "{# ludtwig-disable indentation, twig-block-line-breaks #}",
+            expect![[r##"
+                ROOT@0..57
+                  LUDTWIG_DIRECTIVE_DISABLE@0..57
+                    TK_OPEN_CURLY_HASHTAG@0..2 "{#"
+                    TK_WHITESPACE@2..3 " "
+                    TK_LUDTWIG_DISABLE@3..18 "ludtwig-disable"
+                    LUDTWIG_DIRECTIVE_RULE_LIST@18..54
+                      TK_WHITESPACE@18..19 " "
+                      TK_WORD@19..30 "indentation"
+                      TK_COMMA@30..31 ","
+                      TK_WHITESPACE@31..32 " "
+                      TK_WORD@32..54 "twig-block-line-breaks"
+                    TK_WHITESPACE@54..55 " "
+                    TK_HASHTAG_CLOSE_CURLY@55..57 "#}""##]],
+        );
+    }
+
+    #[test]
+    fn parse_twig_comment_ludtwig_directive_enable() {
+        check_parse(
+            "{# ludtwig-enable #}",
+            expect![[r##"
+            ROOT@0..20
+              LUDTWIG_DIRECTIVE_ENABLE@0..20
+                TK_OPEN_CURLY_HASHTAG@0..2 "{#"
+                TK_WHITESPACE@2..3 " "
+                TK_LUDTWIG_ENABLE@3..17 "ludtwig-enable"
+                LUDTWIG_DIRECTIVE_RULE_LIST@17..17
+                TK_WHITESPACE@17..18 " "
+                TK_HASHTAG_CLOSE_CURLY@18..20 "#}""##]],
+        );
+    }
+
+    #[test]
+    fn parse_html_comment_ludtwig_directive_disable_with_reason() {
+        check_parse(
+            "<!-- ludtwig-disable -- legacy-markup, not ours -->",
+            expect![[r#"
+                ROOT@0..51
+                  LUDTWIG_DIRECTIVE_DISABLE@0..51
+                    TK_LESS_THAN_EXCLAMATION_MARK_MINUS_MINUS@0..4 "<!--"
+                    TK_WHITESPACE@4..5 " "
+                    TK_LUDTWIG_DISABLE@5..20 "ludtwig-disable"
+                    LUDTWIG_DIRECTIVE_RULE_LIST@20..20
+                    LUDTWIG_DIRECTIVE_REASON@20..47
+                      TK_WHITESPACE@20..21 " "
+                      TK_MINUS@21..22 "-"
+                      TK_MINUS@22..23 "-"
+                      TK_WHITESPACE@23..24 " "
+                      TK_WORD@24..37 "legacy-markup"
+                      TK_COMMA@37..38 ","
+                      TK_WHITESPACE@38..39 " "
+                      TK_NOT@39..42 "not"
+                      TK_WHITESPACE@42..43 " "
+                      TK_WORD@43..47 "ours"
+                    TK_WHITESPACE@47..48 " "
+                    TK_MINUS_MINUS_GREATER_THAN@48..51 "-->""#]],
+        );
+    }
 }
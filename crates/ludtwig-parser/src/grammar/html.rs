@@ -1,5 +1,7 @@
 use crate::grammar::twig::{at_twig_termination_tag, parse_any_twig, parse_twig_var_statement};
-use crate::grammar::{parse_any_element, parse_ludtwig_directive, parse_many, ParseFunction};
+use crate::grammar::{
+    parse_any_element, parse_ludtwig_directive, parse_many, ParseFunction, LUDTWIG_DIRECTIVE_TOKENS,
+};
 use crate::parser::event::{CompletedMarker, Marker};
 use crate::parser::{ParseErrorBuilder, Parser, GENERAL_RECOVERY_SET};
 use crate::syntax::untyped::SyntaxKind;
@@ -149,7 +151,7 @@ fn parse_html_comment(parser: &mut Parser) -> CompletedMarker {
     let m = parser.start();
     parser.bump();
 
-    if parser.at_set(&[T!["ludtwig-ignore-file"], T!["ludtwig-ignore"]]) {
+    if parser.at_set(LUDTWIG_DIRECTIVE_TOKENS) {
         parse_ludtwig_directive(parser, m, T!["-->"])
     } else {
         parse_plain_html_comment(parser, m)
@@ -2411,6 +2413,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn parse_inline_style_tag() {
         check_parse(
             r"<style>
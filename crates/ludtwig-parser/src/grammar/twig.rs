@@ -6,7 +6,9 @@ mod tags;
 pub(crate) use tags::at_twig_termination_tag;
 
 use crate::grammar::twig::expression::{parse_twig_expression, TWIG_EXPRESSION_RECOVERY_SET};
-use crate::grammar::{parse_ludtwig_directive, parse_many, ParseFunction};
+use crate::grammar::{
+    parse_ludtwig_directive, parse_many, ParseFunction, LUDTWIG_DIRECTIVE_TOKENS,
+};
 use crate::parser::event::{CompletedMarker, Marker};
 use crate::parser::{ParseErrorBuilder, Parser};
 use crate::syntax::untyped::SyntaxKind;
@@ -34,7 +36,7 @@ fn parse_twig_comment_statement(parser: &mut Parser) -> CompletedMarker {
     let m = parser.start();
     parser.bump();
 
-    if parser.at_set(&[T!["ludtwig-ignore-file"], T!["ludtwig-ignore"]]) {
+    if parser.at_set(LUDTWIG_DIRECTIVE_TOKENS) {
         parse_ludtwig_directive(parser, m, T!["#}"])
     } else {
         parse_twig_plain_comment(parser, m)
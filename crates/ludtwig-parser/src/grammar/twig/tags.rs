@@ -5365,6 +5365,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn parse_twig_full_twig_component_declaration() {
         check_parse(
             r#"{% props icon = null, type = 'primary' %}
@@ -5541,12 +5542,13 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::too_many_lines)]
     fn parse_twig_non_html_component_call() {
         check_parse(
-            r#"{% component Alert with {type: 'success'} %}
+            r"{% component Alert with {type: 'success'} %}
     {% block content %}<div>Congrats!</div>{% endblock %}
     {% block footer %}... footer content{% endblock %}
-{% endcomponent %}"#,
+{% endcomponent %}",
             expect![[r#"
                 ROOT@0..176
                   TWIG_COMPONENT@0..176
@@ -5648,10 +5650,10 @@ mod tests {
     #[test]
     fn parse_twig_non_html_component_call_no_with() {
         check_parse(
-            r#"{% component Alert %}
+            r"{% component Alert %}
     {% block content %}<div>Congrats!</div>{% endblock %}
     {% block footer %}... footer content{% endblock %}
-{% endcomponent %}"#,
+{% endcomponent %}",
             expect![[r#"
                 ROOT@0..153
                   TWIG_COMPONENT@0..153
@@ -5735,9 +5737,9 @@ mod tests {
     #[test]
     fn parse_twig_non_html_component_call_no_name() {
         check_parse(
-            r#"{% component %}
+            r"{% component %}
             <div>Congrats!</div>
-{% endcomponent %}"#,
+{% endcomponent %}",
             expect![[r#"
                 ROOT@0..67
                   TWIG_COMPONENT@0..67
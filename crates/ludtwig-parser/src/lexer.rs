@@ -4,7 +4,7 @@ use crate::syntax::untyped::{SyntaxKind, TextRange, TextSize};
 
 /// Lex the source code into a Vec of tokens with their corresponding span (position in source code).
 /// These tokens are produced by a dumb lexer and don't have any meaning / semantic attached to them.
-pub(crate) fn lex(source: &str) -> Vec<Token> {
+pub(crate) fn lex(source: &str) -> Vec<Token<'_>> {
     let mut lexer = SyntaxKind::lexer(source);
     let mut result = vec![];
 
@@ -37,7 +37,7 @@ pub(crate) struct Token<'source> {
 }
 
 // ToDo: might be able to remove this annotation in future rust-analyzer version
-#[allow(clippy::needless_lifetimes)]
+#[allow(clippy::needless_lifetimes, clippy::elidable_lifetime_names)]
 impl<'source> Token<'source> {
     #[cfg(test)]
     pub(crate) fn new(kind: SyntaxKind, text: &'source str, range: TextRange) -> Self {
@@ -299,6 +299,8 @@ mod tests {
         add("style", T!["style"]);
         add("ludtwig-ignore-file", T!["ludtwig-ignore-file"]);
         add("ludtwig-ignore", T!["ludtwig-ignore"]);
+        add("ludtwig-disable", T!["ludtwig-disable"]);
+        add("ludtwig-enable", T!["ludtwig-enable"]);
         add("€", T![unknown]);
         add("trans", T!["trans"]);
         add("endtrans", T!["endtrans"]);
@@ -1054,4 +1056,14 @@ mod tests {
     fn lex_ludtwig_ignore() {
         check_token("ludtwig-ignore", T!["ludtwig-ignore"]);
     }
+
+    #[test]
+    fn lex_ludtwig_disable() {
+        check_token("ludtwig-disable", T!["ludtwig-disable"]);
+    }
+
+    #[test]
+    fn lex_ludtwig_enable() {
+        check_token("ludtwig-enable", T!["ludtwig-enable"]);
+    }
 }
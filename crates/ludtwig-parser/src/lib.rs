@@ -106,8 +106,10 @@
 //!
 
 pub use parser::parse;
+pub use parser::parse_with_timings;
 pub use parser::Parse;
 pub use parser::ParseError;
+pub use parser::ParseTimings;
 
 use crate::lexer::lex;
 
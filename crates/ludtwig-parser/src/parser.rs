@@ -1,4 +1,5 @@
 use std::fmt::Write;
+use std::time::{Duration, Instant};
 
 use rowan::GreenNode;
 
@@ -46,11 +47,37 @@ pub(crate) static GENERAL_RECOVERY_SET: &[SyntaxKind] =
 /// [crate level documentation](crate).
 #[must_use]
 pub fn parse(input_text: &str) -> Parse {
+    parse_with_timings(input_text).0
+}
+
+/// Like [`parse`] but also measures how long lexing and parsing took.
+#[must_use]
+pub fn parse_with_timings(input_text: &str) -> (Parse, ParseTimings) {
+    let start = Instant::now();
     let lex_result = lex(input_text);
+    let lex_duration = start.elapsed();
+
+    let start = Instant::now();
     let parser = Parser::new(&lex_result);
     let (parse_events, parse_errors) = parser.parse();
     let sink = Sink::new(&lex_result, parse_events, parse_errors);
-    sink.finish()
+    let parse = sink.finish();
+
+    (
+        parse,
+        ParseTimings {
+            lex: lex_duration,
+            parse: start.elapsed(),
+        },
+    )
+}
+
+/// Durations of the phases of [`parse_with_timings`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseTimings {
+    pub lex: Duration,
+    /// Parsing the tokens and building the syntax tree
+    pub parse: Duration,
 }
 
 /// Result of the parser
@@ -107,14 +134,14 @@ impl<'source> Parser<'source> {
         self.source.peek_kind()
     }
 
-    pub(crate) fn peek_token(&mut self) -> Option<&Token> {
+    pub(crate) fn peek_token(&mut self) -> Option<&Token<'_>> {
         self.source.peek_token()
     }
 
     /// Lookahead is expensive!
     /// This lookahead doesn't skip further trivia tokens and is only there for combining the next n lexer tokens!
     /// for n of zero use `peek_token` instead!
-    pub(crate) fn peek_nth_token(&mut self, n: usize) -> Option<&Token> {
+    pub(crate) fn peek_nth_token(&mut self, n: usize) -> Option<&Token<'_>> {
         self.source.peek_nth_token(n)
     }
 
@@ -145,7 +172,7 @@ impl<'source> Parser<'source> {
     }
 
     #[track_caller]
-    pub(crate) fn bump(&mut self) -> &Token {
+    pub(crate) fn bump(&mut self) -> &Token<'_> {
         let consumed = self
             .source
             .next_token()
@@ -166,7 +193,7 @@ impl<'source> Parser<'source> {
     }
 
     #[track_caller]
-    pub(crate) fn bump_as(&mut self, kind: SyntaxKind) -> Token {
+    pub(crate) fn bump_as(&mut self, kind: SyntaxKind) -> Token<'_> {
         let consumed = self
             .source
             .next_token()
@@ -182,7 +209,7 @@ impl<'source> Parser<'source> {
     }
 
     #[track_caller]
-    pub(crate) fn bump_next_n_as(&mut self, n: usize, kind: SyntaxKind) -> Vec<&Token> {
+    pub(crate) fn bump_next_n_as(&mut self, n: usize, kind: SyntaxKind) -> Vec<&Token<'_>> {
         let consumed = self.source.next_n_tokens(n);
         assert_eq!(
             consumed.len(),
@@ -199,7 +226,7 @@ impl<'source> Parser<'source> {
         &mut self,
         kind: SyntaxKind,
         recovery_set: &[SyntaxKind],
-    ) -> Option<&Token> {
+    ) -> Option<&Token<'_>> {
         if self.at(kind) {
             Some(self.bump())
         } else {
@@ -222,7 +249,7 @@ impl<'source> Parser<'source> {
         &mut self,
         expected_kind: Option<SyntaxKind>,
         recovery_set: &[SyntaxKind],
-    ) -> Option<&Token> {
+    ) -> Option<&Token<'_>> {
         if self.at_end() || self.at_set(GENERAL_RECOVERY_SET) || self.at_set(recovery_set) {
             return None;
         }
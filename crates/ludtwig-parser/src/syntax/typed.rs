@@ -126,6 +126,12 @@ impl TwigStartingBlock {
 
 ast_node!(TwigEndingBlock, SyntaxKind::TWIG_ENDING_BLOCK);
 impl TwigEndingBlock {
+    /// Name of the twig block, which is optional in the ending block (e.g. `{% endblock my_block %}`)
+    #[must_use]
+    pub fn name(&self) -> Option<SyntaxToken> {
+        support::token(&self.syntax, T![word])
+    }
+
     /// Parent complete twig block
     #[must_use]
     pub fn twig_block(&self) -> Option<TwigBlock> {
@@ -294,6 +300,27 @@ impl LudtwigDirectiveRuleList {
     }
 }
 
+ast_node!(LudtwigDirectiveReason, SyntaxKind::LUDTWIG_DIRECTIVE_REASON);
+impl LudtwigDirectiveReason {
+    /// The free text after `--` (without surrounding whitespace)
+    #[must_use]
+    pub fn get_text(&self) -> String {
+        self.syntax
+            .children_with_tokens()
+            .skip_while(|element| {
+                matches!(
+                    element.kind(),
+                    SyntaxKind::TK_WHITESPACE | SyntaxKind::TK_LINE_BREAK
+                )
+            })
+            .skip(2) // the '--'
+            .map(|element| element.to_string())
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+}
+
 ast_node!(
     LudtwigDirectiveFileIgnore,
     SyntaxKind::LUDTWIG_DIRECTIVE_FILE_IGNORE
@@ -306,6 +333,11 @@ impl LudtwigDirectiveFileIgnore {
             None => vec![],
         }
     }
+
+    #[must_use]
+    pub fn get_reason(&self) -> Option<String> {
+        support::child::<LudtwigDirectiveReason>(&self.syntax).map(|r| r.get_text())
+    }
 }
 
 ast_node!(LudtwigDirectiveIgnore, SyntaxKind::LUDTWIG_DIRECTIVE_IGNORE);
@@ -317,6 +349,43 @@ impl LudtwigDirectiveIgnore {
             None => vec![],
         }
     }
+
+    #[must_use]
+    pub fn get_reason(&self) -> Option<String> {
+        support::child::<LudtwigDirectiveReason>(&self.syntax).map(|r| r.get_text())
+    }
+}
+
+ast_node!(
+    LudtwigDirectiveDisable,
+    SyntaxKind::LUDTWIG_DIRECTIVE_DISABLE
+);
+impl LudtwigDirectiveDisable {
+    /// The rules which are disabled until the next `ludtwig-enable` (empty means all rules)
+    #[must_use]
+    pub fn get_rules(&self) -> Vec<String> {
+        match support::child::<LudtwigDirectiveRuleList>(&self.syntax) {
+            Some(rule_list) => rule_list.get_rule_names(),
+            None => vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn get_reason(&self) -> Option<String> {
+        support::child::<LudtwigDirectiveReason>(&self.syntax).map(|r| r.get_text())
+    }
+}
+
+ast_node!(LudtwigDirectiveEnable, SyntaxKind::LUDTWIG_DIRECTIVE_ENABLE);
+impl LudtwigDirectiveEnable {
+    /// The rules which are enabled again (empty means all rules)
+    #[must_use]
+    pub fn get_rules(&self) -> Vec<String> {
+        match support::child::<LudtwigDirectiveRuleList>(&self.syntax) {
+            Some(rule_list) => rule_list.get_rule_names(),
+            None => vec![],
+        }
+    }
 }
 
 ast_node!(TwigLiteralString, SyntaxKind::TWIG_LITERAL_STRING);
@@ -689,4 +758,40 @@ mod tests {
             Some("hello".to_string())
         );
     }
+
+    #[test]
+    fn twig_block_names() {
+        let block: TwigBlock = parse_and_extract("{% block a %}{% endblock a %}");
+        assert_eq!(block.name().map(|t| t.to_string()), Some("a".to_string()));
+        assert_eq!(
+            block
+                .ending_block()
+                .and_then(|b| b.name())
+                .map(|t| t.to_string()),
+            Some("a".to_string())
+        );
+
+        let block: TwigBlock = parse_and_extract("{% block a %}{% endblock %}");
+        assert_eq!(block.ending_block().and_then(|b| b.name()), None);
+    }
+
+    #[test]
+    fn ludtwig_directive_reasons() {
+        let directive: LudtwigDirectiveIgnore =
+            parse_and_extract("{# ludtwig-ignore indentation -- generated  markup #}");
+        assert_eq!(directive.get_rules(), vec!["indentation".to_string()]);
+        assert_eq!(
+            directive.get_reason(),
+            Some("generated  markup".to_string())
+        );
+
+        let directive: LudtwigDirectiveDisable =
+            parse_and_extract("<!-- ludtwig-disable -- legacy -->");
+        assert!(directive.get_rules().is_empty());
+        assert_eq!(directive.get_reason(), Some("legacy".to_string()));
+
+        let directive: LudtwigDirectiveFileIgnore =
+            parse_and_extract("{# ludtwig-ignore-file indentation #}");
+        assert_eq!(directive.get_reason(), None);
+    }
 }
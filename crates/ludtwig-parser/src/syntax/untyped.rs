@@ -343,6 +343,10 @@ pub enum SyntaxKind {
     TK_LUDTWIG_IGNORE_FILE,
     #[token("ludtwig-ignore", ignore(ascii_case))]
     TK_LUDTWIG_IGNORE,
+    #[token("ludtwig-disable", ignore(ascii_case))]
+    TK_LUDTWIG_DISABLE,
+    #[token("ludtwig-enable", ignore(ascii_case))]
+    TK_LUDTWIG_ENABLE,
     TK_UNKNOWN, // contains invalid / unrecognized syntax (used for error recovery).
 
     /*
@@ -501,7 +505,10 @@ pub enum SyntaxKind {
     // special ludtwig directive
     LUDTWIG_DIRECTIVE_FILE_IGNORE,
     LUDTWIG_DIRECTIVE_IGNORE,
+    LUDTWIG_DIRECTIVE_DISABLE,
+    LUDTWIG_DIRECTIVE_ENABLE,
     LUDTWIG_DIRECTIVE_RULE_LIST,
+    LUDTWIG_DIRECTIVE_REASON,
     /*
     Special Nodes
      */
@@ -656,6 +663,8 @@ macro_rules! T {
     ["style"] => { $crate::syntax::untyped::SyntaxKind::TK_STYLE };
     ["ludtwig-ignore-file"] => { $crate::syntax::untyped::SyntaxKind::TK_LUDTWIG_IGNORE_FILE };
     ["ludtwig-ignore"] => { $crate::syntax::untyped::SyntaxKind::TK_LUDTWIG_IGNORE };
+    ["ludtwig-disable"] => { $crate::syntax::untyped::SyntaxKind::TK_LUDTWIG_DISABLE };
+    ["ludtwig-enable"] => { $crate::syntax::untyped::SyntaxKind::TK_LUDTWIG_ENABLE };
 }
 
 impl SyntaxKind {
@@ -813,6 +822,8 @@ impl fmt::Display for SyntaxKind {
             SyntaxKind::TK_STYLE => "style",
             SyntaxKind::TK_LUDTWIG_IGNORE_FILE => "ludtwig-ignore-file",
             SyntaxKind::TK_LUDTWIG_IGNORE => "ludtwig-ignore",
+            SyntaxKind::TK_LUDTWIG_DISABLE => "ludtwig-disable",
+            SyntaxKind::TK_LUDTWIG_ENABLE => "ludtwig-enable",
             SyntaxKind::TK_UNKNOWN => "unknown",
             SyntaxKind::ERROR => "error",
             t => unreachable!("Display not implemented for {:?}", t),
@@ -831,6 +842,19 @@ impl From<SyntaxKind> for rowan::SyntaxKind {
     }
 }
 
+/// Get the kind back from its raw value (`kind as u16`), for example after storing it somewhere.
+impl TryFrom<u16> for SyntaxKind {
+    type Error = u16;
+
+    fn try_from(raw: u16) -> Result<Self, Self::Error> {
+        if raw <= SyntaxKind::ROOT as u16 {
+            Ok(TemplateLanguage::kind_from_raw(rowan::SyntaxKind(raw)))
+        } else {
+            Err(raw)
+        }
+    }
+}
+
 // Second, implementing the `Language` trait teaches rowan to convert between
 // these two `SyntaxKind` types, allowing for a nicer `SyntaxNode` API where
 // "kinds" are values from our `enum SyntaxKind`, instead of plain u16 values.
@@ -0,0 +1,316 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ludtwig_parser::syntax::untyped::{SyntaxKind, TextRange};
+use ludtwig_parser::ParseError;
+use serde::{Deserialize, Serialize};
+
+use crate::check::rule::CheckResult;
+use crate::config::{ActiveConfig, LUDTWIG_VERSION};
+
+/// Directory of the result cache if no `--cache-dir` is given.
+pub const DEFAULT_CACHE_DIR: &str = ".ludtwig-cache";
+
+/// On disk cache of the check results of files (`--cache`), so unchanged files don't need to be parsed and checked again.
+///
+/// Every file has one entry, which is only valid for the [`key`](ResultCache::key) it was stored with
+/// and as long as the files it depends on are unchanged.
+#[derive(Debug)]
+pub struct ResultCache {
+    dir: PathBuf,
+}
+
+/// The stored check results and parser errors of a single file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry {
+    key: String,
+    dependencies: Vec<CachedDependency>,
+    parse_errors: Vec<CachedParseError>,
+    pub results: Vec<CheckResult>,
+}
+
+/// Another file (like an extended template) and the hash of its content when the results were stored.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedDependency {
+    path: PathBuf,
+    /// `None` if the file didn't exist
+    hash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedParseError {
+    start: u32,
+    end: u32,
+    found: Option<u16>,
+    expected: String,
+}
+
+impl ResultCache {
+    /// Use (and create if needed) the cache directory.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        // the cache should never be committed
+        let gitignore = dir.join(".gitignore");
+        if !gitignore.exists() {
+            fs::write(gitignore, "*\n")?;
+        }
+
+        Ok(Self { dir })
+    }
+
+    /// Everything (besides other files) the results of a file depend on:
+    /// its content, the ludtwig version and the effective config with its active rules.
+    pub fn key(config: &ActiveConfig, source_code: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(LUDTWIG_VERSION.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(
+            serde_json::to_string(&config.config)
+                .expect("config should be serializable")
+                .as_bytes(),
+        );
+        for rule in &config.rule_definitions {
+            hasher.update(b"\0");
+            hasher.update(rule.name().as_bytes());
+        }
+        hasher.update(b"\0");
+        hasher.update(source_code.as_bytes());
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// Get the entry of the file if it was stored with the same key and none of its dependencies changed.
+    pub fn get(&self, path: &Path, key: &str) -> Option<CacheEntry> {
+        let raw = fs::read(self.entry_path(path)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&raw).ok()?;
+        if entry.key != key {
+            return None;
+        }
+
+        entry
+            .dependencies
+            .iter()
+            .all(|dependency| hash_file(&dependency.path) == dependency.hash)
+            .then_some(entry)
+    }
+
+    /// Store the results of the file, replacing its previous entry.
+    pub fn put(
+        &self,
+        path: &Path,
+        key: String,
+        dependencies: Vec<PathBuf>,
+        parse_errors: &[ParseError],
+        results: Vec<CheckResult>,
+    ) -> io::Result<()> {
+        let entry = CacheEntry {
+            key,
+            dependencies: dependencies
+                .into_iter()
+                .map(|path| CachedDependency {
+                    hash: hash_file(&path),
+                    path,
+                })
+                .collect(),
+            parse_errors: parse_errors
+                .iter()
+                .map(|error| CachedParseError {
+                    start: error.range.start().into(),
+                    end: error.range.end().into(),
+                    found: error.found.map(|kind| kind as u16),
+                    expected: error.expected.clone(),
+                })
+                .collect(),
+            results,
+        };
+        let raw = serde_json::to_vec(&entry).map_err(io::Error::other)?;
+
+        // write the whole entry at once, so concurrent runs never read half written entries
+        let entry_path = self.entry_path(path);
+        let temp_path = entry_path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&temp_path, raw)?;
+        fs::rename(temp_path, entry_path)
+    }
+
+    fn entry_path(&self, path: &Path) -> PathBuf {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let name = blake3::hash(path.to_string_lossy().as_bytes()).to_hex();
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+impl CacheEntry {
+    /// The files the results depend on.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies
+            .iter()
+            .map(|dependency| dependency.path.clone())
+            .collect()
+    }
+
+    /// The stored parser errors, `None` if they are no longer valid syntax kinds.
+    pub fn parse_errors(&self) -> Option<Vec<ParseError>> {
+        self.parse_errors
+            .iter()
+            .map(|error| {
+                let found = match error.found {
+                    Some(raw) => Some(SyntaxKind::try_from(raw).ok()?),
+                    None => None,
+                };
+                Some(ParseError {
+                    range: TextRange::new(error.start.into(), error.end.into()),
+                    found,
+                    expected: error.expected.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    let content = fs::read(path).ok()?;
+    Some(blake3::hash(&content).to_hex().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::sync::{Arc, OnceLock};
+
+    use crate::check::rules::test::debug_rule;
+    use crate::check::run_rules;
+    use crate::config::{ActiveConfig, CustomRule, CustomRuleSeverity};
+    use crate::process::FileContext;
+    use crate::test_util::temp_dir;
+
+    use super::ResultCache;
+
+    #[test]
+    fn cache_entry_round_trip() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let cache = ResultCache::open(dir.join("cache")).unwrap();
+        let source_code = "{% if a === 5 %}{% endif %}<div";
+        let (file_context, results, _rx) = debug_rule("twig-use-is-same-as", source_code);
+        let key = ResultCache::key(&file_context.config, source_code);
+        let template = dir.join("a.html.twig");
+
+        assert!(cache.get(&template, &key).is_none());
+        cache
+            .put(
+                &template,
+                key.clone(),
+                vec![],
+                &file_context.parse_errors,
+                results.clone(),
+            )
+            .unwrap();
+
+        let entry = cache.get(&template, &key).unwrap();
+        assert_eq!(format!("{:?}", entry.results), format!("{results:?}"));
+        assert_eq!(
+            format!("{:?}", entry.parse_errors().unwrap()),
+            format!("{:?}", file_context.parse_errors)
+        );
+        assert!(cache.get(&template, "other-key").is_none());
+        assert!(cache.get(&dir.join("b.html.twig"), &key).is_none());
+        assert_eq!(
+            fs::read_to_string(dir.join("cache").join(".gitignore")).unwrap(),
+            "*\n"
+        );
+    }
+
+    #[test]
+    fn cache_entry_round_trip_with_custom_rule() {
+        let temp = temp_dir();
+        let cache = ResultCache::open(temp.path().join("cache")).unwrap();
+        let source_code = "{{ title|raw }}";
+        let (file_context, _, _rx) = debug_rule("twig-use-is-same-as", source_code);
+        let mut config = file_context.config.config.clone();
+        config.custom_rules = vec![CustomRule {
+            name: "twig-no-raw".to_string(),
+            query: "TWIG_FILTER".to_string(),
+            message: "Don't use filters".to_string(),
+            severity: CustomRuleSeverity::Warning,
+            replacement: None,
+        }];
+        let config = Arc::new(ActiveConfig::new(config, None).unwrap());
+        let file_context = FileContext {
+            file_rule_definitions: config.rule_definitions.clone(),
+            config: Arc::clone(&config),
+            ..file_context
+        };
+        let results = run_rules(&file_context);
+        assert!(results.iter().any(|r| r.rule_name() == "twig-no-raw"));
+
+        let key = ResultCache::key(&config, source_code);
+        let template = temp.path().join("a.html.twig");
+        cache
+            .put(&template, key.clone(), vec![], &[], results.clone())
+            .unwrap();
+
+        let entry = cache.get(&template, &key).unwrap();
+        assert_eq!(format!("{:?}", entry.results), format!("{results:?}"));
+    }
+
+    #[test]
+    fn cache_key_changes_with_content_and_config() {
+        let (file_context, _, _rx) = debug_rule("twig-use-is-same-as", "");
+        let config = &file_context.config;
+        let key = ResultCache::key(config, "{{ a }}");
+
+        assert_eq!(key, ResultCache::key(config, "{{ a }}"));
+        assert_ne!(key, ResultCache::key(config, "{{ b }}"));
+
+        let mut changed_config = config.config.clone();
+        changed_config.format.indentation_count += 1;
+        let changed_config = ActiveConfig::new(changed_config, None).unwrap();
+        assert_ne!(key, ResultCache::key(&changed_config, "{{ a }}"));
+
+        let fewer_rules = ActiveConfig {
+            path: None,
+            config: config.config.clone(),
+            rule_definitions: config.rule_definitions[1..].to_vec(),
+            project: OnceLock::new(),
+        };
+        assert_ne!(key, ResultCache::key(&fewer_rules, "{{ a }}"));
+    }
+
+    #[test]
+    fn cache_entry_is_invalidated_by_changed_dependency() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let cache = ResultCache::open(dir.join("cache")).unwrap();
+        let template = dir.join("a.html.twig");
+        let parent = dir.join("base.html.twig");
+        let missing = dir.join("missing.html.twig");
+        fs::write(&parent, "{% block a %}{% endblock %}").unwrap();
+
+        cache
+            .put(
+                &template,
+                "key".to_string(),
+                vec![parent.clone(), missing.clone()],
+                &[],
+                vec![],
+            )
+            .unwrap();
+        assert!(cache.get(&template, "key").is_some());
+
+        fs::write(&parent, "{% block b %}{% endblock %}").unwrap();
+        assert!(cache.get(&template, "key").is_none());
+
+        cache
+            .put(
+                &template,
+                "key".to_string(),
+                vec![parent, missing.clone()],
+                &[],
+                vec![],
+            )
+            .unwrap();
+        assert!(cache.get(&template, "key").is_some());
+        fs::write(&missing, "").unwrap();
+        assert!(cache.get(&template, "key").is_none());
+    }
+}
@@ -1,36 +1,129 @@
-use std::sync::Arc;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
 
 use codespan_reporting::diagnostic::{Diagnostic, Label};
-use codespan_reporting::files::SimpleFiles;
-use codespan_reporting::term;
-use codespan_reporting::term::termcolor::Buffer;
 
 use ludtwig_parser::syntax::typed;
 use ludtwig_parser::syntax::typed::{
-    AstNode, HtmlStringInner, HtmlTag, LudtwigDirectiveIgnore, TwigLiteralStringInner,
+    AstNode, HtmlStringInner, HtmlTag, LudtwigDirectiveDisable, LudtwigDirectiveEnable,
+    LudtwigDirectiveIgnore, TwigLiteralStringInner,
 };
-use ludtwig_parser::syntax::untyped::{debug_tree, SyntaxElement, SyntaxToken, WalkEvent};
+use ludtwig_parser::syntax::untyped::{
+    debug_tree, SyntaxElement, SyntaxNode, SyntaxToken, TextRange, TextSize, WalkEvent,
+};
+use ludtwig_parser::ParseError;
 
 use crate::check::rule::{
-    CheckResult, CheckSuggestion, RuleRunContext, Severity, TreeTraversalContext,
+    Applicability, CheckResult, CheckSuggestion, Rule, RuleRunContext, Severity,
+    TreeTraversalContext,
 };
+use crate::check::rules::RuleLudtwigDirectiveUnused;
+use crate::config::ActiveConfig;
+use crate::git::ChangedLines;
+use crate::output::FileDiagnostic;
 use crate::process::FileContext;
-use crate::ProcessingEvent;
+use crate::timings::{timed, RulePhase, RuleTimings, Timings};
+use crate::{CliContext, CliSharedData, ProcessingEvent};
 
+pub mod custom;
+pub mod query;
 pub mod rule;
 pub mod rules;
+pub mod script;
+
+/// The code of diagnostics for parser errors.
+pub const SYNTAX_ERROR_CODE: &str = "SyntaxError";
 
-#[allow(clippy::too_many_lines)]
 pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
+    run_rules_in(file_context, RulePhase::Check)
+}
+
+/// Like [`run_rules`] but for source code with applied suggestions, the time is part of fixing the file.
+pub fn run_rules_after_fix(file_context: &FileContext) -> Vec<CheckResult> {
+    run_rules_in(file_context, RulePhase::Fix)
+}
+
+fn run_rules_in(file_context: &FileContext, phase: RulePhase) -> Vec<CheckResult> {
+    let start = Instant::now();
+    let timings = file_context.cli_context.data.timings.as_ref();
+    let mut check_results = run_rule_definitions(
+        &file_context.tree_root,
+        &file_context.file_path,
+        &file_context.config,
+        &file_context.file_rule_definitions,
+        &file_context.dependencies,
+        timings.map(|timings| (timings, phase)),
+    );
+
+    if let Some(rule) = file_context
+        .file_rule_definitions
+        .iter()
+        .find(|rule| rule.name() == RuleLudtwigDirectiveUnused.name())
+    {
+        let mut rule_timings = RuleTimings::default();
+        check_results.extend(timed(timings.map(|_| &mut rule_timings.root), || {
+            RuleLudtwigDirectiveUnused.check_unused_directives(file_context)
+        }));
+        if let Some(timings) = timings {
+            timings.record_rules(std::slice::from_ref(rule), &[rule_timings], phase);
+        }
+    }
+    if let Some(timings) = timings.filter(|_| phase == RulePhase::Check) {
+        timings.record_check(start.elapsed());
+    }
+
+    if let Some(lines) = &file_context.changed_lines {
+        retain_changed_lines(&mut check_results, &file_context.source_code, lines);
+    }
+
+    check_results
+}
+
+/// Remove the results whose primary range is outside the changed lines (`--changed-lines-only`).
+/// Results without a primary range are kept.
+pub fn retain_changed_lines(
+    check_results: &mut Vec<CheckResult>,
+    source_code: &str,
+    lines: &ChangedLines,
+) {
+    let line_number =
+        |offset: TextSize| source_code[..usize::from(offset)].matches('\n').count() + 1;
+
+    check_results.retain(|result| {
+        result.primary.as_ref().map_or(true, |primary| {
+            lines.intersects(
+                line_number(primary.syntax_range.start()),
+                line_number(primary.syntax_range.end()),
+            )
+        })
+    });
+}
+
+/// Run the given rules on the syntax tree while respecting the ignore directives inside it.
+/// The time spent in each rule is added to the `timings` (if there are any) for the phase.
+#[allow(clippy::too_many_lines)]
+pub fn run_rule_definitions(
+    tree_root: &SyntaxNode,
+    file_path: &Path,
+    config: &Arc<ActiveConfig>,
+    rule_definitions: &[Arc<dyn Rule>],
+    dependencies: &FileDependencies,
+    timings: Option<(&Timings, RulePhase)>,
+) -> Vec<CheckResult> {
     let mut check_results = vec![];
+    let mut rule_timings = timings.map(|_| vec![RuleTimings::default(); rule_definitions.len()]);
     let mut run_context = RuleRunContext {
-        cli_data: Arc::clone(&file_context.cli_context.data),
+        active_config: Arc::clone(config),
+        file_path: file_path.to_path_buf(),
         traversal_ctx: TreeTraversalContext {
             inside_trivia_sensitive_node: false,
         },
+        dependencies: dependencies.clone(),
     };
 
-    if file_context.file_rule_definitions.is_empty() {
+    if rule_definitions.is_empty() {
         // no rules to run for this file
         return vec![];
     }
@@ -45,20 +138,30 @@ pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
         - likely also slower (because node / token reconstruction times)
      */
 
-    // run root node checks once for each rule
-    let rule_results_iter = file_context
-        .file_rule_definitions
+    // run root node checks once for each rule, their results are filtered by the directives while walking the tree
+    let root_results: Vec<CheckResult> = rule_definitions
         .iter()
-        .filter_map(|rule| rule.check_root(file_context.tree_root.clone(), &run_context))
-        .flatten();
-    check_results.extend(rule_results_iter);
+        .enumerate()
+        .filter_map(|(i, rule)| {
+            timed(rule_timings.as_mut().map(|t| &mut t[i].root), || {
+                rule.check_root(tree_root.clone(), &run_context)
+            })
+        })
+        .flatten()
+        .collect();
+    let mut root_results_ignored = vec![false; root_results.len()];
 
     // iterate through syntax tree
     let mut ignored_rules: Vec<String> = vec![];
-    let mut preorder = file_context.tree_root.preorder_with_tokens();
+    let mut disabled_regions = DisabledRegions::default();
+    let mut preorder = tree_root.preorder_with_tokens();
     while let Some(walk_event) = preorder.next() {
         match walk_event {
             WalkEvent::Enter(element) => {
+                if let SyntaxElement::Node(n) = &element {
+                    disabled_regions.enter(n);
+                }
+
                 // add ignored rules when entering the sibling after the ignore directive / comment
                 // also skip the whole subtree if there is a ignore directive without specific rules
                 let mut found_ignored_rules: Vec<String> = match element.prev_sibling_or_token() {
@@ -67,6 +170,12 @@ pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
                             let ignored_rules = directive.get_rules();
                             if ignored_rules.is_empty() {
                                 // all rules are disabled
+                                ignore_root_results(
+                                    &root_results,
+                                    &mut root_results_ignored,
+                                    element.text_range(),
+                                    |_| true,
+                                );
                                 preorder.skip_subtree();
                                 continue;
                             }
@@ -78,11 +187,21 @@ pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
                     _ => vec![],
                 };
                 ignored_rules.append(&mut found_ignored_rules);
+                let is_ignored = |rule_name: &str| {
+                    disabled_regions.is_disabled(rule_name)
+                        || ignored_rules.iter().any(|ignored| ignored == rule_name)
+                };
 
                 // actually run the rules
                 match element {
                     SyntaxElement::Node(n) => {
                         if typed::Error::can_cast(n.kind()) {
+                            ignore_root_results(
+                                &root_results,
+                                &mut root_results_ignored,
+                                n.text_range(),
+                                is_ignored,
+                            );
                             preorder.skip_subtree();
                             continue; // Skip error nodes in rules because they should have
                                       // corresponding parser error messages in most cases and can contain
@@ -103,29 +222,40 @@ pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
                         }
 
                         // run node checks for every rule
-                        let results = file_context
-                            .file_rule_definitions
+                        let results = rule_definitions
                             .iter()
-                            .filter_map(|rule| {
-                                if ignored_rules.iter().any(|ignored| ignored == rule.name()) {
+                            .enumerate()
+                            .filter_map(|(i, rule)| {
+                                if is_ignored(rule.name()) {
                                     None
                                 } else {
-                                    rule.check_node(n.clone(), &run_context)
+                                    timed(rule_timings.as_mut().map(|t| &mut t[i].node), || {
+                                        rule.check_node(n.clone(), &run_context)
+                                    })
                                 }
                             })
                             .flatten();
                         check_results.extend(results);
                     }
                     SyntaxElement::Token(t) => {
+                        ignore_root_results(
+                            &root_results,
+                            &mut root_results_ignored,
+                            t.text_range(),
+                            is_ignored,
+                        );
+
                         // run token checks for every rule
-                        let results = file_context
-                            .file_rule_definitions
+                        let results = rule_definitions
                             .iter()
-                            .filter_map(|rule| {
-                                if ignored_rules.iter().any(|ignored| ignored == rule.name()) {
+                            .enumerate()
+                            .filter_map(|(i, rule)| {
+                                if is_ignored(rule.name()) {
                                     None
                                 } else {
-                                    rule.check_token(t.clone(), &run_context)
+                                    timed(rule_timings.as_mut().map(|t| &mut t[i].token), || {
+                                        rule.check_token(t.clone(), &run_context)
+                                    })
                                 }
                             })
                             .flatten();
@@ -163,60 +293,163 @@ pub fn run_rules(file_context: &FileContext) -> Vec<CheckResult> {
         }
     }
 
+    if let (Some((timings, phase)), Some(rule_timings)) = (timings, rule_timings) {
+        timings.record_rules(rule_definitions, &rule_timings, phase);
+    }
+
+    let root_results = root_results
+        .into_iter()
+        .zip(root_results_ignored)
+        .filter_map(|(result, ignored)| (!ignored).then_some(result));
+    check_results.splice(0..0, root_results);
     check_results
 }
 
-pub fn get_rule_context_suggestions(
-    check_results: &[CheckResult],
-) -> Vec<(&'static str, &CheckSuggestion)> {
+/// Mark the root results whose primary location starts inside the range as ignored, if their rule is ignored there.
+fn ignore_root_results(
+    root_results: &[CheckResult],
+    ignored: &mut [bool],
+    range: TextRange,
+    is_ignored: impl Fn(&str) -> bool,
+) {
+    for (result, ignored) in root_results.iter().zip(ignored) {
+        if let Some(primary) = &result.primary {
+            if range.contains(primary.syntax_range.start()) && is_ignored(&result.rule_name) {
+                *ignored = true;
+            }
+        }
+    }
+}
+
+/// Other files whose content was used by the rules while checking a file (shared between clones).
+#[derive(Debug, Clone, Default)]
+pub struct FileDependencies(Arc<Mutex<BTreeSet<PathBuf>>>);
+
+impl FileDependencies {
+    pub fn add(&self, path: PathBuf) {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(path);
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Rules which are disabled by `ludtwig-disable` and `ludtwig-enable` directives.
+/// In contrast to `ludtwig-ignore` these regions apply to everything between the directives
+/// in document order (they can span across siblings and nesting levels).
+#[derive(Debug, Default)]
+pub struct DisabledRegions {
+    all_disabled: bool,
+    /// the disabled rules or (when all rules are disabled) the rules which were enabled again
+    exceptions: Vec<String>,
+}
+
+impl DisabledRegions {
+    /// Must be called for every node in document order (when entering it).
+    pub fn enter(&mut self, node: &SyntaxNode) {
+        if let Some(directive) = LudtwigDirectiveDisable::cast(node.clone()) {
+            self.update(directive.get_rules(), true);
+        } else if let Some(directive) = LudtwigDirectiveEnable::cast(node.clone()) {
+            self.update(directive.get_rules(), false);
+        }
+    }
+
+    fn update(&mut self, rules: Vec<String>, disable: bool) {
+        if rules.is_empty() {
+            self.all_disabled = disable;
+            self.exceptions.clear();
+        } else if self.all_disabled == disable {
+            self.exceptions.retain(|r| !rules.contains(r));
+        } else {
+            self.exceptions.extend(rules);
+        }
+    }
+
+    pub fn is_disabled(&self, rule_name: &str) -> bool {
+        self.all_disabled != self.exceptions.iter().any(|r| r == rule_name)
+    }
+}
+
+/// Get all suggestions which should be applied automatically, respecting `--fix-only` and `--fix-unsafe`.
+pub fn get_rule_context_suggestions<'a>(
+    check_results: &'a [CheckResult],
+    cli_data: &CliSharedData,
+) -> Vec<(&'a str, &'a CheckSuggestion)> {
     check_results
         .iter()
+        .filter(|res| cli_data.fix_only.is_empty() || cli_data.fix_only.contains(&res.rule_name))
         .flat_map(|res| {
-            let rule_name = res.rule_name;
+            let rule_name = res.rule_name.as_str();
             res.suggestions.iter().map(move |sug| (rule_name, sug))
         })
+        .filter(|(_, sug)| cli_data.fix_unsafe || sug.applicability == Applicability::Safe)
         .collect()
 }
 
-pub fn produce_diagnostics(
-    file_context: &FileContext,
-    rule_results: Vec<CheckResult>,
-    buffer: &mut Buffer,
-) {
-    // diagnostic output setup
-    let mut files = SimpleFiles::new();
-    let file_id = files.add(
-        file_context.file_path.to_string_lossy(),
-        &file_context.source_code,
-    );
-    let config = term::Config {
-        // styles: Styles::with_blue(term::termcolor::Color::Cyan),
-        ..Default::default()
-    };
-
+pub fn produce_diagnostics(file_context: &FileContext, rule_results: Vec<CheckResult>) {
     if file_context.cli_context.data.inspect {
-        // notify output about this
-        file_context.send_processing_output(ProcessingEvent::Report(Severity::Info));
-
+        let (files, _) = FileDiagnostic::files(&file_context.file_path, &file_context.source_code);
         let diagnostic = Diagnostic::note()
             .with_code("SyntaxTree")
             .with_message("visualization of the syntax tree (inspect cli option is active)")
             .with_notes(vec![debug_tree(&file_context.tree_root)]);
 
-        term::emit(buffer, &config, &files, &diagnostic).unwrap();
+        // notify output about this
+        file_context.send_processing_output(ProcessingEvent::Diagnostic(FileDiagnostic::new(
+            &file_context.file_path,
+            TextRange::default(),
+            Severity::Info,
+            &files,
+            diagnostic,
+        )));
     }
 
+    send_diagnostics(
+        &file_context.cli_context,
+        &file_context.file_path,
+        &file_context.source_code,
+        &file_context.parse_errors,
+        rule_results,
+    );
+}
+
+/// Send the parser errors and rule check results of a file as diagnostics to the output.
+/// Unlike [`produce_diagnostics`] this doesn't need the syntax tree (for example for cached results).
+pub fn send_diagnostics(
+    cli_context: &CliContext,
+    file_path: &Path,
+    source_code: &str,
+    parse_errors: &[ParseError],
+    rule_results: Vec<CheckResult>,
+) {
+    // diagnostic output setup
+    let (files, file_id) = FileDiagnostic::files(file_path, source_code);
+
     // run through the parser errors
-    for result in &file_context.parse_errors {
-        // notify output about this
-        file_context.send_processing_output(ProcessingEvent::Report(Severity::Error));
+    for result in parse_errors {
         let label = Label::primary(file_id, result.range).with_message(result.expected_message());
         let diagnostic = Diagnostic::error()
-            .with_code("SyntaxError")
+            .with_code(SYNTAX_ERROR_CODE)
             .with_message("The parser encountered a syntax error")
             .with_labels(vec![label]);
 
-        term::emit(buffer, &config, &files, &diagnostic).unwrap();
+        // notify output about this
+        cli_context.send_processing_output(ProcessingEvent::Diagnostic(FileDiagnostic::new(
+            file_path,
+            result.range,
+            Severity::Error,
+            &files,
+            diagnostic,
+        )));
     }
 
     // run through the rule check results
@@ -227,9 +460,19 @@ pub fn produce_diagnostics(
             Severity::Help => Diagnostic::help(),
             Severity::Info => Diagnostic::note(),
         };
-
-        // notify output about this
-        file_context.send_processing_output(ProcessingEvent::Report(result.severity));
+        // the location to sort the diagnostics by
+        let range = result
+            .primary
+            .as_ref()
+            .map(|primary| primary.syntax_range)
+            .or_else(|| result.secondary.first().map(|note| note.syntax_range))
+            .or_else(|| {
+                result
+                    .suggestions
+                    .first()
+                    .map(CheckSuggestion::syntax_range)
+            })
+            .unwrap_or_default();
 
         let mut labels = vec![];
         if let Some(primary) = result.primary {
@@ -244,12 +487,25 @@ pub fn produce_diagnostics(
         }
 
         for suggestion in result.suggestions {
-            labels.push(
-                Label::secondary(file_id, suggestion.syntax_range).with_message(format!(
-                    "{}: {}",
-                    suggestion.message, suggestion.replace_with
-                )),
-            );
+            let unsafe_hint = match suggestion.applicability {
+                Applicability::Safe => "",
+                Applicability::Unsafe => " (unsafe fix)",
+            };
+            let edit_count = suggestion.edits.len();
+            for (i, edit) in suggestion.edits.into_iter().enumerate() {
+                // edits of the same suggestion are numbered to show they belong together
+                let group = if edit_count > 1 {
+                    format!(" ({}/{edit_count})", i + 1)
+                } else {
+                    String::new()
+                };
+                labels.push(
+                    Label::secondary(file_id, edit.syntax_range).with_message(format!(
+                        "{}{unsafe_hint}{group}: {}",
+                        suggestion.message, edit.replace_with
+                    )),
+                );
+            }
         }
 
         let diagnostic = diagnostic
@@ -257,6 +513,13 @@ pub fn produce_diagnostics(
             .with_message(result.message)
             .with_labels(labels);
 
-        term::emit(buffer, &config, &files, &diagnostic).unwrap();
+        // notify output about this
+        cli_context.send_processing_output(ProcessingEvent::Diagnostic(FileDiagnostic::new(
+            file_path,
+            range,
+            result.severity,
+            &files,
+            diagnostic,
+        )));
     }
 }
@@ -0,0 +1,253 @@
+use std::sync::Arc;
+
+use ludtwig_parser::syntax::untyped::{SyntaxElement, SyntaxNode, SyntaxToken};
+
+use crate::check::query::{element_range, element_text, render_template, Captures, Query};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+use crate::check::rules::RULE_DEFINITIONS;
+use crate::config::CustomRule;
+use crate::error::ConfigurationError;
+
+/// A rule of the config (`[[custom-rules]]`), which reports every element matched by its [`Query`]
+/// and optionally replaces it.
+#[derive(Debug)]
+pub struct QueryRule {
+    name: String,
+    query: Query,
+    severity: Severity,
+    message: String,
+    /// Replaces the matched element, where `$capture` is replaced by the text of the captured element
+    replacement: Option<String>,
+}
+
+/// Create the rules of the `[[custom-rules]]` in the config, `existing` are the other rules of the config.
+pub fn load_query_rules(
+    custom_rules: &[CustomRule],
+    existing: &[Arc<dyn Rule>],
+) -> Result<Vec<Arc<dyn Rule>>, ConfigurationError> {
+    let mut rules: Vec<Arc<dyn Rule>> = vec![];
+    for custom_rule in custom_rules {
+        let invalid = |message: String| ConfigurationError::CustomRule {
+            name: custom_rule.name.clone(),
+            message,
+        };
+        if RULE_DEFINITIONS
+            .iter()
+            .chain(existing)
+            .chain(&rules)
+            .any(|r| r.name() == custom_rule.name)
+        {
+            return Err(invalid(
+                "there is already a rule with this name".to_string(),
+            ));
+        }
+
+        let query = Query::parse(&custom_rule.query).map_err(|e| invalid(format!("query: {e}")))?;
+        let capture_names = query.capture_names();
+        let templates = [Some(&custom_rule.message), custom_rule.replacement.as_ref()];
+        for template in templates.into_iter().flatten() {
+            if let Some(unknown) = placeholders(template)
+                .into_iter()
+                .find(|p| !capture_names.contains(p.as_str()))
+            {
+                return Err(invalid(format!(
+                    "${unknown} is not captured by the query (with @{unknown})"
+                )));
+            }
+        }
+
+        rules.push(Arc::new(QueryRule {
+            name: custom_rule.name.clone(),
+            query,
+            severity: custom_rule.severity.corresponding_severity(),
+            message: custom_rule.message.clone(),
+            replacement: custom_rule.replacement.clone(),
+        }));
+    }
+
+    Ok(rules)
+}
+
+impl QueryRule {
+    fn check(&self, element: &SyntaxElement) -> Option<Vec<CheckResult>> {
+        let captures = self.query.matches(element)?;
+        // only the outermost match is reported, the ones inside are matched again after replacing it
+        if element
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.ancestors())
+            .any(|ancestor| self.query.matches(&ancestor.into()).is_some())
+        {
+            return None;
+        }
+        let range = element_range(element);
+
+        let mut result = self
+            .create_result(self.severity, interpolate(&self.message, &captures))
+            .primary_note(range, "matched by the query of the rule");
+        if let Some(replacement) = &self.replacement {
+            result = result.suggestion(
+                range,
+                interpolate(replacement, &captures),
+                "Try this instead",
+            );
+        }
+
+        Some(vec![result])
+    }
+}
+
+impl Rule for QueryRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: &self.message,
+            rationale: "",
+            category: RuleCategory::Custom,
+            fixable: self.replacement.is_some(),
+            config_keys: &[],
+            bad_example: "",
+            good_example: "",
+        }
+    }
+
+    fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        self.check(&node.into())
+    }
+
+    fn check_token(&self, token: SyntaxToken, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        self.check(&token.into())
+    }
+}
+
+/// Replace the `$capture` placeholders in the template with the text of the captured elements
+/// (or nothing if the element was not captured). `$$` is a literal `$`.
+fn interpolate(template: &str, captures: &Captures) -> String {
+    render_template(template, |name| {
+        captures
+            .iter()
+            .find(|(captured, _)| captured == name)
+            .map(|(_, element)| element_text(element))
+            .unwrap_or_default()
+    })
+}
+
+/// The names of the `$capture` placeholders in the template.
+fn placeholders(template: &str) -> Vec<String> {
+    let mut names = vec![];
+    render_template(template, |name| {
+        names.push(name.to_string());
+        String::new()
+    });
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use expect_test::expect;
+
+    use crate::config::{Config, CustomRule, CustomRuleSeverity, DEFAULT_CONFIG_PATH};
+    use crate::linter::Linter;
+
+    fn linter(custom_rules: Vec<CustomRule>) -> Linter {
+        let mut config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        config.general.active_rules = vec![];
+        config.custom_rules = custom_rules;
+        Linter::new(config).unwrap()
+    }
+
+    fn no_raw_filter() -> CustomRule {
+        CustomRule {
+            name: "twig-no-raw".to_string(),
+            query: r#"TWIG_FILTER:has(> TWIG_OPERAND:first-child @value):has(> TWIG_OPERAND:last-child[text="raw"])"#.to_string(),
+            message: "Don't print $value without escaping".to_string(),
+            severity: CustomRuleSeverity::Warning,
+            replacement: Some("$value".to_string()),
+        }
+    }
+
+    #[test]
+    fn custom_rule_reports_and_replaces_matches() {
+        let linter = linter(vec![no_raw_filter()]);
+        let path = Path::new("a.html.twig");
+        let source_code = "{{ title|raw }}\n{# ludtwig-ignore twig-no-raw #}\n{{ html|raw }}\n";
+
+        let diagnostics: Vec<String> = linter
+            .check_source(path, source_code)
+            .into_iter()
+            .map(|d| format!("{}:{} {} {}", d.line, d.column, d.code, d.message))
+            .collect();
+        expect![[r#"
+            [
+                "1:4 twig-no-raw Don't print title without escaping",
+            ]
+        "#]]
+        .assert_debug_eq(&diagnostics);
+
+        let fixed = linter.fix_source(path, source_code).unwrap();
+        expect![[r"
+            {{ title }}
+            {# ludtwig-ignore twig-no-raw #}
+            {{ html|raw }}
+        "]]
+        .assert_eq(&fixed.source_code);
+    }
+
+    #[test]
+    fn custom_rule_only_replaces_outermost_match() {
+        let linter = linter(vec![no_raw_filter()]);
+        let path = Path::new("a.html.twig");
+        let source_code = "{{ a|raw|raw }}\n";
+
+        let diagnostics: Vec<String> = linter
+            .check_source(path, source_code)
+            .into_iter()
+            .map(|d| format!("{}:{} {} {}", d.line, d.column, d.code, d.message))
+            .collect();
+        expect![[r#"
+            [
+                "1:4 twig-no-raw Don't print a|raw without escaping",
+            ]
+        "#]]
+        .assert_debug_eq(&diagnostics);
+
+        let fixed = linter.fix_source(path, source_code).unwrap();
+        assert_eq!(fixed.source_code, "{{ a }}\n");
+    }
+
+    #[test]
+    fn invalid_custom_rules_are_config_errors() {
+        let error = |custom_rule: CustomRule| {
+            let mut config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+            config.custom_rules = vec![custom_rule];
+            Linter::new(config).unwrap_err().to_string()
+        };
+
+        expect![
+            "custom rule 'twig-no-raw' is invalid: query: expected ']' at the end of the query"
+        ]
+        .assert_eq(&error(CustomRule {
+            query: "TWIG_FILTER[text='a'".to_string(),
+            ..no_raw_filter()
+        }));
+        expect![
+            "custom rule 'twig-no-raw' is invalid: $html is not captured by the query (with @html)"
+        ]
+        .assert_eq(&error(CustomRule {
+            replacement: Some("$html".to_string()),
+            ..no_raw_filter()
+        }));
+        expect!["custom rule 'indentation' is invalid: there is already a rule with this name"]
+            .assert_eq(&error(CustomRule {
+                name: "indentation".to_string(),
+                ..no_raw_filter()
+            }));
+    }
+}
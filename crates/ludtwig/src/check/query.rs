@@ -0,0 +1,695 @@
+use std::collections::BTreeSet;
+
+use ludtwig_parser::syntax::typed::{
+    AstNode, HtmlAttribute, HtmlStartingTag, HtmlTag, TwigBlock, TwigEndingBlock,
+    TwigLiteralString, TwigStartingBlock,
+};
+use ludtwig_parser::syntax::untyped::{
+    SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, TextRange,
+};
+use regex::Regex;
+
+/// A query which matches elements of the syntax tree, similar to CSS selectors over the [`SyntaxKind`]s.
+///
+/// ```text
+/// TWIG_FILTER:has(> TWIG_OPERAND:first-child @value):has(> TWIG_OPERAND:last-child[text="raw"])
+/// HTML_TAG[name="font"], HTML_TAG[name="center"]
+/// HTML_STARTING_TAG:has(HTML_ATTRIBUTE[name="target"][value="_blank"]):not(:has(HTML_ATTRIBUTE[name="rel"][value~="noopener"]))
+/// ```
+///
+/// - `KIND` or `*` matches an element (node or token) of that kind or of any kind
+/// - `A B` matches `B` inside of `A` and `A > B` matches `B` which is a direct child of `A`
+/// - `[text="..."]`, `[name="..."]` and `[value="..."]` compare the text of the element (without surrounding
+///   whitespace), the name of blocks, html tags and attributes or the value of html attributes and twig strings.
+///   The operators are `=`, `!=`, `^=` (starts with), `$=` (ends with), `*=` (contains) and `~=` (contains the word)
+///   and `[name]` only checks that there is a name. `[text=/regex/]` matches a regular expression.
+/// - `:has(...)` matches if an element inside matches (`:has(> ...)` for direct children), `:not(...)` if the
+///   element doesn't match and `:first-child` / `:last-child` if the element is the first or last child
+///   (ignoring whitespace)
+/// - `@name` after a compound captures its element, so the text can be used in messages and replacements
+/// - `A, B` matches elements which match `A` or `B`
+#[derive(Debug)]
+pub struct Query {
+    selectors: Vec<Selector>,
+}
+
+/// Captured elements by their name (in the order they were captured).
+pub type Captures = Vec<(String, SyntaxElement)>;
+
+#[derive(Debug)]
+struct Selector {
+    /// The compounds with the combinator to the previous one (or for the first one to the element of `:has`).
+    compounds: Vec<(Combinator, Compound)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+#[derive(Debug)]
+struct Compound {
+    /// `None` matches any kind
+    kind: Option<SyntaxKind>,
+    filters: Vec<Filter>,
+    capture: Option<String>,
+}
+
+#[derive(Debug)]
+enum Filter {
+    Property(Property, Operator),
+    Has(Vec<Selector>),
+    Not(Vec<Selector>),
+    FirstChild,
+    LastChild,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Text,
+    Name,
+    Value,
+}
+
+#[derive(Debug)]
+enum Operator {
+    Exists,
+    Equals(String),
+    NotEquals(String),
+    StartsWith(String),
+    EndsWith(String),
+    Contains(String),
+    ContainsWord(String),
+    Matches(Regex),
+    NotMatches(Regex),
+}
+
+impl Query {
+    /// Parse the query, the error message contains the position of the invalid part.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parser = QueryParser {
+            source,
+            position: 0,
+        };
+        let selectors = parser.selector_list(false)?;
+        parser.skip_whitespace();
+        if let Some(c) = parser.peek() {
+            return Err(parser.error(&format!("unexpected '{c}'")));
+        }
+
+        Ok(Self { selectors })
+    }
+
+    /// The captures of the query if it matches the element.
+    pub fn matches(&self, element: &SyntaxElement) -> Option<Captures> {
+        let mut captures = vec![];
+        self.selectors
+            .iter()
+            .any(|selector| selector.matches(element, None, &mut captures))
+            .then_some(captures)
+    }
+
+    /// Names of all captures in the query.
+    pub fn capture_names(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        collect_capture_names(&self.selectors, &mut names);
+        names
+    }
+}
+
+fn collect_capture_names<'a>(selectors: &'a [Selector], names: &mut BTreeSet<&'a str>) {
+    for (_, compound) in selectors.iter().flat_map(|s| &s.compounds) {
+        names.extend(compound.capture.as_deref());
+        for filter in &compound.filters {
+            if let Filter::Has(inner) = filter {
+                collect_capture_names(inner, names);
+            }
+        }
+    }
+}
+
+impl Selector {
+    /// Does the element match the last compound and the ones before it match its ancestors?
+    /// With an `anchor` (for `:has`) the first compound must also be inside of the anchor.
+    fn matches(
+        &self,
+        element: &SyntaxElement,
+        anchor: Option<&SyntaxNode>,
+        captures: &mut Captures,
+    ) -> bool {
+        self.matches_from(self.compounds.len() - 1, element, anchor, captures)
+    }
+
+    fn matches_from(
+        &self,
+        index: usize,
+        element: &SyntaxElement,
+        anchor: Option<&SyntaxNode>,
+        captures: &mut Captures,
+    ) -> bool {
+        let rollback_len = captures.len();
+        let (combinator, compound) = &self.compounds[index];
+        let parent = element.parent();
+
+        let matches =
+            compound.matches(element, captures)
+                && match (index, combinator) {
+                    (0, Combinator::Child) => {
+                        anchor.map_or(true, |anchor| parent.as_ref() == Some(anchor))
+                    }
+                    (0, Combinator::Descendant) => anchor.map_or(true, |anchor| {
+                        parent
+                            .iter()
+                            .flat_map(SyntaxNode::ancestors)
+                            .any(|ancestor| &ancestor == anchor)
+                    }),
+                    (_, Combinator::Child) => parent.is_some_and(|parent| {
+                        self.matches_from(index - 1, &parent.into(), anchor, captures)
+                    }),
+                    (_, Combinator::Descendant) => parent
+                        .iter()
+                        .flat_map(SyntaxNode::ancestors)
+                        .any(|ancestor| {
+                            self.matches_from(index - 1, &ancestor.into(), anchor, captures)
+                        }),
+                };
+
+        if !matches {
+            captures.truncate(rollback_len);
+        }
+        matches
+    }
+}
+
+impl Compound {
+    fn matches(&self, element: &SyntaxElement, captures: &mut Captures) -> bool {
+        if self.kind.is_some_and(|kind| kind != element.kind()) {
+            return false;
+        }
+
+        let filters_match = self
+            .filters
+            .iter()
+            .all(|filter| filter.matches(element, captures));
+        if filters_match {
+            if let Some(name) = &self.capture {
+                captures.push((name.clone(), element.clone()));
+            }
+        }
+        filters_match
+    }
+}
+
+impl Filter {
+    fn matches(&self, element: &SyntaxElement, captures: &mut Captures) -> bool {
+        match self {
+            Filter::Property(property, operator) => {
+                let value = match property {
+                    Property::Text => Some(element_text(element)),
+                    Property::Name => element
+                        .as_node()
+                        .and_then(typed_name)
+                        .map(|name| name.text().to_string()),
+                    Property::Value => element
+                        .as_node()
+                        .and_then(typed_value)
+                        .map(|value| value.text().to_string()),
+                };
+                operator.matches(value.as_deref())
+            }
+            Filter::Has(selectors) => {
+                let Some(anchor) = element.as_node() else {
+                    return false;
+                };
+                anchor.descendants_with_tokens().skip(1).any(|descendant| {
+                    selectors
+                        .iter()
+                        .any(|selector| selector.matches(&descendant, Some(anchor), captures))
+                })
+            }
+            Filter::Not(selectors) => {
+                // captures of the negated selectors are never used
+                let mut discarded = vec![];
+                !selectors
+                    .iter()
+                    .any(|selector| selector.matches(element, None, &mut discarded))
+            }
+            Filter::FirstChild => significant_siblings(element).next().as_ref() == Some(element),
+            Filter::LastChild => significant_siblings(element).last().as_ref() == Some(element),
+        }
+    }
+}
+
+impl Operator {
+    fn matches(&self, value: Option<&str>) -> bool {
+        let Some(value) = value else {
+            return matches!(self, Operator::NotEquals(_) | Operator::NotMatches(_));
+        };
+
+        match self {
+            Operator::Exists => !value.is_empty(),
+            Operator::Equals(expected) => value == expected,
+            Operator::NotEquals(expected) => value != expected,
+            Operator::StartsWith(expected) => value.starts_with(expected.as_str()),
+            Operator::EndsWith(expected) => value.ends_with(expected.as_str()),
+            Operator::Contains(expected) => value.contains(expected.as_str()),
+            Operator::ContainsWord(expected) => value.split_whitespace().any(|w| w == expected),
+            Operator::Matches(regex) => regex.is_match(value),
+            Operator::NotMatches(regex) => !regex.is_match(value),
+        }
+    }
+}
+
+/// The children of the parent of the element, without whitespace and line breaks.
+fn significant_siblings(element: &SyntaxElement) -> impl Iterator<Item = SyntaxElement> {
+    element
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.children_with_tokens())
+        .filter(|sibling| !sibling.kind().is_trivia())
+}
+
+/// The text of the element without surrounding whitespace and line breaks.
+pub fn element_text(element: &SyntaxElement) -> String {
+    match element {
+        SyntaxElement::Node(node) => node.text().to_string().trim().to_string(),
+        SyntaxElement::Token(token) => token.text().trim().to_string(),
+    }
+}
+
+/// The range of the element without its leading whitespace and line breaks.
+pub fn element_range(element: &SyntaxElement) -> TextRange {
+    let range = element.text_range();
+    let SyntaxElement::Node(node) = element else {
+        return range;
+    };
+    let start = node
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .find(|token| !token.kind().is_trivia())
+        .map_or(range.start(), |token| token.text_range().start());
+
+    TextRange::new(start, range.end())
+}
+
+/// The name token of blocks, html tags and attributes.
+pub fn typed_name(node: &SyntaxNode) -> Option<SyntaxToken> {
+    let node = node.clone();
+    TwigBlock::cast(node.clone())
+        .and_then(|n| n.name())
+        .or_else(|| TwigStartingBlock::cast(node.clone()).and_then(|n| n.name()))
+        .or_else(|| TwigEndingBlock::cast(node.clone()).and_then(|n| n.name()))
+        .or_else(|| HtmlTag::cast(node.clone()).and_then(|n| n.name()))
+        .or_else(|| HtmlStartingTag::cast(node.clone()).and_then(|n| n.name()))
+        .or_else(|| HtmlAttribute::cast(node).and_then(|n| n.name()))
+}
+
+/// The inner string node of html attribute values and twig strings (without the quotes).
+pub fn typed_value(node: &SyntaxNode) -> Option<SyntaxNode> {
+    HtmlAttribute::cast(node.clone())
+        .and_then(|attribute| attribute.value()?.get_inner())
+        .map(|inner| inner.syntax().clone())
+        .or_else(|| {
+            TwigLiteralString::cast(node.clone())
+                .and_then(|string| string.get_inner())
+                .map(|inner| inner.syntax().clone())
+        })
+}
+
+/// Replace the `$name` placeholders in the template with the text returned by `placeholder` for the name.
+/// `$$` is a literal `$`.
+pub fn render_template(template: &str, mut placeholder: impl FnMut(&str) -> String) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('$') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(after) = after.strip_prefix('$') {
+            rendered.push('$');
+            rest = after;
+            continue;
+        }
+
+        let end = after
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(after.len());
+        if end == 0 {
+            rendered.push('$');
+        } else {
+            rendered.push_str(&placeholder(&after[..end]));
+        }
+        rest = &after[end..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+struct QueryParser<'a> {
+    source: &'a str,
+    /// Byte offset of the next character
+    position: usize,
+}
+
+impl<'a> QueryParser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let found = self.source[self.position..].starts_with(expected);
+        if found {
+            self.position += expected.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{expected}'")))
+        }
+    }
+
+    /// Returns whether there was any whitespace.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+        self.position > start
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.peek() {
+            Some(_) => format!("{message} at position {}", self.position + 1),
+            None => format!("{message} at the end of the query"),
+        }
+    }
+
+    fn identifier(&mut self) -> &'a str {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            self.bump();
+        }
+        &self.source[start..self.position]
+    }
+
+    fn selector_list(&mut self, relative: bool) -> Result<Vec<Selector>, String> {
+        let mut selectors = vec![self.selector(relative)?];
+        while self.eat(",") {
+            selectors.push(self.selector(relative)?);
+        }
+        Ok(selectors)
+    }
+
+    /// A selector, which may start with `>` if it is `relative` to the element of `:has`.
+    fn selector(&mut self, relative: bool) -> Result<Selector, String> {
+        self.skip_whitespace();
+        let mut combinator = Combinator::Descendant;
+        if relative && self.eat(">") {
+            combinator = Combinator::Child;
+            self.skip_whitespace();
+        }
+
+        let mut compounds = vec![(combinator, self.compound()?)];
+        loop {
+            let whitespace = self.skip_whitespace();
+            match self.peek() {
+                Some('>') => {
+                    self.bump();
+                    self.skip_whitespace();
+                    compounds.push((Combinator::Child, self.compound()?));
+                }
+                // a capture after whitespace still belongs to the previous compound
+                Some('@') => {
+                    self.bump();
+                    let capture = self.capture_name()?;
+                    if let Some((_, compound)) = compounds.last_mut() {
+                        compound.capture = Some(capture);
+                    }
+                }
+                Some(',' | ')') | None => break,
+                Some(_) if whitespace => compounds.push((Combinator::Descendant, self.compound()?)),
+                Some(c) => return Err(self.error(&format!("unexpected '{c}'"))),
+            }
+        }
+
+        Ok(Selector { compounds })
+    }
+
+    fn compound(&mut self) -> Result<Compound, String> {
+        let start = self.position;
+        let kind = if self.eat("*") {
+            None
+        } else {
+            let name = self.identifier();
+            if name.is_empty() {
+                None
+            } else {
+                Some(syntax_kind(name).ok_or_else(|| {
+                    self.position = start;
+                    self.error(&format!("unknown syntax kind '{name}'"))
+                })?)
+            }
+        };
+
+        let mut filters = vec![];
+        let mut capture = None;
+        loop {
+            if self.eat("[") {
+                filters.push(self.property_filter()?);
+            } else if self.eat(":") {
+                filters.push(self.pseudo_filter()?);
+            } else if self.eat("@") {
+                capture = Some(self.capture_name()?);
+            } else {
+                break;
+            }
+        }
+
+        if self.position == start {
+            return Err(self.error("expected a syntax kind, '*', '[', ':' or '@'"));
+        }
+        Ok(Compound {
+            kind,
+            filters,
+            capture,
+        })
+    }
+
+    fn capture_name(&mut self) -> Result<String, String> {
+        let name = self.identifier();
+        if name.is_empty() || name.contains('-') {
+            return Err(self.error("expected the name of the capture (letters, digits and '_')"));
+        }
+        Ok(name.to_string())
+    }
+
+    fn property_filter(&mut self) -> Result<Filter, String> {
+        self.skip_whitespace();
+        let property = match self.identifier() {
+            "text" => Property::Text,
+            "name" => Property::Name,
+            "value" => Property::Value,
+            name => {
+                return Err(self.error(&format!(
+                    "unknown property '{name}', use 'text', 'name' or 'value'"
+                )));
+            }
+        };
+        self.skip_whitespace();
+        if self.eat("]") {
+            return Ok(Filter::Property(property, Operator::Exists));
+        }
+
+        let operator = ["!=", "^=", "$=", "*=", "~=", "="]
+            .into_iter()
+            .find(|operator| self.eat(operator))
+            .ok_or_else(|| self.error("expected ']' or an operator like '='"))?;
+        self.skip_whitespace();
+
+        let operator = if self.peek() == Some('/') {
+            let pattern = self.delimited('/')?;
+            let regex = Regex::new(&pattern)
+                .map_err(|e| self.error(&format!("invalid regular expression ({e})")))?;
+            match operator {
+                "=" => Operator::Matches(regex),
+                "!=" => Operator::NotMatches(regex),
+                _ => return Err(self.error("regular expressions only work with '=' and '!='")),
+            }
+        } else {
+            let value = match self.peek() {
+                Some(quote @ ('"' | '\'')) => self.delimited(quote)?,
+                _ => return Err(self.error("expected a quoted string or /regex/")),
+            };
+            match operator {
+                "=" => Operator::Equals(value),
+                "!=" => Operator::NotEquals(value),
+                "^=" => Operator::StartsWith(value),
+                "$=" => Operator::EndsWith(value),
+                "*=" => Operator::Contains(value),
+                _ => Operator::ContainsWord(value),
+            }
+        };
+
+        self.skip_whitespace();
+        self.expect("]")?;
+        Ok(Filter::Property(property, operator))
+    }
+
+    /// The content between two delimiters, where `\` escapes the delimiter.
+    fn delimited(&mut self, delimiter: char) -> Result<String, String> {
+        self.bump();
+        let mut content = String::new();
+        loop {
+            match self.bump() {
+                Some('\\') if self.peek() == Some(delimiter) => {
+                    content.push(delimiter);
+                    self.bump();
+                }
+                Some(c) if c == delimiter => return Ok(content),
+                Some(c) => content.push(c),
+                None => return Err(self.error(&format!("expected closing {delimiter}"))),
+            }
+        }
+    }
+
+    fn pseudo_filter(&mut self) -> Result<Filter, String> {
+        let start = self.position;
+        match self.identifier() {
+            "has" => {
+                self.expect("(")?;
+                let selectors = self.selector_list(true)?;
+                self.expect(")")?;
+                Ok(Filter::Has(selectors))
+            }
+            "not" => {
+                self.expect("(")?;
+                let selectors = self.selector_list(false)?;
+                self.expect(")")?;
+                Ok(Filter::Not(selectors))
+            }
+            "first-child" => Ok(Filter::FirstChild),
+            "last-child" => Ok(Filter::LastChild),
+            name => {
+                self.position = start;
+                Err(self.error(&format!(
+                    "unknown pseudo class ':{name}', use ':has(...)', ':not(...)', ':first-child' or ':last-child'"
+                )))
+            }
+        }
+    }
+}
+
+/// Find the kind by its name (like `TWIG_BLOCK`).
+fn syntax_kind(name: &str) -> Option<SyntaxKind> {
+    (0..=u16::MAX)
+        .map_while(|raw| SyntaxKind::try_from(raw).ok())
+        .find(|kind| format!("{kind:?}") == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use expect_test::{expect, Expect};
+    use ludtwig_parser::syntax::untyped::SyntaxNode;
+
+    use super::{element_text, Query};
+
+    /// Every matched element with its captures, one per line.
+    #[allow(clippy::needless_pass_by_value)]
+    fn check_query(query: &str, source_code: &str, expected: Expect) {
+        let query = Query::parse(query).unwrap();
+        let root = SyntaxNode::new_root(ludtwig_parser::parse(source_code).green_node);
+
+        let mut matched = String::new();
+        for element in root.descendants_with_tokens() {
+            if let Some(captures) = query.matches(&element) {
+                write!(matched, "{:?} {:?}", element.kind(), element_text(&element)).unwrap();
+                for (name, captured) in captures {
+                    write!(matched, " @{name}={:?}", element_text(&captured)).unwrap();
+                }
+                matched.push('\n');
+            }
+        }
+        expected.assert_eq(&matched);
+    }
+
+    #[test]
+    fn query_kinds_and_properties() {
+        check_query(
+            r#"HTML_TAG[name="font"], HTML_TAG[name="center"]"#,
+            "<font>a</font><div><center>b</center></div>",
+            expect![[r#"
+                HTML_TAG "<font>a</font>"
+                HTML_TAG "<center>b</center>"
+            "#]],
+        );
+        check_query(
+            r#"HTML_ATTRIBUTE[name^="data-"][value~="b"] @attribute"#,
+            r#"<div data-a="a b" data-c="bc" class="b"></div>"#,
+            expect![[r#"
+                HTML_ATTRIBUTE "data-a=\"a b\"" @attribute="data-a=\"a b\""
+            "#]],
+        );
+        check_query(
+            "TK_WORD[text=/^[A-Z]+$/]",
+            "<p>Hello WORLD</p>",
+            expect![[r#"
+                TK_WORD "WORLD"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn query_combinators_and_pseudo_classes() {
+        check_query(
+            r#"TWIG_FILTER:has(> TWIG_OPERAND:first-child @value):has(> TWIG_OPERAND:last-child[text="raw"])"#,
+            "{{ a|raw }}{{ b|upper }}{{ c.d|raw }}",
+            expect![[r#"
+                TWIG_FILTER "a|raw" @value="a"
+                TWIG_FILTER "c.d|raw" @value="c.d"
+            "#]],
+        );
+        check_query(
+            r#"HTML_STARTING_TAG:has(HTML_ATTRIBUTE[name="target"][value="_blank"]):not(:has(HTML_ATTRIBUTE[name="rel"][value~="noopener"]))"#,
+            r#"<a target="_blank">a</a><a target="_blank" rel="noopener noreferrer">b</a><a>c</a>"#,
+            expect![[r#"
+                HTML_STARTING_TAG "<a target=\"_blank\">"
+            "#]],
+        );
+        check_query(
+            r#"TWIG_BLOCK[name="outer"] > BODY > TWIG_BLOCK @inner"#,
+            "{% block outer %}{% block a %}{% block b %}{% endblock %}{% endblock %}{% endblock %}",
+            expect![[r#"
+                TWIG_BLOCK "{% block a %}{% block b %}{% endblock %}{% endblock %}" @inner="{% block a %}{% block b %}{% endblock %}{% endblock %}"
+            "#]],
+        );
+    }
+
+    #[test]
+    fn query_syntax_errors() {
+        let error = |query: &str| Query::parse(query).unwrap_err();
+        expect!["unknown syntax kind 'HTML_TAGG' at position 1"].assert_eq(&error("HTML_TAGG"));
+        expect!["unknown property 'id', use 'text', 'name' or 'value' at position 12"]
+            .assert_eq(&error("HTML_TAG[id='a']"));
+        expect!["expected ']' at the end of the query"].assert_eq(&error("HTML_TAG[name='a'"));
+        expect!["regular expressions only work with '=' and '!=' at position 19"]
+            .assert_eq(&error("HTML_TAG[name^=/a/]"));
+        expect!["unknown pseudo class ':empty', use ':has(...)', ':not(...)', ':first-child' or ':last-child' at position 10"]
+            .assert_eq(&error("HTML_TAG:empty"));
+        expect!["unexpected ')' at position 9"].assert_eq(&error("HTML_TAG)"));
+    }
+}
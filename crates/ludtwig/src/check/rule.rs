@@ -1,11 +1,19 @@
-use crate::{CliSharedData, Config};
+use crate::check::FileDependencies;
+use crate::config::ActiveConfig;
+use crate::project::TemplateReferenceKind;
+use crate::Config;
 use ludtwig_parser::syntax::untyped::{SyntaxNode, SyntaxToken, TextRange};
-use std::fmt::{Debug, Formatter};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Debug, Display, Formatter};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-pub trait Rule: Sync {
+pub trait Rule: Send + Sync {
     /// A unique, kebab-case name for the rule.
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
+
+    /// Documentation of the rule, which is displayed by `ludtwig rules` and `ludtwig explain <rule>`.
+    fn metadata(&self) -> RuleMetadata<'_>;
 
     /// Check an individual untyped node in the syntax tree.
     /// The conversion to a typed AST node can be made at any time with a simple call to cast.
@@ -59,7 +67,7 @@ pub trait RuleExt: Rule {
 impl<R: Rule> RuleExt for R {
     fn create_result<S: Into<String>>(&self, severity: Severity, message: S) -> CheckResult {
         CheckResult {
-            rule_name: self.name(),
+            rule_name: self.name().to_string(),
             severity,
             message: message.into(),
             primary: None,
@@ -75,6 +83,53 @@ impl Debug for dyn Rule {
     }
 }
 
+/// Documentation of a rule for the user.
+#[derive(Debug)]
+pub struct RuleMetadata<'a> {
+    /// What the rule checks in a single sentence.
+    pub description: &'a str,
+    /// Why the reported code is a problem.
+    pub rationale: &'a str,
+    pub category: RuleCategory,
+    /// Does the rule provide suggestions which can be applied with `--fix`?
+    pub fixable: bool,
+    /// Config keys (like `format.indentation-count`) which change the behaviour of the rule.
+    pub config_keys: &'a [&'a str],
+    /// Code which is reported by the rule with the default config.
+    pub bad_example: &'a str,
+    /// The same code, written in a way which is not reported by the rule with the default config.
+    pub good_example: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleCategory {
+    /// Code which doesn't work as intended
+    Correctness,
+    /// Formatting and code style
+    Style,
+    /// Naming conventions
+    Naming,
+    /// Conventions of the shopware platform
+    Shopware,
+    /// Usage of ludtwig itself (like directives)
+    Ludtwig,
+    /// Rules of the project (like script rules)
+    Custom,
+}
+
+impl Display for RuleCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleCategory::Correctness => write!(f, "correctness"),
+            RuleCategory::Style => write!(f, "style"),
+            RuleCategory::Naming => write!(f, "naming"),
+            RuleCategory::Shopware => write!(f, "shopware"),
+            RuleCategory::Ludtwig => write!(f, "ludtwig"),
+            RuleCategory::Custom => write!(f, "custom"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TreeTraversalContext {
     pub inside_trivia_sensitive_node: bool,
@@ -84,24 +139,52 @@ pub struct TreeTraversalContext {
 pub struct RuleRunContext {
     // file_id
     // source_text
-    pub(super) cli_data: Arc<CliSharedData>,
+    pub(super) active_config: Arc<ActiveConfig>,
+    pub(super) file_path: PathBuf,
     pub(super) traversal_ctx: TreeTraversalContext,
+    pub(super) dependencies: FileDependencies,
 }
 
 impl RuleRunContext {
+    #[must_use]
     pub fn config(&self) -> &Config {
-        &self.cli_data.config
+        &self.active_config.config
     }
 
+    #[must_use]
     pub fn traversal_ctx(&self) -> &TreeTraversalContext {
         &self.traversal_ctx
     }
+
+    /// Record that the results of the rule depend on the content of another file (like a template it extends).
+    /// Cached results of the checked file are invalidated when that file changes.
+    pub fn add_dependency(&self, path: PathBuf) {
+        self.dependencies.add(path);
+    }
+
+    /// The path of the checked file.
+    #[must_use]
+    pub fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    /// Find the file of a template name referenced by a tag of the kind in the checked file
+    /// (using the `templates` config). Every file which was looked at is added as a dependency,
+    /// so creating a template which would be found instead also invalidates the cached results.
+    #[must_use]
+    pub fn resolve_template(&self, name: &str, kind: TemplateReferenceKind) -> Option<PathBuf> {
+        self.active_config
+            .project()
+            .resolve_probing(name, kind, &self.file_path, |candidate| {
+                self.add_dependency(candidate.to_owned());
+            })
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckResult {
     // file_id
-    pub(super) rule_name: &'static str,
+    pub(super) rule_name: String,
     pub(super) severity: Severity,
     pub(super) message: String,
     pub(super) primary: Option<CheckNote>,
@@ -110,9 +193,40 @@ pub struct CheckResult {
 }
 
 impl CheckResult {
+    #[must_use]
+    pub fn rule_name(&self) -> &str {
+        &self.rule_name
+    }
+
+    #[must_use]
+    pub fn suggestions(&self) -> &[CheckSuggestion] {
+        &self.suggestions
+    }
+
+    #[must_use]
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    #[must_use]
+    pub fn primary(&self) -> Option<&CheckNote> {
+        self.primary.as_ref()
+    }
+
+    #[must_use]
+    pub fn secondary(&self) -> &[CheckNote] {
+        &self.secondary
+    }
+
     // TODO: enforce only one primary_note call via type builder pattern
     /// The primary (red) label and location of the error, there should be only one of these per check result.
     /// Further context can be provided with multiple secondary notes.
+    #[must_use]
     pub fn primary_note<S: Into<String>>(mut self, syntax_range: TextRange, message: S) -> Self {
         self.primary = Some(CheckNote {
             syntax_range,
@@ -122,6 +236,7 @@ impl CheckResult {
     }
 
     /// The secondary (blue) label which can provide more context and explain the error to a user.
+    #[must_use]
     pub fn secondary_note<S: Into<String>>(mut self, syntax_range: TextRange, message: S) -> Self {
         self.secondary.push(CheckNote {
             syntax_range,
@@ -131,6 +246,7 @@ impl CheckResult {
     }
 
     /// Add a code suggestion which the user can follow or is replaced automatically
+    #[must_use]
     pub fn suggestion<R: Into<String>, S: Into<String>>(
         mut self,
         syntax_range: TextRange,
@@ -138,28 +254,105 @@ impl CheckResult {
         message: S,
     ) -> Self {
         self.suggestions.push(CheckSuggestion {
-            syntax_range,
-            replace_with: replace_with.into(),
+            edits: vec![TextEdit {
+                syntax_range,
+                replace_with: replace_with.into(),
+            }],
+            message: message.into(),
+            applicability: Applicability::Safe,
+        });
+        self
+    }
+
+    /// Add a code suggestion which changes multiple places at once. All the edits are applied together or not at all
+    /// and must not overlap each other.
+    #[must_use]
+    pub fn multi_edit_suggestion<I, R, S>(mut self, edits: I, message: S) -> Self
+    where
+        I: IntoIterator<Item = (TextRange, R)>,
+        R: Into<String>,
+        S: Into<String>,
+    {
+        let mut edits: Vec<TextEdit> = edits
+            .into_iter()
+            .map(|(syntax_range, replace_with)| TextEdit {
+                syntax_range,
+                replace_with: replace_with.into(),
+            })
+            .collect();
+        edits.sort_by_key(|e| (e.syntax_range.start(), e.syntax_range.end()));
+
+        self.suggestions.push(CheckSuggestion {
+            edits,
             message: message.into(),
+            applicability: Applicability::Safe,
+        });
+        self
+    }
+
+    /// Add a code suggestion which may change the rendered output of the template.
+    /// It is only replaced automatically if the user allows unsafe fixes (`--fix-unsafe`).
+    #[must_use]
+    pub fn unsafe_suggestion<R: Into<String>, S: Into<String>>(
+        mut self,
+        syntax_range: TextRange,
+        replace_with: R,
+        message: S,
+    ) -> Self {
+        self.suggestions.push(CheckSuggestion {
+            edits: vec![TextEdit {
+                syntax_range,
+                replace_with: replace_with.into(),
+            }],
+            message: message.into(),
+            applicability: Applicability::Unsafe,
         });
         self
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckNote {
+    #[serde(with = "text_range")]
     pub syntax_range: TextRange,
     pub message: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckSuggestion {
+    /// The text edits of this suggestion sorted by their range, which are applied all at once
+    pub edits: Vec<TextEdit>,
+    pub message: String,
+    pub applicability: Applicability,
+}
+
+impl CheckSuggestion {
+    /// The range from the start of the first to the end of the last edit.
+    pub fn syntax_range(&self) -> TextRange {
+        self.edits
+            .iter()
+            .map(|e| e.syntax_range)
+            .reduce(TextRange::cover)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEdit {
+    #[serde(with = "text_range")]
     pub syntax_range: TextRange,
     pub replace_with: String,
-    pub message: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Applicability {
+    /// The suggestion keeps the behaviour of the template and can always be applied automatically
+    Safe,
+    /// The suggestion may change the rendered output and is only applied with `--fix-unsafe`
+    Unsafe,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Severity {
     /// Errors which must be fixed for the template to work correctly
     Error,
@@ -170,3 +363,23 @@ pub enum Severity {
     /// Just information
     Info,
 }
+
+/// (De)serialize a [`TextRange`] as its start and end offset.
+mod text_range {
+    use super::{Deserialize, Deserializer, Serialize, Serializer, TextRange};
+
+    #[allow(clippy::trivially_copy_pass_by_ref)] // signature required by serde
+    pub fn serialize<S: Serializer>(range: &TextRange, serializer: S) -> Result<S::Ok, S::Error> {
+        (u32::from(range.start()), u32::from(range.end())).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TextRange, D::Error> {
+        let (start, end) = <(u32, u32)>::deserialize(deserializer)?;
+        if start > end {
+            return Err(serde::de::Error::custom(
+                "text range start is after its end",
+            ));
+        }
+        Ok(TextRange::new(start.into(), end.into()))
+    }
+}
@@ -3,6 +3,7 @@ use crate::check::rules::html_attribute_name_kebab_case::RuleHtmlAttributeNameKe
 use crate::check::rules::html_string_quotation::RuleHtmlStringQuotation;
 use crate::check::rules::indentation::RuleIndentation;
 use crate::check::rules::line_ending::RuleLineEnding;
+pub use crate::check::rules::ludtwig_directive_unused::RuleLudtwigDirectiveUnused;
 use crate::check::rules::ludtwig_ignore_file_not_on_top::RuleLudtwigIgnoreFileNotOnTop;
 use crate::check::rules::twig_block_duplicate::RuleTwigBlockDuplicate;
 use crate::check::rules::twig_block_line_breaks::RuleTwigBlockLineBreaks;
@@ -19,11 +20,13 @@ use crate::error::ConfigurationError;
 use crate::Config;
 use ludtwig_parser::syntax::typed::{AstNode, LudtwigDirectiveFileIgnore};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
+use std::sync::{Arc, LazyLock};
 
 mod html_attribute_name_kebab_case;
 mod html_string_quotation;
 mod indentation;
 mod line_ending;
+mod ludtwig_directive_unused;
 mod ludtwig_ignore_file_not_on_top;
 mod twig_block_duplicate;
 mod twig_block_line_breaks;
@@ -38,29 +41,32 @@ mod twig_use_is_same_as;
 mod whitespace_between_line_breaks;
 
 /// List of all rule trait objects, also add them to the `active-rules` in `ludtwig-config.toml`!
-pub static RULE_DEFINITIONS: &[&'static dyn Rule] = &[
-    &RuleLudtwigIgnoreFileNotOnTop,
-    &RuleWhitespaceBetweenLineBreaks,
-    &RuleLineEnding,
-    &RuleIndentation,
-    &RuleTwigBlockLineBreaks,
-    &RuleTwigBlockNameSnakeCase,
-    &RuleHtmlAttributeNameKebabCase,
-    &RuleTwigLogicAnd,
-    &RuleTwigLogicOr,
-    &RuleTwigStringQuotation,
-    &RuleHtmlStringQuotation,
-    &RuleTwigHashKeyNoQuotes,
-    &RuleTwigPreferShopwareExtends,
-    &RuleTwigUseIsSameAs,
-    &RuleTwigUseIsNotSameAs,
-    &RuleTwigBlockDuplicate,
-];
+pub static RULE_DEFINITIONS: LazyLock<Vec<Arc<dyn Rule>>> = LazyLock::new(|| {
+    vec![
+        Arc::new(RuleLudtwigIgnoreFileNotOnTop),
+        Arc::new(RuleLudtwigDirectiveUnused),
+        Arc::new(RuleWhitespaceBetweenLineBreaks),
+        Arc::new(RuleLineEnding),
+        Arc::new(RuleIndentation),
+        Arc::new(RuleTwigBlockLineBreaks),
+        Arc::new(RuleTwigBlockNameSnakeCase),
+        Arc::new(RuleHtmlAttributeNameKebabCase),
+        Arc::new(RuleTwigLogicAnd),
+        Arc::new(RuleTwigLogicOr),
+        Arc::new(RuleTwigStringQuotation),
+        Arc::new(RuleHtmlStringQuotation),
+        Arc::new(RuleTwigHashKeyNoQuotes),
+        Arc::new(RuleTwigPreferShopwareExtends),
+        Arc::new(RuleTwigUseIsSameAs),
+        Arc::new(RuleTwigUseIsNotSameAs),
+        Arc::new(RuleTwigBlockDuplicate),
+    ]
+});
 
 /// Get active rule definitions based on config
 pub fn get_config_active_rule_definitions(
     config: &Config,
-) -> Result<Vec<&'static dyn Rule>, ConfigurationError> {
+) -> Result<Vec<Arc<dyn Rule>>, ConfigurationError> {
     // gather active rules
     let config_active_rules: Vec<&str> = config
         .general
@@ -69,11 +75,11 @@ pub fn get_config_active_rule_definitions(
         .map(String::as_ref)
         .collect();
 
-    let active_rules: Vec<&'static dyn Rule> = RULE_DEFINITIONS
+    let active_rules: Vec<Arc<dyn Rule>> = RULE_DEFINITIONS
         .iter()
         .filter_map(|r| {
             if config_active_rules.contains(&r.name()) {
-                Some(*r)
+                Some(Arc::clone(r))
             } else {
                 None
             }
@@ -103,8 +109,8 @@ pub fn get_config_active_rule_definitions(
 /// after looking inside it for ludtwig-ignore-file directives
 pub fn get_file_active_rule_definitions(
     root: &SyntaxNode,
-    definitions: &[&'static dyn Rule],
-) -> Vec<&'static dyn Rule> {
+    definitions: &[Arc<dyn Rule>],
+) -> Vec<Arc<dyn Rule>> {
     let mut disabled_rules: Vec<String> = vec![];
 
     for directive in root
@@ -124,7 +130,7 @@ pub fn get_file_active_rule_definitions(
     definitions
         .iter()
         .filter(|d| !disabled_rules.iter().any(|rule_name| rule_name == d.name()))
-        .copied()
+        .cloned()
         .collect()
 }
 
@@ -132,7 +138,7 @@ pub fn get_file_active_rule_definitions(
 pub mod test {
     use std::path::PathBuf;
     use std::sync::mpsc::Receiver;
-    use std::sync::{mpsc, Arc};
+    use std::sync::{mpsc, Arc, OnceLock};
 
     use codespan_reporting::term::termcolor::Buffer;
 
@@ -141,12 +147,16 @@ pub mod test {
 
     use crate::check::produce_diagnostics;
     use crate::check::rule::CheckResult;
-    use crate::check::rules::RULE_DEFINITIONS;
-    use crate::check::run_rules;
+    use crate::check::rules::{get_config_active_rule_definitions, RULE_DEFINITIONS};
+    use crate::check::{
+        get_rule_context_suggestions, retain_changed_lines, run_rules, FileDependencies,
+    };
+    use crate::config::{ActiveConfig, ConfigResolver};
+    use crate::git::ChangedLines;
     use crate::process::{iteratively_apply_suggestions, FileContext};
     use crate::{CliContext, CliSharedData, Config, ProcessingEvent};
 
-    fn debug_rule(
+    pub fn debug_rule(
         rule_name: &str,
         source_code: &str,
     ) -> (FileContext, Vec<CheckResult>, Receiver<ProcessingEvent>) {
@@ -158,22 +168,44 @@ pub mod test {
             .unwrap();
         let (tx, rx) = mpsc::channel();
         let parse = parse(source_code);
+        let config_resolver = ConfigResolver::new(
+            ActiveConfig {
+                path: None,
+                rule_definitions: get_config_active_rule_definitions(&config).unwrap(),
+                config,
+                project: OnceLock::new(),
+            },
+            false,
+            false,
+        );
 
         let file_context = FileContext {
+            config: config_resolver.fallback(),
             cli_context: CliContext {
                 output_tx: tx,
                 data: Arc::new(CliSharedData {
                     fix: false,
+                    check: false,
+                    diff: false,
+                    fix_only: vec![],
+                    fix_unsafe: true,
+                    verify_fixes: true,
+                    interactive: None,
                     inspect: false,
-                    config,
-                    rule_definitions: vec![*rule],
+                    cache: None,
+                    changed_files: None,
+                    changed_lines_only: false,
+                    timings: None,
+                    config_resolver,
                 }),
             },
             file_path: PathBuf::from("./debug-rule.html.twig"),
             tree_root: SyntaxNode::new_root(parse.green_node),
             source_code: source_code.to_owned(),
             parse_errors: parse.errors,
-            file_rule_definitions: vec![*rule],
+            file_rule_definitions: vec![Arc::clone(rule)],
+            dependencies: FileDependencies::default(),
+            changed_lines: None,
         };
 
         let rule_result_context = run_rules(&file_context);
@@ -184,10 +216,16 @@ pub mod test {
     #[allow(clippy::needless_pass_by_value)]
     pub fn test_rule(rule_name: &str, source_code: &str, expected_report: expect_test::Expect) {
         let (file_context, rule_result_context, rx) = debug_rule(rule_name, source_code);
+        produce_diagnostics(&file_context, rule_result_context);
+        drop(file_context);
+
         let mut buffer = Buffer::no_color();
-        produce_diagnostics(&file_context, rule_result_context, &mut buffer);
+        for event in rx {
+            if let ProcessingEvent::Diagnostic(diagnostic) = event {
+                diagnostic.render(&mut buffer).unwrap();
+            }
+        }
         expected_report.assert_eq(&String::from_utf8_lossy(buffer.as_slice()));
-        drop(rx);
     }
 
     #[allow(clippy::needless_pass_by_value)]
@@ -225,6 +263,153 @@ pub mod test {
         drop(rx);
     }
 
+    #[test]
+    fn test_all_rules_metadata_examples() {
+        let config = Config::new(crate::config::DEFAULT_CONFIG_PATH).unwrap();
+
+        for rule in RULE_DEFINITIONS.iter() {
+            let metadata = rule.metadata();
+            for key in metadata.config_keys {
+                assert!(
+                    crate::explain::config_value(&config, key).is_some(),
+                    "config key {key} of rule {} should exist",
+                    rule.name()
+                );
+            }
+
+            let (_, good_results, rx) = debug_rule(rule.name(), metadata.good_example);
+            assert!(
+                good_results.is_empty(),
+                "good example of rule {} should not be reported",
+                rule.name()
+            );
+            drop(rx);
+
+            let (file_context, bad_results, rx) = debug_rule(rule.name(), metadata.bad_example);
+            assert!(
+                !bad_results.is_empty(),
+                "bad example of rule {} should be reported",
+                rule.name()
+            );
+            let has_suggestions = bad_results.iter().any(|r| !r.suggestions.is_empty());
+            assert_eq!(
+                metadata.fixable,
+                has_suggestions,
+                "fixable metadata of rule {} should match its suggestions",
+                rule.name()
+            );
+            if metadata.fixable {
+                let (_, fixed_results, _, _) =
+                    iteratively_apply_suggestions(file_context, bad_results).unwrap();
+                assert!(
+                    fixed_results.is_empty(),
+                    "fixed bad example of rule {} should not be reported",
+                    rule.name()
+                );
+            }
+            drop(rx);
+        }
+    }
+
+    #[test]
+    fn test_suggestions_respect_fix_only_and_fix_unsafe() {
+        let (file_context, results, rx) =
+            debug_rule("twig-use-is-same-as", "{% if a === 5 %}{% endif %}");
+        let cli_data = |fix_only: Vec<String>, fix_unsafe: bool| CliSharedData {
+            fix: true,
+            check: false,
+            diff: false,
+            fix_only,
+            fix_unsafe,
+            verify_fixes: false,
+            interactive: None,
+            inspect: false,
+            cache: None,
+            changed_files: None,
+            changed_lines_only: false,
+            timings: None,
+            config_resolver: ConfigResolver::new(
+                ActiveConfig {
+                    path: None,
+                    config: file_context.config.config.clone(),
+                    rule_definitions: vec![],
+                    project: OnceLock::new(),
+                },
+                false,
+                false,
+            ),
+        };
+
+        assert!(get_rule_context_suggestions(&results, &cli_data(vec![], false)).is_empty());
+        assert_eq!(
+            get_rule_context_suggestions(&results, &cli_data(vec![], true)).len(),
+            1
+        );
+        assert!(get_rule_context_suggestions(
+            &results,
+            &cli_data(vec!["twig-string-quotation".to_string()], true)
+        )
+        .is_empty());
+        assert_eq!(
+            get_rule_context_suggestions(
+                &results,
+                &cli_data(vec!["twig-use-is-same-as".to_string()], true)
+            )
+            .len(),
+            1
+        );
+        drop(rx);
+    }
+
+    #[test]
+    fn test_disabled_regions_span_siblings_and_nesting() {
+        test_rule(
+            "html-attribute-name-kebab-case",
+            r#"<div aA="1">
+    {# ludtwig-disable -- vendor markup #}
+    <div bB="2"></div>
+    <div>
+        <span cC="3"></span>
+        {# ludtwig-enable html-attribute-name-kebab-case #}
+        <span dD="4"></span>
+    </div>
+    {# ludtwig-disable html-attribute-name-kebab-case #}
+    <div eE="5"></div>
+    {# ludtwig-enable #}
+    <div fF="6"></div>
+</div>"#,
+            expect_test::expect![[r#"
+                help[html-attribute-name-kebab-case]: Attribute name is not written in kebab-case
+                  ┌─ ./debug-rule.html.twig:1:6
+                  │
+                1 │ <div aA="1">
+                  │      ^^
+                  │      │
+                  │      help: rename this attribute in kebab-case
+                  │      Try this name instead: a-a
+
+                help[html-attribute-name-kebab-case]: Attribute name is not written in kebab-case
+                  ┌─ ./debug-rule.html.twig:7:15
+                  │
+                7 │         <span dD="4"></span>
+                  │               ^^
+                  │               │
+                  │               help: rename this attribute in kebab-case
+                  │               Try this name instead: d-d
+
+                help[html-attribute-name-kebab-case]: Attribute name is not written in kebab-case
+                   ┌─ ./debug-rule.html.twig:12:10
+                   │
+                12 │     <div fF="6"></div>
+                   │          ^^
+                   │          │
+                   │          help: rename this attribute in kebab-case
+                   │          Try this name instead: f-f
+
+            "#]],
+        );
+    }
+
     #[test]
     fn test_all_rules_in_config_exists() {
         let config = Config::new(crate::config::DEFAULT_CONFIG_PATH).unwrap();
@@ -234,4 +419,32 @@ pub mod test {
             assert!(active_rules.iter().any(|r| r.name() == rule));
         }
     }
+
+    #[test]
+    fn test_retain_changed_lines() {
+        let (file_context, mut results, _rx) = debug_rule(
+            "html-attribute-name-kebab-case",
+            "<div aA=\"1\">\n    <span bB=\"2\"\n        cC=\"3\"></span>\n</div>",
+        );
+        assert_eq!(results.len(), 3);
+
+        retain_changed_lines(
+            &mut results,
+            &file_context.source_code,
+            &ChangedLines::Ranges(vec![2..=2]),
+        );
+        // only the attribute `bB` on line 2
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            u32::from(results[0].primary.as_ref().unwrap().syntax_range.start()),
+            23
+        );
+
+        retain_changed_lines(
+            &mut results,
+            &file_context.source_code,
+            &ChangedLines::Ranges(vec![]),
+        );
+        assert!(results.is_empty());
+    }
 }
@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, HtmlAttribute};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleHtmlAttributeNameKebabCase;
 
@@ -10,6 +12,18 @@ impl Rule for RuleHtmlAttributeNameKebabCase {
         "html-attribute-name-kebab-case"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports HTML attribute names which are not written in kebab-case.",
+            rationale: "HTML attribute names are case-insensitive, so kebab-case is the common convention for them.",
+            category: RuleCategory::Naming,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "<div dataId=\"1\"></div>",
+            good_example: "<div data-id=\"1\"></div>",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let attribute_name = HtmlAttribute::cast(node)?.name()?;
         if !is_valid_alphanumeric_kebab_case(attribute_name.text()) {
@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, HtmlString};
 use ludtwig_parser::syntax::untyped::{SyntaxNode, SyntaxNodeExt, TextRange, TextSize};
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleHtmlStringQuotation;
 
@@ -10,6 +12,18 @@ impl Rule for RuleHtmlStringQuotation {
         "html-string-quotation"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports HTML attribute values which don't use the configured quotation.",
+            rationale: "Consistent quotation makes templates easier to read and search.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &["format.html-quotation"],
+            bad_example: "<div class='container'></div>",
+            good_example: "<div class=\"container\"></div>",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let html_string = HtmlString::cast(node)?;
 
@@ -45,7 +59,7 @@ impl Rule for RuleHtmlStringQuotation {
 pub fn make_changed_quotes_suggestion_if_possible(
     twig_string: &HtmlString,
     correct_quote: char,
-    mut result: CheckResult,
+    result: CheckResult,
 ) -> CheckResult {
     let Some(inner) = twig_string.get_inner() else {
         return result;
@@ -56,29 +70,20 @@ pub fn make_changed_quotes_suggestion_if_possible(
         return result; // TODO: could still try to transform the string with more effort...
     }
 
-    // opening quote
-    if let Some(quote) = twig_string.get_opening_quote() {
-        result = result.suggestion(quote.text_range(), correct_quote, "Try this quote instead");
-    } else {
-        result = result.suggestion(
-            TextRange::at(inner.syntax().text_range().start(), TextSize::from(0)),
-            correct_quote,
-            "Try this quote instead",
-        );
-    }
-
-    // closing quote
-    if let Some(quote) = twig_string.get_closing_quote() {
-        result = result.suggestion(quote.text_range(), correct_quote, "Try this quote instead");
-    } else {
-        result = result.suggestion(
-            TextRange::at(inner.syntax().text_range().end(), TextSize::from(0)),
-            correct_quote,
-            "Try this quote instead",
-        );
-    }
+    // both quotes are changed together, otherwise the string would be broken in between
+    let opening = match twig_string.get_opening_quote() {
+        Some(quote) => quote.text_range(),
+        None => TextRange::at(inner.syntax().text_range().start(), TextSize::from(0)),
+    };
+    let closing = match twig_string.get_closing_quote() {
+        Some(quote) => quote.text_range(),
+        None => TextRange::at(inner.syntax().text_range().end(), TextSize::from(0)),
+    };
 
-    result
+    result.multi_edit_suggestion(
+        [(opening, correct_quote), (closing, correct_quote)],
+        "Try this quote instead",
+    )
 }
 
 #[cfg(test)]
@@ -98,9 +103,9 @@ mod tests {
                 1 │ <div class='a'></div>
                   │            ^^^
                   │            │ │
-                  │            │ Try this quote instead: "
+                  │            │ Try this quote instead (2/2): "
                   │            help: change the quotation to double quotes (")
-                  │            Try this quote instead: "
+                  │            Try this quote instead (1/2): "
 
             "#]],
         );
@@ -132,10 +137,10 @@ mod tests {
                   ┌─ ./debug-rule.html.twig:1:12
                   │
                 1 │ <div class=a></div>
-                  │            ^- Try this quote instead: "
+                  │            ^- Try this quote instead (2/2): "
                   │            │ 
                   │            help: change the quotation to double quotes (")
-                  │            Try this quote instead: "
+                  │            Try this quote instead (1/2): "
 
             "#]],
         );
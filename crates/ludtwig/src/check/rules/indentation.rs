@@ -4,7 +4,10 @@ use ludtwig_parser::syntax::untyped::{
     WalkEvent,
 };
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+use crate::check::DisabledRegions;
 
 pub struct RuleIndentation;
 
@@ -13,6 +16,18 @@ impl Rule for RuleIndentation {
         "indentation"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports lines which are not indented according to their nesting level.",
+            rationale: "Consistent indentation makes the nesting of HTML and Twig structures visible at a glance.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &["format.indentation-mode", "format.indentation-count", "format.indent-children-of-blocks"],
+            bad_example: "<div>\n<span></span>\n</div>\n",
+            good_example: "<div>\n    <span></span>\n</div>\n",
+        }
+    }
+
     fn check_root(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         // keep track of some state during tree traversal
         let mut line_break_encountered = true;
@@ -20,6 +35,7 @@ impl Rule for RuleIndentation {
         let mut indentation_substeps = 0; // additional spaces for alignment (like attributes)
         let mut inside_trivia_sensitive_node = false;
         let mut is_ignored = false;
+        let mut disabled_regions = DisabledRegions::default();
 
         let indent_block_children = ctx.config().format.indent_children_of_blocks;
 
@@ -35,7 +51,11 @@ impl Rule for RuleIndentation {
                         SyntaxElement::Token(t) if t.kind() == SyntaxKind::TK_LINE_BREAK => {
                             line_break_encountered = true;
                         }
-                        SyntaxElement::Token(t) if !is_ignored && line_break_encountered => {
+                        SyntaxElement::Token(t)
+                            if line_break_encountered
+                                && !is_ignored
+                                && !disabled_regions.is_disabled(self.name()) =>
+                        {
                             if !inside_trivia_sensitive_node {
                                 check_results.append(&mut self.handle_first_token_in_line(
                                     &t,
@@ -51,6 +71,7 @@ impl Rule for RuleIndentation {
                             line_break_encountered = false;
                         }
                         SyntaxElement::Node(n) => {
+                            disabled_regions.enter(&n);
                             if self.check_for_rule_ignore_enter(&mut is_ignored, &mut tree_iter, &n)
                             {
                                 continue;
@@ -481,4 +502,37 @@ mod tests {
             expect![[r""]],
         );
     }
+
+    #[test]
+    fn rule_ignores_disabled_region() {
+        test_rule(
+            "indentation",
+            r"{% block outer %}
+    <div>
+        {# ludtwig-disable indentation -- generated markup #}
+        <span>
+        inner
+        </span>
+    </div>
+    <div>
+    inner
+        {# ludtwig-enable indentation #}
+    </div>
+    <div>
+    inner
+    </div>
+{% endblock %}",
+            expect![[r"
+                help[indentation]: Wrong indentation
+                   ┌─ ./debug-rule.html.twig:13:1
+                   │
+                13 │     inner
+                   │ ^^^^
+                   │ │
+                   │ Found 4 spaces and 0 tabs but expected indentation of 8 spaces here
+                   │ Change indentation to 8 spaces:         
+
+            "]],
+        );
+    }
 }
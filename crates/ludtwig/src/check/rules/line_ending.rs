@@ -3,7 +3,9 @@ use std::sync::OnceLock;
 
 use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxToken, TextRange, TextSize};
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 use crate::config::LineEnding;
 
 pub struct RuleLineEnding;
@@ -13,6 +15,19 @@ impl Rule for RuleLineEnding {
         "line-ending"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports line endings which don't match the configured line ending.",
+            rationale:
+                "Mixed line endings produce noisy diffs and can confuse editors and other tools.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &["format.line-ending"],
+            bad_example: "<div></div>\r\n<span></span>\r\n",
+            good_example: "<div></div>\n<span></span>\n",
+        }
+    }
+
     fn check_token(&self, token: SyntaxToken, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         static INVALID_REGEX: OnceLock<Regex> = OnceLock::new();
 
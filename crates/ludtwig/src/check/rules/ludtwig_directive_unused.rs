@@ -0,0 +1,441 @@
+use std::sync::Arc;
+
+use ludtwig_parser::syntax::typed::{
+    AstNode, LudtwigDirectiveDisable, LudtwigDirectiveEnable, LudtwigDirectiveFileIgnore,
+    LudtwigDirectiveIgnore, LudtwigDirectiveReason, LudtwigDirectiveRuleList,
+};
+use ludtwig_parser::syntax::untyped::{
+    SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken, TextRange,
+};
+use ludtwig_parser::T;
+
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+use crate::check::rules::RULE_DEFINITIONS;
+use crate::check::run_rule_definitions;
+use crate::config::ActiveConfig;
+use crate::process::FileContext;
+
+pub struct RuleLudtwigDirectiveUnused;
+
+impl Rule for RuleLudtwigDirectiveUnused {
+    fn name(&self) -> &'static str {
+        "ludtwig-directive-unused"
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `ludtwig-ignore` and `ludtwig-ignore-file` directives which don't suppress anything and directives with unknown rule names.",
+            rationale: "Ignore directives pile up after the code was already fixed and hide new problems. Typos in rule names are not noticed otherwise, because the directive silently ignores nothing.",
+            category: RuleCategory::Ludtwig,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{# ludtwig-ignore twig-block-name-snake-case, indentaton #}\n{% block content %}{% endblock %}\n",
+            good_example: "{% block content %}{% endblock %}\n",
+        }
+    }
+
+    fn check_node(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        // ignore directives are checked together with their usage in `check_unused_directives`
+        if !LudtwigDirectiveDisable::can_cast(node.kind())
+            && !LudtwigDirectiveEnable::can_cast(node.kind())
+        {
+            return None;
+        }
+
+        let stale: Vec<(SyntaxToken, StaleReason)> = rule_name_tokens(&node)
+            .into_iter()
+            .filter(|t| !is_known_rule(t.text(), &ctx.active_config))
+            .map(|t| (t, StaleReason::Unknown))
+            .collect();
+        self.stale_rules_result(&node, &stale).map(|r| vec![r])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StaleReason {
+    /// There is no rule with this name
+    Unknown,
+    /// The rule doesn't report anything where the directive applies
+    Unused,
+}
+
+impl RuleLudtwigDirectiveUnused {
+    /// Find the `ludtwig-ignore` and `ludtwig-ignore-file` directives which don't suppress any result
+    /// or contain unknown rule names.
+    ///
+    /// For that the rules run again on the same source code where these directives are turned into plain comments
+    /// (which keeps all the text ranges the same). A rule of a directive is used if it reports a result
+    /// in the range the directive applies to.
+    pub fn check_unused_directives(&self, file_context: &FileContext) -> Vec<CheckResult> {
+        let directives: Vec<(SyntaxNode, TextRange)> = file_context
+            .tree_root
+            .descendants()
+            .filter_map(|node| Some((node.clone(), directive_scope(&node)?)))
+            .collect();
+        if directives.is_empty() {
+            return vec![];
+        }
+
+        // only judge the rules which actually run (others may be active in another config)
+        let rule_definitions: Vec<Arc<dyn Rule>> = file_context
+            .config
+            .rule_definitions
+            .iter()
+            .filter(|r| r.name() != self.name())
+            .cloned()
+            .collect();
+
+        let mut source_code = file_context.source_code.clone();
+        for (node, _) in &directives {
+            if let Some(keyword) = node
+                .children_with_tokens()
+                .find(|t| matches!(t.kind(), T!["ludtwig-ignore"] | T!["ludtwig-ignore-file"]))
+            {
+                let start = usize::from(keyword.text_range().start());
+                source_code.replace_range(start..=start, "_");
+            }
+        }
+        let parse = ludtwig_parser::parse(&source_code);
+        let unsuppressed = run_rule_definitions(
+            &SyntaxNode::new_root(parse.green_node),
+            &file_context.file_path,
+            &file_context.config,
+            &rule_definitions,
+            &file_context.dependencies,
+            None,
+        );
+
+        let is_suppressed = |scope: TextRange, rule_name: Option<&str>| {
+            unsuppressed.iter().any(|result| {
+                rule_name.map_or(true, |name| name == result.rule_name)
+                    && result
+                        .primary
+                        .as_ref()
+                        .is_some_and(|note| scope.contains_range(note.syntax_range))
+            })
+        };
+
+        let mut results = vec![];
+        for (node, scope) in directives {
+            let rule_names = rule_name_tokens(&node);
+            if rule_names.is_empty() {
+                if !is_suppressed(scope, None) {
+                    results.push(self.unused_directive_result(&node));
+                }
+                continue;
+            }
+
+            let stale: Vec<(SyntaxToken, StaleReason)> = rule_names
+                .into_iter()
+                .filter_map(|t| {
+                    if !is_known_rule(t.text(), &file_context.config) {
+                        Some((t, StaleReason::Unknown))
+                    } else if rule_definitions.iter().any(|r| r.name() == t.text())
+                        && !is_suppressed(scope, Some(t.text()))
+                    {
+                        Some((t, StaleReason::Unused))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            results.extend(self.stale_rules_result(&node, &stale));
+        }
+
+        results
+    }
+
+    fn unused_directive_result(&self, node: &SyntaxNode) -> CheckResult {
+        let result = self
+            .create_result(Severity::Warning, "Directive is not needed")
+            .primary_note(
+                directive_range(node),
+                "this directive doesn't suppress anything",
+            );
+        remove_directive(with_reason_note(result, node), node)
+    }
+
+    /// A single result for all stale rule names of a directive, which removes them
+    /// (or the whole directive if none of its rules are needed).
+    fn stale_rules_result(
+        &self,
+        node: &SyntaxNode,
+        stale: &[(SyntaxToken, StaleReason)],
+    ) -> Option<CheckResult> {
+        let rule_names = rule_name_tokens(node);
+        let (first, first_reason) = stale.first()?;
+
+        let reason_note = |reason: StaleReason| match reason {
+            StaleReason::Unknown => "no rule with this name exists",
+            StaleReason::Unused => "this rule doesn't report anything here",
+        };
+        let mut result = self
+            .create_result(
+                Severity::Warning,
+                if stale.len() == rule_names.len() {
+                    "Directive is not needed"
+                } else {
+                    "Directive contains rules which are not needed"
+                },
+            )
+            .primary_note(first.text_range(), reason_note(*first_reason));
+        for (token, reason) in &stale[1..] {
+            result = result.secondary_note(token.text_range(), reason_note(*reason));
+        }
+        result = with_reason_note(result, node);
+
+        if stale.len() == rule_names.len() {
+            // removing all names would change the directive to apply to all rules
+            return Some(remove_directive(result, node));
+        }
+
+        let kept: Vec<&str> = rule_names
+            .iter()
+            .filter(|t| !stale.iter().any(|(s, _)| s == *t))
+            .map(SyntaxToken::text)
+            .collect();
+        let names_range = TextRange::new(
+            rule_names[0].text_range().start(),
+            rule_names[rule_names.len() - 1].text_range().end(),
+        );
+        let removed: Vec<String> = stale
+            .iter()
+            .map(|(t, _)| format!("'{}'", t.text()))
+            .collect();
+        Some(result.suggestion(
+            names_range,
+            kept.join(", "),
+            format!("Remove {}", removed.join(", ")),
+        ))
+    }
+}
+
+/// Built-in rules and the script rules of the config are known.
+fn is_known_rule(name: &str, active_config: &ActiveConfig) -> bool {
+    RULE_DEFINITIONS
+        .iter()
+        .chain(&active_config.rule_definitions)
+        .any(|r| r.name() == name)
+}
+
+/// The range in which results are suppressed by an ignore directive.
+fn directive_scope(node: &SyntaxNode) -> Option<TextRange> {
+    if LudtwigDirectiveIgnore::can_cast(node.kind()) {
+        // applies to the next sibling (an empty range if there is none)
+        Some(
+            node.next_sibling_or_token()
+                .map_or(TextRange::empty(node.text_range().end()), |sibling| {
+                    sibling.text_range()
+                }),
+        )
+    } else if LudtwigDirectiveFileIgnore::can_cast(node.kind())
+        && node.parent().is_some_and(|p| p.kind() == SyntaxKind::ROOT)
+    {
+        // directives which are not on top level are reported by 'ludtwig-ignore-file-not-on-top'
+        node.parent().map(|p| p.text_range())
+    } else {
+        None
+    }
+}
+
+fn rule_name_tokens(node: &SyntaxNode) -> Vec<SyntaxToken> {
+    node.children()
+        .find_map(LudtwigDirectiveRuleList::cast)
+        .map(|list| {
+            list.syntax()
+                .children_with_tokens()
+                .filter_map(SyntaxElement::into_token)
+                .filter(|t| t.kind() == T![word])
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The range of the directive without the leading trivia (which belongs to the line before).
+fn directive_range(node: &SyntaxNode) -> TextRange {
+    let start = node
+        .children_with_tokens()
+        .find(|e| !e.kind().is_trivia())
+        .map_or(node.text_range().start(), |e| e.text_range().start());
+    TextRange::new(start, node.text_range().end())
+}
+
+fn with_reason_note(result: CheckResult, node: &SyntaxNode) -> CheckResult {
+    match node.children().find_map(LudtwigDirectiveReason::cast) {
+        Some(reason) => result.secondary_note(
+            reason.syntax().text_range(),
+            format!("the reason was: {}", reason.get_text()),
+        ),
+        None => result,
+    }
+}
+
+/// Remove the whole directive including the line it is on.
+fn remove_directive(result: CheckResult, node: &SyntaxNode) -> CheckResult {
+    let starts_with_line_break = node
+        .first_token()
+        .is_some_and(|t| t.kind() == SyntaxKind::TK_LINE_BREAK);
+    let range = if starts_with_line_break {
+        node.text_range()
+    } else {
+        // for example on the first line of the file: remove the following line break instead
+        let end = node
+            .next_sibling_or_token()
+            .and_then(|next| {
+                let first = match next {
+                    SyntaxElement::Node(n) => n.first_token(),
+                    SyntaxElement::Token(t) => Some(t),
+                }?;
+                (first.kind() == SyntaxKind::TK_LINE_BREAK).then(|| first.text_range().end())
+            })
+            .unwrap_or(node.text_range().end());
+        TextRange::new(node.text_range().start(), end)
+    };
+
+    let is_html_comment = node.children_with_tokens().any(|e| e.kind() == T!["<!--"]);
+    if is_html_comment {
+        // html comments are part of the rendered output
+        result.unsafe_suggestion(range, "", "Remove the directive")
+    } else {
+        result.suggestion(range, "", "Remove the directive")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use crate::check::rules::test::{test_rule, test_rule_fix};
+
+    #[test]
+    fn rule_reports_unknown_rule_names() {
+        test_rule(
+            "ludtwig-directive-unused",
+            "{# ludtwig-ignore twig-block-name-snake-case, indentaton #}\n{% block fooBar %}{% endblock %}",
+            expect![[r"
+                warning[ludtwig-directive-unused]: Directive contains rules which are not needed
+                  ┌─ ./debug-rule.html.twig:1:47
+                  │
+                1 │ {# ludtwig-ignore twig-block-name-snake-case, indentaton #}
+                  │                   ----------------------------^^^^^^^^^^
+                  │                   │                           │
+                  │                   │                           no rule with this name exists
+                  │                   Remove 'indentaton': twig-block-name-snake-case
+
+            "]],
+        );
+    }
+
+    #[test]
+    fn rule_reports_unknown_rule_names_in_regions() {
+        test_rule(
+            "ludtwig-directive-unused",
+            "{# ludtwig-disable indentaton #}\n<div></div>\n{# ludtwig-enable indentation, indentaton #}",
+            expect![[r"
+                warning[ludtwig-directive-unused]: Directive is not needed
+                  ┌─ ./debug-rule.html.twig:1:20
+                  │  
+                1 │ ╭ {# ludtwig-disable indentaton #}
+                  │                      ^^^^^^^^^^ no rule with this name exists
+                2 │ │ <div></div>
+                  │ ╰' Remove the directive: 
+
+                warning[ludtwig-directive-unused]: Directive contains rules which are not needed
+                  ┌─ ./debug-rule.html.twig:3:32
+                  │
+                3 │ {# ludtwig-enable indentation, indentaton #}
+                  │                   -------------^^^^^^^^^^
+                  │                   │            │
+                  │                   │            no rule with this name exists
+                  │                   Remove 'indentaton': indentation
+
+            "]],
+        );
+    }
+
+    #[test]
+    fn rule_reports_unused_directive() {
+        test_rule(
+            "ludtwig-directive-unused",
+            "<div>\n    {# ludtwig-ignore html-attribute-name-kebab-case -- legacy #}\n    <span class=\"a\"></span>\n</div>",
+            expect![[r"
+                warning[ludtwig-directive-unused]: Directive is not needed
+                  ┌─ ./debug-rule.html.twig:2:23
+                  │  
+                1 │   <div>
+                  │ ╭─────'
+                2 │ │     {# ludtwig-ignore html-attribute-name-kebab-case -- legacy #}
+                  │ │                       ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^---------- the reason was: legacy
+                  │ │                       │                              
+                  │ │                       this rule doesn't report anything here
+                  │ ╰─────────────────────────────────────────────────────────────────' Remove the directive: 
+
+            "]],
+        );
+    }
+
+    #[test]
+    fn rule_does_not_report_used_directives() {
+        test_rule(
+            "ludtwig-directive-unused",
+            r#"{# ludtwig-ignore-file twig-block-name-snake-case #}
+<div>
+    {# ludtwig-ignore html-attribute-name-kebab-case #}
+    <span dataFoo="a"></span>
+    {# ludtwig-ignore #}
+    <span dataBar="a"></span>
+    {# ludtwig-ignore indentation #}
+    <div>
+    inner
+    </div>
+</div>
+{% block fooBar %}{% endblock %}"#,
+            expect![[r""]],
+        );
+    }
+
+    #[test]
+    fn rule_fixes_stale_rule_names() {
+        test_rule_fix(
+            "ludtwig-directive-unused",
+            "<div>\n    {# ludtwig-ignore indentation, html-attribute-name-kebab-case, indentaton #}\n    <span dataFoo=\"a\"></span>\n</div>",
+            expect![[r#"
+                <div>
+                    {# ludtwig-ignore html-attribute-name-kebab-case #}
+                    <span dataFoo="a"></span>
+                </div>"#]],
+        );
+    }
+
+    #[test]
+    fn rule_fixes_unused_directive() {
+        test_rule_fix(
+            "ludtwig-directive-unused",
+            "{# ludtwig-ignore-file twig-block-name-snake-case #}\n<div>\n    {# ludtwig-ignore indentation #}\n    <span></span>\n</div>",
+            expect![[r"
+                <div>
+                    <span></span>
+                </div>"]],
+        );
+    }
+
+    #[test]
+    fn rule_reports_removing_html_comment_as_unsafe() {
+        test_rule(
+            "ludtwig-directive-unused",
+            "<!-- ludtwig-ignore indentation -->\n<div></div>",
+            expect![[r"
+                warning[ludtwig-directive-unused]: Directive is not needed
+                  ┌─ ./debug-rule.html.twig:1:21
+                  │  
+                1 │ ╭ <!-- ludtwig-ignore indentation -->
+                  │                       ^^^^^^^^^^^ this rule doesn't report anything here
+                2 │ │ <div></div>
+                  │ ╰' Remove the directive (unsafe fix): 
+
+            "]],
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext};
+use crate::check::rule::{CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext};
 use crate::Severity;
 use ludtwig_parser::syntax::typed::{AstNode, LudtwigDirectiveFileIgnore};
 use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxNode};
@@ -10,6 +10,18 @@ impl Rule for RuleLudtwigIgnoreFileNotOnTop {
         "ludtwig-ignore-file-not-on-top"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `ludtwig-ignore-file` directives which are not on the top level of a file.",
+            rationale: "Ludtwig only looks for `ludtwig-ignore-file` directives on the top level of a file. Anywhere else they are discarded and don't ignore anything.",
+            category: RuleCategory::Ludtwig,
+            fixable: false,
+            config_keys: &[],
+            bad_example: "<div>\n    {# ludtwig-ignore-file #}\n</div>\n",
+            good_example: "{# ludtwig-ignore-file #}\n<div></div>\n",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let directive = LudtwigDirectiveFileIgnore::cast(node)?;
         let parent = directive.syntax().parent()?;
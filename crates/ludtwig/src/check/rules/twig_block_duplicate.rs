@@ -2,7 +2,10 @@ use ludtwig_parser::syntax::typed::{AstNode, LudtwigDirectiveIgnore, TwigBlock};
 use ludtwig_parser::syntax::untyped::{Preorder, SyntaxKind, SyntaxNode, WalkEvent};
 use std::collections::HashMap;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+use crate::check::DisabledRegions;
 
 pub struct RuleTwigBlockDuplicate;
 
@@ -11,11 +14,25 @@ impl Rule for RuleTwigBlockDuplicate {
         "twig-block-duplicate"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description:
+                "Reports Twig blocks which have the same name as another block in the same file.",
+            rationale: "Block names must be unique in a template, otherwise Twig can't compile it.",
+            category: RuleCategory::Correctness,
+            fixable: false,
+            config_keys: &[],
+            bad_example: "{% block content %}{% endblock %}\n{% block content %}{% endblock %}\n",
+            good_example: "{% block content %}{% endblock %}\n{% block footer %}{% endblock %}\n",
+        }
+    }
+
     fn check_root(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         // keep track of some state during tree traversal
         let mut block_table: HashMap<String, TwigBlock> = HashMap::new();
 
         let mut is_ignored = false;
+        let mut disabled_regions = DisabledRegions::default();
         let mut check_results = vec![];
         let mut tree_iter = node.preorder();
         while let Some(walk) = tree_iter.next() {
@@ -24,10 +41,11 @@ impl Rule for RuleTwigBlockDuplicate {
                     if element.kind() == SyntaxKind::ERROR {
                         tree_iter.skip_subtree(); // Skip everything under error nodes!
                     }
+                    disabled_regions.enter(&element);
                     if self.check_for_rule_ignore_enter(&mut is_ignored, &mut tree_iter, &element) {
                         continue;
                     }
-                    if is_ignored {
+                    if is_ignored || disabled_regions.is_disabled(self.name()) {
                         continue;
                     }
 
@@ -200,4 +218,37 @@ mod tests {
             "]],
         );
     }
+
+    #[test]
+    fn rule_respects_disabled_region() {
+        test_rule(
+            "twig-block-duplicate",
+            r"
+            {% block foo %}
+            {% endblock %}
+
+            {# ludtwig-disable twig-block-duplicate -- overridden on purpose #}
+            {% block first_duplicate %}
+                {% block foo %}
+                {% endblock %}
+            {% endblock %}
+            {# ludtwig-enable #}
+
+            {% block second_duplicate %}
+                {% block foo %}
+                {% endblock %}
+            {% endblock %}",
+            expect![[r"
+                error[twig-block-duplicate]: block name duplicate
+                   ┌─ ./debug-rule.html.twig:13:26
+                   │
+                 2 │             {% block foo %}
+                   │                      --- first defined here
+                   ·
+                13 │                 {% block foo %}
+                   │                          ^^^ duplicate block 'foo'
+
+            "]],
+        );
+    }
 }
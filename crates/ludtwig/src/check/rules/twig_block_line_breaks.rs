@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, TwigBlock};
 use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxNode, TextRange, TextSize};
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigBlockLineBreaks;
 
@@ -10,6 +12,18 @@ impl Rule for RuleTwigBlockLineBreaks {
         "twig-block-line-breaks"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports Twig blocks which are not surrounded by the configured amount of line breaks.",
+            rationale: "Empty lines around blocks visually separate the blocks which can be overridden by other templates.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &["format.linebreaks-around-blocks", "format.line-ending"],
+            bad_example: "<div>\n    {% block a %}{% endblock %}\n    {% block b %}{% endblock %}\n</div>\n",
+            good_example: "<div>\n    {% block a %}{% endblock %}\n\n    {% block b %}{% endblock %}\n</div>\n",
+        }
+    }
+
     #[allow(clippy::too_many_lines)]
     fn check_node(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         if ctx.traversal_ctx().inside_trivia_sensitive_node {
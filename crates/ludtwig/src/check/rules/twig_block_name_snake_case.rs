@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, TwigStartingBlock};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigBlockNameSnakeCase;
 
@@ -10,8 +12,21 @@ impl Rule for RuleTwigBlockNameSnakeCase {
         "twig-block-name-snake-case"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports Twig block names which are not written in snake_case.",
+            rationale: "A single naming convention for blocks makes them easier to find and to override in other templates.",
+            category: RuleCategory::Naming,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{% block myBlock %}{% endblock %}",
+            good_example: "{% block my_block %}{% endblock %}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
-        let block_name = TwigStartingBlock::cast(node)?.name()?;
+        let starting_block = TwigStartingBlock::cast(node)?;
+        let block_name = starting_block.name()?;
         if !is_valid_ascii_alpha_snake_case(block_name.text()) {
             // name is not valid ascii snake case
             let mut result = self
@@ -23,11 +38,17 @@ impl Rule for RuleTwigBlockNameSnakeCase {
 
             // try make a suggestion
             if let Some(suggested_name) = try_make_snake_case(block_name.text()) {
-                result = result.suggestion(
-                    block_name.text_range(),
-                    suggested_name,
-                    "Try this name instead",
-                );
+                // also rename the block in a named `{% endblock name %}`
+                let ending_name = starting_block
+                    .twig_block()
+                    .and_then(|b| b.ending_block())
+                    .and_then(|b| b.name())
+                    .filter(|name| name.text() == block_name.text());
+                let edits = std::iter::once(block_name.text_range())
+                    .chain(ending_name.map(|name| name.text_range()))
+                    .map(|range| (range, suggested_name.clone()));
+
+                result = result.multi_edit_suggestion(edits, "Try this name instead");
             }
 
             return Some(vec![result]);
@@ -214,4 +235,32 @@ mod tests {
             expect!["{% block a_b %}hello{% endblock %}"],
         );
     }
+
+    #[test]
+    fn rule_reports_with_named_endblock() {
+        test_rule(
+            "twig-block-name-snake-case",
+            "{% block myBlock %}hello{% endblock myBlock %}",
+            expect![[r"
+                help[twig-block-name-snake-case]: Block name is not written in snake_case
+                  ┌─ ./debug-rule.html.twig:1:10
+                  │
+                1 │ {% block myBlock %}hello{% endblock myBlock %}
+                  │          ^^^^^^^                    ------- Try this name instead (2/2): my_block
+                  │          │                           
+                  │          help: rename this block in snake_case
+                  │          Try this name instead (1/2): my_block
+
+            "]],
+        );
+    }
+
+    #[test]
+    fn rule_fixes_named_endblock() {
+        test_rule_fix(
+            "twig-block-name-snake-case",
+            "{% block myBlock %}hello{% endblock myBlock %}",
+            expect!["{% block my_block %}hello{% endblock my_block %}"],
+        );
+    }
 }
@@ -2,7 +2,9 @@ use ludtwig_parser::syntax::typed::{support, AstNode, TwigLiteralHashKey, TwigLi
 use ludtwig_parser::syntax::untyped::{SyntaxNode, SyntaxNodeExt, TextRange, TextSize};
 use ludtwig_parser::TWIG_NAME_REGEX;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigHashKeyNoQuotes;
 
@@ -11,6 +13,19 @@ impl Rule for RuleTwigHashKeyNoQuotes {
         "twig-hash-key-no-quotes"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports quoted Twig hash keys which don't need quotes.",
+            rationale:
+                "Hash keys which are valid names don't need quotes, leaving them out reduces noise.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{{ { 'key': 1 } }}",
+            good_example: "{{ { key: 1 } }}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let hash_key = TwigLiteralHashKey::cast(node)?;
         let key_string_literal: TwigLiteralString = support::child(hash_key.syntax())?;
@@ -2,7 +2,9 @@ use ludtwig_parser::syntax::typed::{AstNode, TwigBinaryExpression};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 use ludtwig_parser::T;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigLogicAnd;
 
@@ -11,6 +13,18 @@ impl Rule for RuleTwigLogicAnd {
         "twig-logic-and"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `&&`, which is not a valid Twig operator.",
+            rationale: "Twig uses the `and` keyword for a logical and, `&&` results in a syntax error when the template is compiled.",
+            category: RuleCategory::Correctness,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{% if a && b %}{% endif %}",
+            good_example: "{% if a and b %}{% endif %}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let binary_expr_op = TwigBinaryExpression::cast(node)?.operator()?;
 
@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, TwigExtends};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigPreferShopwareExtends;
 
@@ -10,6 +12,18 @@ impl Rule for RuleTwigPreferShopwareExtends {
         "twig-prefer-shopware-extends"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `extends`, which should be `sw_extends` in Shopware templates.",
+            rationale: "Only `sw_extends` respects the Shopware template inheritance, which allows multiple plugins and themes to extend the same template.",
+            category: RuleCategory::Shopware,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{% extends '@Storefront/storefront/base.html.twig' %}",
+            good_example: "{% sw_extends '@Storefront/storefront/base.html.twig' %}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let twig_extends = TwigExtends::cast(node)?;
         let extends_keyword = twig_extends.get_extends_keyword()?;
@@ -24,7 +38,7 @@ impl Rule for RuleTwigPreferShopwareExtends {
                 twig_extends.syntax().text_range(),
                 "change this 'extends' to 'sw_extends'",
             )
-            .suggestion(
+            .unsafe_suggestion(
                 extends_keyword.text_range(),
                 "sw_extends",
                 "Try this keyword instead",
@@ -36,7 +50,7 @@ impl Rule for RuleTwigPreferShopwareExtends {
 
 #[cfg(test)]
 mod tests {
-    use crate::check::rules::test::{test_rule, test_rule_fix};
+    use crate::check::rules::test::{test_rule, test_rule_does_not_fix, test_rule_fix};
     use expect_test::expect;
 
     #[test]
@@ -51,7 +65,7 @@ mod tests {
                 1 │ {% extends foo %}
                   │ ^^^^^^^^^^^^^^^^^
                   │ │  │
-                  │ │  Try this keyword instead: sw_extends
+                  │ │  Try this keyword instead (unsafe fix): sw_extends
                   │ change this 'extends' to 'sw_extends'
 
             "]],
@@ -61,9 +75,19 @@ mod tests {
     #[test]
     fn rule_fixes() {
         test_rule_fix(
+            "twig-prefer-shopware-extends",
+            "{% extends '@Storefront/foo.html.twig' %}",
+            expect!["{% sw_extends '@Storefront/foo.html.twig' %}"],
+        );
+    }
+
+    #[test]
+    fn rule_does_not_fix_into_syntax_error() {
+        // sw_extends only allows strings, so the fix would introduce a syntax error
+        test_rule_does_not_fix(
             "twig-prefer-shopware-extends",
             "{% extends foo %}",
-            expect!["{% sw_extends foo %}"],
+            expect!["{% extends foo %}"],
         );
     }
 }
@@ -1,7 +1,9 @@
 use ludtwig_parser::syntax::typed::{AstNode, TwigLiteralString};
 use ludtwig_parser::syntax::untyped::{SyntaxNode, SyntaxNodeExt, TextRange, TextSize};
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 use crate::config::Quotation;
 
 pub struct RuleTwigStringQuotation;
@@ -11,6 +13,18 @@ impl Rule for RuleTwigStringQuotation {
         "twig-string-quotation"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports Twig strings which don't use the configured quotation.",
+            rationale: "Consistent quotation makes templates easier to read and search.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &["format.twig-quotation"],
+            bad_example: "{{ \"hello\" }}",
+            good_example: "{{ 'hello' }}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let twig_string = TwigLiteralString::cast(node)?;
 
@@ -57,7 +71,7 @@ impl Rule for RuleTwigStringQuotation {
 pub fn make_changed_quotes_suggestion_if_possible(
     twig_string: &TwigLiteralString,
     correct_quote: char,
-    mut result: CheckResult,
+    result: CheckResult,
 ) -> CheckResult {
     let Some(inner) = twig_string.get_inner() else {
         return result;
@@ -68,29 +82,20 @@ pub fn make_changed_quotes_suggestion_if_possible(
         return result; // TODO: could still try to transform the string with more effort...
     }
 
-    // opening quote
-    if let Some(quote) = twig_string.get_opening_quote() {
-        result = result.suggestion(quote.text_range(), correct_quote, "Try this quote instead");
-    } else {
-        result = result.suggestion(
-            TextRange::at(inner.syntax().text_range().start(), TextSize::from(0)),
-            correct_quote,
-            "Try this quote instead",
-        );
-    }
-
-    // closing quote
-    if let Some(quote) = twig_string.get_closing_quote() {
-        result = result.suggestion(quote.text_range(), correct_quote, "Try this quote instead");
-    } else {
-        result = result.suggestion(
-            TextRange::at(inner.syntax().text_range().end(), TextSize::from(0)),
-            correct_quote,
-            "Try this quote instead",
-        );
-    }
+    // both quotes are changed together, otherwise the string would be broken in between
+    let opening = match twig_string.get_opening_quote() {
+        Some(quote) => quote.text_range(),
+        None => TextRange::at(inner.syntax().text_range().start(), TextSize::from(0)),
+    };
+    let closing = match twig_string.get_closing_quote() {
+        Some(quote) => quote.text_range(),
+        None => TextRange::at(inner.syntax().text_range().end(), TextSize::from(0)),
+    };
 
-    result
+    result.multi_edit_suggestion(
+        [(opening, correct_quote), (closing, correct_quote)],
+        "Try this quote instead",
+    )
 }
 
 #[cfg(test)]
@@ -110,9 +115,9 @@ mod tests {
                 1 │ {{ "double-quoted" }}
                   │    ^^^^^^^^^^^^^^^
                   │    │             │
-                  │    │             Try this quote instead: '
+                  │    │             Try this quote instead (2/2): '
                   │    help: change the quotation to single quotes (')
-                  │    Try this quote instead: '
+                  │    Try this quote instead (1/2): '
 
             "#]],
         );
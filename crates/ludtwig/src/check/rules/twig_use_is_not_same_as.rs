@@ -1,5 +1,5 @@
-use crate::check::rule::{CheckResult, RuleExt, RuleRunContext};
-use crate::{Rule, Severity};
+use crate::check::rule::{CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext};
+use crate::Severity;
 use ludtwig_parser::syntax::typed::{AstNode, TwigBinaryExpression};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 use ludtwig_parser::T;
@@ -11,6 +11,18 @@ impl Rule for RuleTwigUseIsNotSameAs {
         "twig-use-is-not-same-as"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `!==`, which is not a valid Twig operator.",
+            rationale: "Twig doesn't support `!==`, the strict comparison is written as `is not same as(...)`.",
+            category: RuleCategory::Correctness,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{% if a !== 5 %}{% endif %}",
+            good_example: "{% if a is not same as(5) %}{% endif %}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let binary = TwigBinaryExpression::cast(node)?;
         let op = binary.operator()?;
@@ -27,7 +39,7 @@ impl Rule for RuleTwigUseIsNotSameAs {
             );
 
         if let Some(rhs) = binary.rhs_expression() {
-            result = result.suggestion(
+            result = result.unsafe_suggestion(
                 op.text_range().cover(rhs.syntax().text_range()),
                 format!("is not same as({})", rhs.syntax().text().to_string().trim()),
                 "Try this instead",
@@ -55,7 +67,7 @@ mod tests {
                 1 │ {% if test !== false %}{% endif %}
                   │            ^^^------
                   │            │
-                  │            Try this instead: is not same as(false)
+                  │            Try this instead (unsafe fix): is not same as(false)
                   │            This is not a valid Twig operator, try 'is not same as(condition)' instead
 
             "]],
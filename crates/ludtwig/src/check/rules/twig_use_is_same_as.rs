@@ -2,7 +2,9 @@ use ludtwig_parser::syntax::typed::{AstNode, TwigBinaryExpression};
 use ludtwig_parser::syntax::untyped::SyntaxNode;
 use ludtwig_parser::T;
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleTwigUseIsSameAs;
 
@@ -11,6 +13,19 @@ impl Rule for RuleTwigUseIsSameAs {
         "twig-use-is-same-as"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports `===`, which is not a valid Twig operator.",
+            rationale:
+                "Twig doesn't support `===`, the strict comparison is written as `is same as(...)`.",
+            category: RuleCategory::Correctness,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "{% if a === 5 %}{% endif %}",
+            good_example: "{% if a is same as(5) %}{% endif %}",
+        }
+    }
+
     fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         let binary = TwigBinaryExpression::cast(node)?;
         let op = binary.operator()?;
@@ -27,7 +42,7 @@ impl Rule for RuleTwigUseIsSameAs {
             );
 
         if let Some(rhs) = binary.rhs_expression() {
-            result = result.suggestion(
+            result = result.unsafe_suggestion(
                 op.text_range().cover(rhs.syntax().text_range()),
                 format!("is same as({})", rhs.syntax().text().to_string().trim()),
                 "Try this instead",
@@ -55,7 +70,7 @@ mod tests {
                 1 │ {% if a === 5 %}hello{% endif %}
                   │         ^^^--
                   │         │
-                  │         Try this instead: is same as(5)
+                  │         Try this instead (unsafe fix): is same as(5)
                   │         This is not a valid Twig operator, try 'is same as(condition)' instead
 
             "]],
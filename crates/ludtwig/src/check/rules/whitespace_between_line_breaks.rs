@@ -1,6 +1,8 @@
 use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxToken};
 
-use crate::check::rule::{CheckResult, Rule, RuleExt, RuleRunContext, Severity};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
 
 pub struct RuleWhitespaceBetweenLineBreaks;
 
@@ -9,6 +11,19 @@ impl Rule for RuleWhitespaceBetweenLineBreaks {
         "whitespace-between-line-breaks"
     }
 
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Reports whitespace on otherwise empty lines.",
+            rationale:
+                "Trailing whitespace is invisible in most editors and only produces noise in diffs.",
+            category: RuleCategory::Style,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "<div></div>\n    \n<span></span>\n",
+            good_example: "<div></div>\n\n<span></span>\n",
+        }
+    }
+
     fn check_token(&self, token: SyntaxToken, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
         // rule only inspects line breaks
         if token.kind() != SyntaxKind::TK_LINE_BREAK {
@@ -0,0 +1,594 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+
+use ludtwig_parser::syntax::typed::{AstNode, HtmlTag};
+use ludtwig_parser::syntax::untyped::{
+    SyntaxElement, SyntaxNode, SyntaxToken, TextRange, TextSize,
+};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Scope, AST};
+
+use crate::check::query::{typed_name, typed_value};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+use crate::check::rules::RULE_DEFINITIONS;
+use crate::error::ConfigurationError;
+
+/// Functions a script can define, which are called like the methods of the [`Rule`] trait.
+const CHECK_FUNCTIONS: [&str; 3] = ["check_root", "check_node", "check_token"];
+
+/// Limits of a single call of a script, so endless loops or recursion are reported instead of hanging
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_FUNCTION_EXPR_DEPTH: usize = 32;
+
+static NEXT_SCRIPT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A script compiled for a single thread, with the source of its rule (to know when the rule is dropped).
+type CompiledScript = (Weak<str>, Rc<(Engine, AST)>);
+
+thread_local! {
+    /// The compiled scripts by the id of their rule, because a Rhai engine can't be shared between threads.
+    /// Scripts of dropped rules (like after reloading the config) are forgotten when the next script is compiled.
+    static COMPILED_SCRIPTS: RefCell<HashMap<usize, CompiledScript>> = RefCell::new(HashMap::new());
+}
+
+/// A custom rule written in a [Rhai](https://rhai.rs) script (`general.script-rules` in the config).
+/// Its name is the file name of the script without the extension.
+///
+/// The script defines any of the functions `check_root(node)`, `check_node(node)` and `check_token(token)`,
+/// which return a result, an array of results or nothing. Results are created with `result(severity, message)`
+/// and the same builder methods as [`CheckResult`] (like `primary_note(range, message)`).
+/// Optional constants like `DESCRIPTION` and `RATIONALE` are shown by `ludtwig explain`.
+#[derive(Debug)]
+pub struct ScriptRule {
+    id: usize,
+    name: String,
+    path: PathBuf,
+    source: Arc<str>,
+    metadata: ScriptMetadata,
+    /// Which of the [`CHECK_FUNCTIONS`] the script defines
+    defines: [bool; 3],
+}
+
+#[derive(Debug, Default)]
+struct ScriptMetadata {
+    description: String,
+    rationale: String,
+    fixable: bool,
+    bad_example: String,
+    good_example: String,
+}
+
+/// Load the script rules of a config. Relative paths are resolved from `base_dir`.
+pub fn load_script_rules(
+    paths: &[String],
+    base_dir: &Path,
+) -> Result<Vec<Arc<dyn Rule>>, ConfigurationError> {
+    let mut rules: Vec<Arc<dyn Rule>> = vec![];
+    for path in paths {
+        let rule = ScriptRule::load(base_dir.join(path))?;
+        if RULE_DEFINITIONS
+            .iter()
+            .chain(&rules)
+            .any(|r| r.name() == rule.name)
+        {
+            return Err(ConfigurationError::ScriptRule {
+                path: rule.path,
+                message: format!("there is already a rule named '{}'", rule.name),
+            });
+        }
+
+        rules.push(Arc::new(rule));
+    }
+
+    Ok(rules)
+}
+
+impl ScriptRule {
+    fn load(path: PathBuf) -> Result<Self, ConfigurationError> {
+        let invalid = |path: &Path, message: String| ConfigurationError::ScriptRule {
+            path: path.to_owned(),
+            message,
+        };
+        let source =
+            fs::read_to_string(&path).map_err(|e| invalid(&path, format!("can't be read: {e}")))?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (engine, ast) = compile(&name, &source).map_err(|e| invalid(&path, e))?;
+        let defines = CHECK_FUNCTIONS.map(|function| {
+            ast.iter_functions()
+                .any(|f| f.name == function && f.params.len() == 1)
+        });
+        if !defines.contains(&true) {
+            return Err(invalid(
+                &path,
+                "the script doesn't define any of the functions check_root(node), check_node(node) or check_token(token)".to_string(),
+            ));
+        }
+
+        // the top level statements only define the constants of the metadata
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| invalid(&path, e.to_string()))?;
+        let constant = |key: &str| -> String {
+            scope
+                .get_value::<ImmutableString>(key)
+                .map(|value| value.to_string())
+                .unwrap_or_default()
+        };
+        let metadata = ScriptMetadata {
+            description: constant("DESCRIPTION"),
+            rationale: constant("RATIONALE"),
+            fixable: scope.get_value::<bool>("FIXABLE").unwrap_or_default(),
+            bad_example: constant("BAD_EXAMPLE"),
+            good_example: constant("GOOD_EXAMPLE"),
+        };
+
+        Ok(Self {
+            id: NEXT_SCRIPT_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            path,
+            source: source.into(),
+            metadata,
+            defines,
+        })
+    }
+
+    /// Call a check function of the script. Errors of the script are reported as results at `range`.
+    fn call(
+        &self,
+        function: &str,
+        argument: Dynamic,
+        range: TextRange,
+    ) -> Option<Vec<CheckResult>> {
+        let compiled = COMPILED_SCRIPTS.with(|scripts| {
+            let mut scripts = scripts.borrow_mut();
+            if !scripts.contains_key(&self.id) {
+                scripts.retain(|_, (source, _)| source.strong_count() > 0);
+                let compiled = compile(&self.name, &self.source)
+                    .expect("script should compile like when it was loaded");
+                scripts.insert(self.id, (Arc::downgrade(&self.source), Rc::new(compiled)));
+            }
+            Rc::clone(&scripts[&self.id].1)
+        });
+        let (engine, ast) = compiled.as_ref();
+
+        let returned = engine
+            .call_fn_with_options::<Dynamic>(
+                CallFnOptions::new().eval_ast(false),
+                &mut Scope::new(),
+                ast,
+                function,
+                (argument,),
+            )
+            .map_err(|e| match *e {
+                EvalAltResult::ErrorTooManyOperations(_) => {
+                    format!("{e}, a call may run at most {MAX_OPERATIONS} operations")
+                }
+                EvalAltResult::ErrorStackOverflow(_) => {
+                    format!("{e}, functions may be called at most {MAX_CALL_LEVELS} levels deep")
+                }
+                _ => e.to_string(),
+            })
+            .and_then(|returned| into_results(returned, function));
+
+        match returned {
+            Ok(results) if results.is_empty() => None,
+            Ok(results) => Some(results),
+            Err(message) => Some(vec![self
+                .create_result(
+                    Severity::Error,
+                    format!("The script {} failed", self.path.display()),
+                )
+                .primary_note(range, message)]),
+        }
+    }
+}
+
+impl Rule for ScriptRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: &self.metadata.description,
+            rationale: &self.metadata.rationale,
+            category: RuleCategory::Custom,
+            fixable: self.metadata.fixable,
+            config_keys: &[],
+            bad_example: &self.metadata.bad_example,
+            good_example: &self.metadata.good_example,
+        }
+    }
+
+    fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        if !self.defines[1] {
+            return None;
+        }
+        let range = node.text_range();
+        self.call(CHECK_FUNCTIONS[1], Dynamic::from(node), range)
+    }
+
+    fn check_token(&self, token: SyntaxToken, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        if !self.defines[2] {
+            return None;
+        }
+        let range = token.text_range();
+        self.call(CHECK_FUNCTIONS[2], Dynamic::from(token), range)
+    }
+
+    fn check_root(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        // cached results are invalid when the script changes
+        ctx.add_dependency(self.path.clone());
+        if !self.defines[0] {
+            return None;
+        }
+        let range = TextRange::empty(node.text_range().start());
+        self.call(CHECK_FUNCTIONS[0], Dynamic::from(node), range)
+    }
+}
+
+/// The value returned by a check function: a result, an array of results or nothing.
+fn into_results(returned: Dynamic, function: &str) -> Result<Vec<CheckResult>, String> {
+    if returned.is_unit() {
+        return Ok(vec![]);
+    }
+    let values = if returned.is_array() {
+        returned.cast::<Array>()
+    } else {
+        vec![returned]
+    };
+
+    values
+        .into_iter()
+        .map(|value| {
+            let type_name = value.type_name();
+            value.try_cast::<CheckResult>().ok_or_else(|| {
+                format!("{function} should return results (created with 'result(severity, message)'), not {type_name}")
+            })
+        })
+        .collect()
+}
+
+/// Compile the script with an engine that knows the syntax tree and result types.
+fn compile(rule_name: &str, source: &str) -> Result<(Engine, AST), String> {
+    let engine = script_engine(rule_name.to_string());
+    let ast = engine.compile(source).map_err(|e| e.to_string())?;
+    Ok((engine, ast))
+}
+
+#[allow(clippy::too_many_lines)]
+fn script_engine(rule_name: String) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_FUNCTION_EXPR_DEPTH);
+    // stdout may be used for the (fixed) templates
+    engine.on_print(|text| eprintln!("{text}"));
+    engine.on_debug(|text, _, position| eprintln!("{position:?} | {text}"));
+
+    engine
+        .register_type_with_name::<TextRange>("Range")
+        .register_fn("range", |start: i64, end: i64| {
+            let offset = |value: i64| u32::try_from(value).map(TextSize::from);
+            match (offset(start), offset(end)) {
+                (Ok(start), Ok(end)) if start <= end => Ok(TextRange::new(start, end)),
+                _ => Err(Box::<EvalAltResult>::from(format!(
+                    "invalid range {start}..{end}"
+                ))),
+            }
+        })
+        .register_get("start", |range: &mut TextRange| {
+            i64::from(u32::from(range.start()))
+        })
+        .register_get("end", |range: &mut TextRange| {
+            i64::from(u32::from(range.end()))
+        })
+        .register_fn("cover", |a: TextRange, b: TextRange| a.cover(b))
+        .register_fn("to_string", |range: &mut TextRange| format!("{range:?}"));
+
+    engine
+        .register_type_with_name::<SyntaxNode>("Node")
+        .register_get("kind", |node: &mut SyntaxNode| format!("{:?}", node.kind()))
+        .register_get("text", |node: &mut SyntaxNode| node.text().to_string())
+        .register_get("range", |node: &mut SyntaxNode| node.text_range())
+        .register_get("parent", |node: &mut SyntaxNode| {
+            node.parent().map_or(Dynamic::UNIT, Dynamic::from)
+        })
+        .register_get("children", |node: &mut SyntaxNode| -> Array {
+            node.children().map(Dynamic::from).collect()
+        })
+        .register_get("tokens", |node: &mut SyntaxNode| -> Array {
+            node.children_with_tokens()
+                .filter_map(SyntaxElement::into_token)
+                .map(Dynamic::from)
+                .collect()
+        })
+        .register_get("descendants", |node: &mut SyntaxNode| -> Array {
+            node.descendants().skip(1).map(Dynamic::from).collect()
+        })
+        // typed AST
+        .register_get("name", |node: &mut SyntaxNode| {
+            typed_name(node).map_or(Dynamic::UNIT, Dynamic::from)
+        })
+        .register_get("attributes", |node: &mut SyntaxNode| -> Array {
+            HtmlTag::cast(node.clone())
+                .map(|tag| {
+                    tag.attributes()
+                        .map(|attribute| Dynamic::from(attribute.syntax().clone()))
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+        .register_get("value", |node: &mut SyntaxNode| {
+            typed_value(node).map_or(Dynamic::UNIT, Dynamic::from)
+        })
+        .register_fn("to_string", |node: &mut SyntaxNode| node.text().to_string());
+
+    engine
+        .register_type_with_name::<SyntaxToken>("Token")
+        .register_get("kind", |token: &mut SyntaxToken| {
+            format!("{:?}", token.kind())
+        })
+        .register_get("text", |token: &mut SyntaxToken| token.text().to_string())
+        .register_get("range", |token: &mut SyntaxToken| token.text_range())
+        .register_get("parent", |token: &mut SyntaxToken| {
+            token.parent().map_or(Dynamic::UNIT, Dynamic::from)
+        })
+        .register_fn("to_string", |token: &mut SyntaxToken| {
+            token.text().to_string()
+        });
+
+    engine
+        .register_type_with_name::<CheckResult>("CheckResult")
+        .register_fn(
+            "result",
+            move |severity: &str, message: &str| -> Result<CheckResult, Box<EvalAltResult>> {
+                let severity = match severity {
+                    "error" => Severity::Error,
+                    "warning" => Severity::Warning,
+                    "help" => Severity::Help,
+                    "info" => Severity::Info,
+                    _ => {
+                        return Err(format!(
+                        "unknown severity '{severity}', use 'error', 'warning', 'help' or 'info'"
+                    )
+                        .into())
+                    }
+                };
+                Ok(CheckResult {
+                    rule_name: rule_name.clone(),
+                    severity,
+                    message: message.to_string(),
+                    primary: None,
+                    secondary: vec![],
+                    suggestions: vec![],
+                })
+            },
+        )
+        .register_fn(
+            "primary_note",
+            |result: CheckResult, range: TextRange, message: &str| {
+                result.primary_note(range, message)
+            },
+        )
+        .register_fn(
+            "secondary_note",
+            |result: CheckResult, range: TextRange, message: &str| {
+                result.secondary_note(range, message)
+            },
+        )
+        .register_fn(
+            "suggestion",
+            |result: CheckResult, range: TextRange, replace_with: &str, message: &str| {
+                result.suggestion(range, replace_with, message)
+            },
+        )
+        .register_fn(
+            "unsafe_suggestion",
+            |result: CheckResult, range: TextRange, replace_with: &str, message: &str| {
+                result.unsafe_suggestion(range, replace_with, message)
+            },
+        );
+
+    engine
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use expect_test::expect;
+    use tempfile::TempDir;
+
+    use crate::config::{ActiveConfig, ConfigResolver};
+    use crate::error::ConfigurationError;
+    use crate::linter::Linter;
+    use crate::test_util::temp_dir;
+
+    const BLOCK_PREFIX_SCRIPT: &str = r#"
+const DESCRIPTION = "Twig blocks should start with the component prefix";
+const FIXABLE = true;
+
+fn check_node(node) {
+    if node.kind == "TWIG_STARTING_BLOCK" && !node.name.text.starts_with("my_") {
+        return result("warning", "Block without prefix")
+            .primary_note(node.name.range, "add the 'my_' prefix")
+            .suggestion(node.name.range, "my_" + node.name.text, "Try this name");
+    }
+}
+"#;
+
+    /// A directory with a config which uses the scripts (by their file name) in its `rules` directory.
+    fn project_with_scripts(scripts: &[(&str, &str)]) -> TempDir {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::create_dir(dir.join("rules")).unwrap();
+
+        let mut script_rules = vec![];
+        for (file_name, source) in scripts {
+            fs::write(dir.join("rules").join(file_name), source).unwrap();
+            script_rules.push(format!("\"rules/{file_name}\""));
+        }
+        fs::write(
+            dir.join("ludtwig-config.toml"),
+            format!(
+                "[general]\nactive-rules = []\nscript-rules = [{}]\n",
+                script_rules.join(", ")
+            ),
+        )
+        .unwrap();
+        temp
+    }
+
+    fn linter(dir: &Path) -> Result<Linter, ConfigurationError> {
+        let active = ActiveConfig::load(&dir.join("ludtwig-config.toml"))?;
+        Ok(Linter::with_resolver(ConfigResolver::new(
+            active, false, false,
+        )))
+    }
+
+    #[test]
+    fn script_rule_reports_results_and_suggestions() {
+        let temp = project_with_scripts(&[("block-prefix.rhai", BLOCK_PREFIX_SCRIPT)]);
+        let linter = linter(temp.path()).unwrap();
+        let path = Path::new("a.html.twig");
+        let source_code = "{% block my_content %}{% endblock %}\n{% block footer %}{% endblock %}\n{# ludtwig-ignore block-prefix #}\n{% block header %}{% endblock %}\n";
+
+        let diagnostics: Vec<String> = linter
+            .check_source(path, source_code)
+            .into_iter()
+            .map(|d| format!("{}:{} {} {}", d.line, d.column, d.code, d.message))
+            .collect();
+        expect![[r#"
+            [
+                "2:10 block-prefix Block without prefix",
+            ]
+        "#]]
+        .assert_debug_eq(&diagnostics);
+
+        let fixed = linter.fix_source(path, source_code).unwrap();
+        expect![[r"
+            {% block my_content %}{% endblock %}
+            {% block my_footer %}{% endblock %}
+            {# ludtwig-ignore block-prefix #}
+            {% block header %}{% endblock %}
+        "]]
+        .assert_eq(&fixed.source_code);
+    }
+
+    #[test]
+    fn script_rules_are_relative_to_the_extended_config() {
+        let temp = project_with_scripts(&[("block-prefix.rhai", BLOCK_PREFIX_SCRIPT)]);
+        let project = temp.path().join("project");
+        fs::create_dir(&project).unwrap();
+        fs::write(
+            project.join("ludtwig-config.toml"),
+            "extends = [\"../ludtwig-config.toml\"]\n",
+        )
+        .unwrap();
+
+        let linter = linter(&project).unwrap();
+        let diagnostics =
+            linter.check_source(Path::new("a.html.twig"), "{% block a %}{% endblock %}\n");
+        assert_eq!(diagnostics[0].code, "block-prefix");
+    }
+
+    #[test]
+    fn script_root_results_respect_directives() {
+        let temp = project_with_scripts(&[(
+            "no-blocks.rhai",
+            r#"
+fn check_root(node) {
+    let results = [];
+    for child in node.descendants {
+        if child.kind == "TWIG_STARTING_BLOCK" {
+            results.push(result("warning", "Block found").primary_note(child.name.range, "here"));
+        }
+    }
+    results
+}
+"#,
+        )]);
+        let source_code = "{% block a %}{% endblock %}\n{# ludtwig-ignore no-blocks #}\n{% block b %}{% endblock %}\n{# ludtwig-disable #}\n{% block c %}{% endblock %}\n{# ludtwig-enable #}\n{% block d %}{% endblock %}\n";
+
+        let lines: Vec<usize> = linter(temp.path())
+            .unwrap()
+            .check_source(Path::new("a.html.twig"), source_code)
+            .into_iter()
+            .map(|d| d.line)
+            .collect();
+        assert_eq!(lines, vec![1, 7]);
+    }
+
+    #[test]
+    fn script_rule_errors_are_reported() {
+        let temp = project_with_scripts(&[(
+            "broken.rhai",
+            "fn check_root(node) { result(\"fatal\", \"nope\") }",
+        )]);
+        let diagnostics = linter(temp.path())
+            .unwrap()
+            .check_source(Path::new("a.html.twig"), "<div></div>\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "broken");
+        assert!(diagnostics[0].labels[0]
+            .message
+            .contains("unknown severity 'fatal'"));
+    }
+
+    #[test]
+    fn script_rule_limits_are_reported() {
+        let temp = project_with_scripts(&[
+            ("endless.rhai", "fn check_root(node) { loop {} }"),
+            (
+                "recursive.rhai",
+                "fn deeper(n) { deeper(n + 1) }\nfn check_root(node) { deeper(0) }",
+            ),
+        ]);
+        let mut diagnostics: Vec<String> = linter(temp.path())
+            .unwrap()
+            .check_source(Path::new("a.html.twig"), "<div></div>\n")
+            .into_iter()
+            .map(|d| format!("{} {}", d.code, d.labels[0].message))
+            .collect();
+        diagnostics.sort();
+        expect![[r#"
+            [
+                "endless Too many operations, a call may run at most 1000000 operations",
+                "recursive Stack overflow, functions may be called at most 32 levels deep",
+            ]
+        "#]]
+        .assert_debug_eq(&diagnostics);
+    }
+
+    #[test]
+    fn invalid_scripts_are_config_errors() {
+        let error_of = |file_name: &str, source: &str| {
+            let temp = project_with_scripts(&[(file_name, source)]);
+            let error = linter(temp.path()).unwrap_err().to_string();
+            error.replace(&temp.path().display().to_string(), "<dir>")
+        };
+
+        expect!["script rule <dir>/rules/syntax.rhai is invalid: Expecting ',' to separate the parameters of function 'check_node' (line 1, position 20)"]
+            .assert_eq(&error_of("syntax.rhai", "fn check_node(node { }"));
+        expect![[r"script rule <dir>/rules/empty.rhai is invalid: the script doesn't define any of the functions check_root(node), check_node(node) or check_token(token)"]]
+            .assert_eq(&error_of("empty.rhai", "const DESCRIPTION = \"nothing\";"));
+        expect![[r"script rule <dir>/rules/indentation.rhai is invalid: there is already a rule named 'indentation'"]]
+            .assert_eq(&error_of("indentation.rhai", "fn check_token(token) { }"));
+    }
+}
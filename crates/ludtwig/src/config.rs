@@ -1,41 +1,128 @@
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fmt::{Display, Formatter};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
 
-use figment::providers::{Env, Format as FigFormat, Toml};
-use figment::Figment;
+use figment::providers::{Format as FigFormat, Serialized, Toml};
+use figment::value::{Dict, Map, Value};
+use figment::{Figment, Metadata, Profile, Provider};
 use regex::Regex;
-use serde::Deserialize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-use crate::Opts;
+use crate::check::custom::load_query_rules;
+use crate::check::rule::{Rule, Severity};
+use crate::check::rules::get_config_active_rule_definitions;
+use crate::check::script::load_script_rules;
+use crate::error::ConfigurationError;
+use crate::output::{error, exit_code, info};
+use crate::project::Project;
+use crate::{ConfigCommand, Opts};
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
-#[serde(rename_all = "kebab-case")]
+mod migrate;
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Config {
+    /// The ludtwig version this config was written for.
     pub version: String,
+    /// Other config files or built-in presets (`ludtwig:<name>`) this config is based on.
+    #[serde(default)]
+    pub extends: Vec<String>,
     pub general: General,
     pub format: Format,
+    /// How template names of `extends`, `include`, ... are resolved to files.
+    #[serde(default)]
+    pub templates: Templates,
+    /// Rules which report everything matched by a query (`[[custom-rules]]`).
+    #[serde(default)]
+    pub custom_rules: Vec<CustomRule>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct General {
+    /// The rules which should run on the files.
     pub active_rules: Vec<String>,
+    /// Rhai scripts with custom rules (relative to the config file), which run in addition to the active rules.
+    #[serde(default)]
+    pub script_rules: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Format {
+    /// How should the line endings look like? Checked by the 'line-ending' rule.
     pub line_ending: LineEnding,
+    /// Which character should be used for indentation. Checked by the 'indentation' rule.
     pub indentation_mode: IndentationMode,
+    /// How many times should the indentation character be printed for one indentation.
+    /// Checked by the 'indentation' rule.
     pub indentation_count: u8,
+    /// Should the children of twig blocks be indented? Checked by the 'indentation' rule.
     pub indent_children_of_blocks: bool,
+    /// Should twig blocks have empty lines around them? Checked by the 'twig-block-line-breaks' rule.
     pub linebreaks_around_blocks: bool,
+    /// How twig strings should be quotated. Checked by the 'twig-string-quotation' rule.
     pub twig_quotation: Quotation,
+    /// How html attribute strings should be quotated. Checked by the 'html-string-quotation' rule.
     pub html_quotation: Quotation,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone, Default)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Templates {
+    /// The directories (relative to the config file) of each twig namespace, like
+    /// `Storefront = ["vendor/shopware/storefront/Resources/views"]`. `__main__` is used for names without a namespace.
+    #[serde(default)]
+    pub namespaces: BTreeMap<String, Vec<String>>,
+    /// The shopware bundle inheritance order from the most specific bundle (like your theme) to `Storefront`.
+    /// `sw_extends` and `sw_include` search the template in the namespaces of these bundles in this order.
+    #[serde(default)]
+    pub bundles: Vec<String>,
+}
+
+/// A rule which reports (and optionally replaces) every element of the syntax tree matched by its query.
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct CustomRule {
+    /// The name of the rule, which is used in the output and ignore directives.
+    pub name: String,
+    /// Which elements are reported, like `HTML_TAG[name="font"]`. Elements can be captured with `@name`.
+    pub query: String,
+    /// The message of the diagnostic, where `$name` is replaced by the text of a captured element.
+    pub message: String,
+    pub severity: CustomRuleSeverity,
+    /// Replaces the matched element with `--fix`, where `$name` is replaced by the text of a captured element.
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum CustomRuleSeverity {
+    Error,
+    Warning,
+    Help,
+    Info,
+}
+
+impl CustomRuleSeverity {
+    #[must_use]
+    pub fn corresponding_severity(&self) -> Severity {
+        match self {
+            CustomRuleSeverity::Error => Severity::Error,
+            CustomRuleSeverity::Warning => Severity::Warning,
+            CustomRuleSeverity::Help => Severity::Help,
+            CustomRuleSeverity::Info => Severity::Info,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub enum IndentationMode {
     Space,
@@ -56,6 +143,7 @@ impl Display for IndentationMode {
 }
 
 impl IndentationMode {
+    #[must_use]
     pub fn corresponding_char(&self) -> char {
         match self {
             IndentationMode::Space => ' ',
@@ -64,7 +152,7 @@ impl IndentationMode {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
 pub enum LineEnding {
     #[serde(rename = "unix_LF")]
     UnixLF,
@@ -86,6 +174,7 @@ impl Display for LineEnding {
 }
 
 impl LineEnding {
+    #[must_use]
     pub fn corresponding_string(&self) -> &'static str {
         match self {
             LineEnding::UnixLF => "\n",
@@ -94,7 +183,7 @@ impl LineEnding {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Deserialize, Serialize, JsonSchema, PartialEq, Eq, Clone)]
 pub enum Quotation {
     #[serde(rename = "single")]
     Single,
@@ -116,6 +205,7 @@ impl Display for Quotation {
 }
 
 impl Quotation {
+    #[must_use]
     pub fn corresponding_char(&self) -> char {
         match self {
             Quotation::Single => '\'',
@@ -124,99 +214,889 @@ impl Quotation {
     }
 }
 
+pub const CONFIG_FILE_NAME: &str = "ludtwig-config.toml";
 pub const DEFAULT_CONFIG_PATH: &str = "./ludtwig-config.toml";
 pub const DEFAULT_RAW_CONFIG: &str = include_str!("../ludtwig-config.toml");
 
+/// Prefix of the environment variables which override config values.
+const ENV_PREFIX: &str = "LUDTWIG_";
+
 pub const LUDTWIG_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Prefix for referencing one of the [`PRESETS`] inside `extends`.
+pub const PRESET_PREFIX: &str = "ludtwig:";
+
+/// Built-in configurations which can be used with `extends = ["ludtwig:<name>"]`.
+pub static PRESETS: &[(&str, &str)] = &[
+    (
+        "shopware-storefront",
+        include_str!("../presets/shopware-storefront.toml"),
+    ),
+    (
+        "shopware-admin",
+        include_str!("../presets/shopware-admin.toml"),
+    ),
+    ("symfony", include_str!("../presets/symfony.toml")),
+    ("drupal", include_str!("../presets/drupal.toml")),
+    ("craft", include_str!("../presets/craft.toml")),
+];
+
+/// Only the `extends` field of a config, which is needed before the rest of it can be merged.
+#[derive(Debug, Default, Deserialize)]
+struct Extends {
+    #[serde(default)]
+    extends: Vec<String>,
+}
+
 impl Config {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, figment::Error> {
-        let config: Config = Figment::new()
-            // first read the raw config from memory (for default values)
-            .merge(Toml::string(
-                &DEFAULT_RAW_CONFIG.replace("{{LUDTWIG_VERSION}}", LUDTWIG_VERSION),
-            ))
-            // then read the config in the file system (if it exists)
-            .merge(Toml::file(path))
-            // last read from the environment
-            .merge(
-                Env::prefixed("LUDTWIG_")
-                    .split("__")
-                    .map(|key| key.as_str().replace('_', "-").into()),
-            )
-            .extract()?;
+    /// Load the config file at the path (the default config if it doesn't exist) together with everything it extends
+    /// and the overrides from `LUDTWIG_` environment variables.
+    ///
+    /// # Errors
+    /// if a config file can't be read or is invalid.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, ConfigurationError> {
+        Self::with_env(path.as_ref(), env::vars())
+    }
+
+    /// Like [`Config::new`] with the given environment variables instead of the ones of the process.
+    fn with_env(
+        path: &Path,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigurationError> {
+        // first read the raw config from memory (for default values)
+        let mut figment = Figment::new().merge(Toml::string(
+            &DEFAULT_RAW_CONFIG.replace("{{LUDTWIG_VERSION}}", LUDTWIG_VERSION),
+        ));
+
+        // then read the config in the file system (if it exists) together with everything it extends
+        if path.exists() {
+            figment = merge_config_file(figment, path, &mut vec![])?;
+        }
+
+        // last read from the environment
+        let config: Config = figment
+            .merge(EnvOverrides::new(vars))
+            .extract()
+            .map_err(|e| invalid_config_error(path, e))?;
 
         Ok(config)
     }
 }
 
-pub fn handle_config_or_exit(opts: &Opts) -> Config {
-    let config_path = opts
-        .config_path
-        .clone()
-        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+/// The `LUDTWIG_` environment variables which override config values (like `LUDTWIG_FORMAT__INDENTATION_COUNT`)
+/// as config keys and their values. Variables which don't match a config key are ignored, because they can belong
+/// to something else.
+struct EnvOverrides(Vec<(String, String)>);
+
+impl EnvOverrides {
+    fn new(vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let overrides = vars
+            .into_iter()
+            .filter_map(|(variable, value)| {
+                let key = env_config_key(variable.strip_prefix(ENV_PREFIX)?);
+                is_config_key(&key).then_some((key, value))
+            })
+            .collect();
+
+        Self(overrides)
+    }
+}
+
+impl Provider for EnvOverrides {
+    fn metadata(&self) -> Metadata {
+        Metadata::named(format!("`{ENV_PREFIX}` environment variable(s)")).interpolater(
+            |_, keys| {
+                let keys: Vec<String> = keys
+                    .iter()
+                    .map(|key| key.replace('-', "_").to_ascii_uppercase())
+                    .collect();
+                format!("{ENV_PREFIX}{}", keys.join("__"))
+            },
+        )
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        let mut dict = Dict::new();
+        for (key, value) in &self.0 {
+            // parsed like figment does for environment variables (`2` is a number, `true` a boolean, ...)
+            let value: Value = value.parse().expect("parsing a value is infallible");
+            if let Some(nested) = figment::util::nest(key, value).into_dict() {
+                merge_dicts(&mut dict, nested);
+            }
+        }
+
+        Ok(Profile::Default.collect(dict))
+    }
+}
+
+fn merge_dicts(dict: &mut Dict, other: Dict) {
+    for (key, value) in other {
+        match (dict.get_mut(&key), value) {
+            (Some(Value::Dict(_, existing)), Value::Dict(_, nested)) => {
+                merge_dicts(existing, nested);
+            }
+            (_, value) => {
+                dict.insert(key, value);
+            }
+        }
+    }
+}
+
+/// The config key of an environment variable without the prefix (`FORMAT__INDENTATION_COUNT` is
+/// `format.indentation-count`).
+fn env_config_key(variable: &str) -> String {
+    variable
+        .to_ascii_lowercase()
+        .replace("__", ".")
+        .replace('_', "-")
+}
+
+/// The key is in the default config or inside of one of its tables (like `format.indentation-count`).
+fn is_config_key(key: &str) -> bool {
+    static KEYS: OnceLock<Vec<String>> = OnceLock::new();
+    let keys = KEYS.get_or_init(|| {
+        let document: toml_edit::DocumentMut = DEFAULT_RAW_CONFIG
+            .parse()
+            .expect("default config should be valid toml");
+        let mut keys = vec![];
+        collect_keys(document.as_table(), "", &mut keys);
+        keys
+    });
+
+    keys.iter().any(|known| {
+        key == known
+            || key
+                .strip_prefix(known.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+fn collect_keys(table: &dyn toml_edit::TableLike, prefix: &str, keys: &mut Vec<String>) {
+    for (key, item) in table.iter() {
+        let key = format!("{prefix}{key}");
+        if let Some(table) = item.as_table_like() {
+            collect_keys(table, &format!("{key}."), keys);
+        }
+        keys.push(key);
+    }
+}
+
+/// Turn a figment error into a [`ConfigurationError`] and suggest the closest known key for unknown ones.
+fn invalid_config_error(path: &Path, error: figment::Error) -> ConfigurationError {
+    let figment::error::Kind::UnknownField(field, expected) = &error.kind else {
+        return ConfigurationError::Invalid {
+            path: path.to_owned(),
+            error: Box::new(error),
+        };
+    };
+
+    let suggestion = expected
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(field, candidate), candidate))
+        .filter(|(confidence, _)| *confidence > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| (*candidate).to_string());
+    let key = error.path.join(".");
+    let origin = error.metadata.as_ref().map_or_else(
+        || path.to_string_lossy().to_string(),
+        |m| {
+            m.source
+                .as_ref()
+                .map_or_else(|| m.name.to_string(), ToString::to_string)
+        },
+    );
+
+    ConfigurationError::UnknownKey {
+        origin,
+        key,
+        suggestion,
+    }
+}
+
+/// JSON Schema of the config file, which editors can use for validation and autocompletion.
+pub fn json_schema() -> String {
+    let mut schema = serde_json::to_value(schemars::schema_for!(Config))
+        .expect("config schema should be serializable");
+    // every key is optional in a config file, because missing ones are taken from the default config
+    remove_required(&mut schema);
+
+    serde_json::to_string_pretty(&schema).expect("config schema should be serializable")
+}
+
+fn remove_required(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.remove("required");
+            map.values_mut().for_each(remove_required);
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(remove_required),
+        _ => {}
+    }
+}
+
+/// Merge a config file on top of `figment`, after merging all the configs it extends.
+fn merge_config_file(
+    figment: Figment,
+    path: &Path,
+    stack: &mut Vec<String>,
+) -> Result<Figment, ConfigurationError> {
+    let raw = fs::read_to_string(path).map_err(|io_error| ConfigurationError::FileRead {
+        path: path.to_owned(),
+        io_error,
+    })?;
+    let origin = fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_owned())
+        .to_string_lossy()
+        .to_string();
+    let base_dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    let figment = merge_extends(figment, path, &raw, origin, Some(base_dir), stack)?;
+    let figment = figment.merge(Toml::file(path));
+    Ok(resolve_relative_paths(figment, &raw, base_dir))
+}
+
+/// Resolve the relative paths of the config source `raw` from `base_dir` (the directory of its file),
+/// so they stay relative to the file which declared them when another config extends it.
+fn resolve_relative_paths(mut figment: Figment, raw: &str, base_dir: &Path) -> Figment {
+    let base_dir = std::path::absolute(base_dir).unwrap_or_else(|_| base_dir.to_owned());
+    let resolve = |path: &String| base_dir.join(path).to_string_lossy().to_string();
+    let declared = Figment::from(Toml::string(raw));
+
+    if let Ok(script_rules) = declared.extract_inner::<Vec<String>>("general.script-rules") {
+        let script_rules: Vec<String> = script_rules.iter().map(resolve).collect();
+        figment = figment.merge(Serialized::default("general.script-rules", script_rules));
+    }
+    if let Ok(namespaces) =
+        declared.extract_inner::<BTreeMap<String, Vec<String>>>("templates.namespaces")
+    {
+        let namespaces: BTreeMap<String, Vec<String>> = namespaces
+            .iter()
+            .map(|(namespace, dirs)| (namespace.clone(), dirs.iter().map(resolve).collect()))
+            .collect();
+        figment = figment.merge(Serialized::default("templates.namespaces", namespaces));
+    }
+
+    figment
+}
+
+/// Merge everything the config source `raw` extends on top of `figment` in the order of the `extends` field.
+/// Relative paths are resolved from `base_dir`, which is `None` for presets (those can only extend other presets).
+fn merge_extends(
+    mut figment: Figment,
+    path: &Path,
+    raw: &str,
+    origin: String,
+    base_dir: Option<&Path>,
+    stack: &mut Vec<String>,
+) -> Result<Figment, ConfigurationError> {
+    if stack.contains(&origin) {
+        let mut chain = stack.clone();
+        chain.push(origin);
+        return Err(ConfigurationError::ExtendsCycle { chain });
+    }
+
+    let Extends { extends } =
+        Figment::from(Toml::string(raw))
+            .extract()
+            .map_err(|e| ConfigurationError::Invalid {
+                path: path.to_owned(),
+                error: Box::new(e),
+            })?;
+
+    stack.push(origin);
+    for entry in extends {
+        if let Some(name) = entry.strip_prefix(PRESET_PREFIX) {
+            let Some((_, preset_raw)) = PRESETS.iter().find(|(n, _)| *n == name) else {
+                return Err(ConfigurationError::PresetNotFound {
+                    name: name.to_string(),
+                });
+            };
+
+            figment = merge_extends(figment, path, preset_raw, entry.clone(), None, stack)?;
+            figment = figment.merge(Toml::string(preset_raw));
+        } else {
+            let Some(base_dir) = base_dir else {
+                return Err(ConfigurationError::RelativeExtendsInPreset {
+                    preset: stack.last().cloned().unwrap_or_default(),
+                    entry,
+                });
+            };
+
+            figment = merge_config_file(figment, &base_dir.join(&entry), stack)?;
+        }
+    }
+    stack.pop();
+
+    Ok(figment)
+}
+
+/// A loaded configuration together with the rules it activates.
+#[derive(Debug, Clone)]
+pub struct ActiveConfig {
+    /// The config file this was loaded from, `None` if no config file exists (default config is used).
+    pub path: Option<PathBuf>,
+    /// The config values to use.
+    pub config: Config,
+    /// Config active rule definitions
+    pub rule_definitions: Vec<Arc<dyn Rule>>,
+    /// The templates of the config, created when they are used the first time.
+    pub project: OnceLock<Project>,
+}
+
+impl ActiveConfig {
+    pub fn new(config: Config, path: Option<PathBuf>) -> Result<Self, ConfigurationError> {
+        let mut rule_definitions = get_config_active_rule_definitions(&config)?;
+        let base_dir = config_dir(path.as_deref());
+        rule_definitions.extend(load_script_rules(&config.general.script_rules, base_dir)?);
+        let query_rules = load_query_rules(&config.custom_rules, &rule_definitions)?;
+        rule_definitions.extend(query_rules);
+
+        Ok(Self {
+            path,
+            config,
+            rule_definitions,
+            project: OnceLock::new(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigurationError> {
+        let config = Config::new(path)?;
+        Self::new(config, path.exists().then(|| path.to_owned()))
+    }
+
+    /// The templates of the project, which resolves template names relative to the config file.
+    pub fn project(&self) -> &Project {
+        self.project
+            .get_or_init(|| Project::new(&self.config.templates, config_dir(self.path.as_deref())))
+    }
+}
+
+/// Paths in a config file are relative to its directory (or the working directory for the default config).
+fn config_dir(path: Option<&Path>) -> &Path {
+    path.and_then(Path::parent).unwrap_or(Path::new(""))
+}
+
+/// Finds the configuration which applies to a scanned file.
+#[derive(Debug)]
+pub struct ConfigResolver {
+    /// Used for files without any config file in their parent directories
+    /// or for all files if the config path was specified explicitly.
+    fallback: RwLock<Arc<ActiveConfig>>,
+    /// Look for the nearest `ludtwig-config.toml` from each file upwards.
+    discover: bool,
+    verbose: bool,
+    /// Already loaded config files by their canonical path.
+    loaded: Mutex<HashMap<PathBuf, Arc<ActiveConfig>>>,
+    /// Used instead of the active rules of every config (`ludtwig ssr`)
+    rules: Option<Vec<Arc<dyn Rule>>>,
+}
+
+impl ConfigResolver {
+    pub fn new(fallback: ActiveConfig, discover: bool, verbose: bool) -> Self {
+        let fallback = Arc::new(fallback);
+        Self {
+            loaded: Mutex::new(Self::initially_loaded(&fallback)),
+            fallback: RwLock::new(fallback),
+            discover,
+            verbose,
+            rules: None,
+        }
+    }
+
+    /// Only run these rules instead of the active rules of the configs, which still provide the config values
+    /// (like the replacements of `ludtwig ssr`).
+    #[must_use]
+    pub fn with_rules(mut self, rules: Vec<Arc<dyn Rule>>) -> Self {
+        self.rules = Some(rules);
+        let fallback = Arc::new(self.use_rules(self.fallback().as_ref().clone()));
+        self.loaded = Mutex::new(Self::initially_loaded(&fallback));
+        self.fallback = RwLock::new(fallback);
+        self
+    }
+
+    /// Replace the rules of a loaded config with the [`Self::with_rules`].
+    fn use_rules(&self, mut active: ActiveConfig) -> ActiveConfig {
+        if let Some(rules) = &self.rules {
+            active.rule_definitions.clone_from(rules);
+        }
+        active
+    }
+
+    fn initially_loaded(fallback: &Arc<ActiveConfig>) -> HashMap<PathBuf, Arc<ActiveConfig>> {
+        let mut loaded = HashMap::new();
+        if let Some(path) = &fallback.path {
+            loaded.insert(
+                fs::canonicalize(path).unwrap_or_else(|_| path.clone()),
+                Arc::clone(fallback),
+            );
+        }
+        loaded
+    }
+
+    pub fn fallback(&self) -> Arc<ActiveConfig> {
+        Arc::clone(&self.fallback.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Forget all loaded config files, so they are loaded again after they (or their script rules) changed (`--watch`).
+    /// The fallback is loaded again, too, if it was read from a file. It stays unchanged if it is invalid now.
+    ///
+    /// # Panics
+    /// if another thread panicked while loading a config file.
+    pub fn reload(&self) -> Result<(), ConfigurationError> {
+        let mut fallback = self
+            .fallback
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        let result = match &fallback.path {
+            Some(path) => ActiveConfig::load(path).map(|active| {
+                report_loaded_config(&active, self.verbose);
+                *fallback = Arc::new(self.use_rules(active));
+            }),
+            None => Ok(()),
+        };
+
+        *self
+            .loaded
+            .lock()
+            .expect("config cache should not be poisoned") = Self::initially_loaded(&fallback);
+        result
+    }
+
+    /// Get the config of the nearest `ludtwig-config.toml` in the directory of `file_path` or any of its parents.
+    ///
+    /// # Panics
+    /// if another thread panicked while loading a config file.
+    pub fn resolve(&self, file_path: &Path) -> Result<Arc<ActiveConfig>, ConfigurationError> {
+        if !self.discover {
+            return Ok(self.fallback());
+        }
+
+        let Some(config_path) = find_nearest_config_file(file_path) else {
+            return Ok(self.fallback());
+        };
+        let key = fs::canonicalize(&config_path).unwrap_or_else(|_| config_path.clone());
+
+        let loaded = || {
+            self.loaded
+                .lock()
+                .expect("config cache should not be poisoned")
+        };
+        if let Some(active) = loaded().get(&key) {
+            return Ok(Arc::clone(active));
+        }
 
-    if opts.create_config {
-        if Path::exists(config_path.as_ref()) {
-            println!("The configuration file already exists at the location {config_path:?}. \
-            Try choosing a different location with '-c my-path' or make a backup of your current config file (rename it).");
-            std::process::exit(1);
+        // load without holding the lock, so files of other configs don't wait for it.
+        // another thread can load the same config meanwhile, then the first one is kept.
+        let active = Arc::new(self.use_rules(ActiveConfig::load(&config_path)?));
+        match loaded().entry(key) {
+            Entry::Occupied(entry) => return Ok(Arc::clone(entry.get())),
+            Entry::Vacant(entry) => entry.insert(Arc::clone(&active)),
+        };
+        report_loaded_config(&active, self.verbose);
+
+        Ok(active)
+    }
+}
+
+/// Walk up from the directory of `file_path` and return the first config file found.
+fn find_nearest_config_file(file_path: &Path) -> Option<PathBuf> {
+    file_path
+        .ancestors()
+        .skip(1)
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|p| p.is_file())
+}
+
+/// Print out where a config was loaded from and warn about outdated config versions.
+fn report_loaded_config(active: &ActiveConfig, verbose: bool) {
+    let c = &active.config;
+
+    let raw_user_config = match &active.path {
+        Some(path) => {
+            info!("Loaded configuration file at {}", path.to_string_lossy());
+            std::fs::read_to_string(path).unwrap_or_default()
         }
+        None => String::new(),
+    };
 
-        let config_raw = DEFAULT_RAW_CONFIG.replace("{{LUDTWIG_VERSION}}", LUDTWIG_VERSION);
-        std::fs::write(&config_path, config_raw).expect("can't write default config");
-        println!(
-            "Default config was written to {}",
-            config_path.to_string_lossy()
+    let version_field_regex = Regex::new("version\\s?=").unwrap();
+    if c.version != LUDTWIG_VERSION {
+        info!(
+            "Warning: The version of the config file ({}) does not match the version of ludtwig ({}). You should update your config file with 'ludtwig config migrate' and carefully read the changelog to not miss out on new features.",
+            c.version, LUDTWIG_VERSION
         );
+    } else if active.path.is_some() && !version_field_regex.is_match(&raw_user_config) {
+        // ToDo #119: this edge case should be removed in future versions, the version field was introduced in 0.9.0
+        info!(
+            "Warning: The version of the config file (UNKNOWN) does not match the version of ludtwig ({LUDTWIG_VERSION}). You should update your config file with 'ludtwig config migrate' and carefully read the changelog to not miss out on new features.",
+        );
+    }
 
-        std::process::exit(0);
+    if verbose {
+        info!("Used config values: \n{c:#?}");
     }
+}
+
+/// Run a `ludtwig config` subcommand and return the process exit code.
+pub fn handle_config_command(command: &ConfigCommand, opts: &Opts) -> i32 {
+    match command {
+        ConfigCommand::Schema => {
+            println!("{}", json_schema());
+            0
+        }
+        ConfigCommand::Migrate => {
+            let config_path = opts
+                .config_path
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
 
-    match Config::new(config_path.clone()) {
-        Ok(c) => {
-            if config_path.exists() {
+            let raw = match fs::read_to_string(&config_path) {
+                Ok(raw) => raw,
+                Err(e) => {
+                    error!(
+                        "Error: can't read the config file at {}: {e}",
+                        config_path.display()
+                    );
+                    return exit_code::CONFIG_ERROR;
+                }
+            };
+
+            let migrated = match migrate::migrate(&config_path, &raw) {
+                Ok(migrated) => migrated,
+                Err(e) => {
+                    error!("Error: {e}");
+                    return exit_code::CONFIG_ERROR;
+                }
+            };
+
+            if migrated.changes.is_empty() {
                 println!(
-                    "Loaded configuration file at {}",
-                    config_path.to_string_lossy()
+                    "The config file at {} is already up to date",
+                    config_path.display()
                 );
-            } else {
-                println!(
-                    "Using default config, because no config file found at {}",
-                    config_path.to_string_lossy()
+                return 0;
+            }
+
+            if let Err(e) = fs::write(&config_path, migrated.source) {
+                error!(
+                    "Error: can't write the config file at {}: {e}",
+                    config_path.display()
                 );
+                return exit_code::CONFIG_ERROR;
             }
 
-            for (k, v) in env::vars() {
-                if k.starts_with("LUDTWIG_") {
-                    println!("Found environment variable for overriding config: {k}={v}");
-                }
+            println!("Migrated the config file at {}:", config_path.display());
+            for change in migrated.changes {
+                println!("- {change}");
             }
+            0
+        }
+    }
+}
 
-            let raw_user_config = std::fs::read_to_string(config_path).unwrap_or_default();
-            let version_field_regex = Regex::new("version\\s?=").unwrap();
-            if c.version != LUDTWIG_VERSION {
-                println!(
-                    "Warning: The version of the config file ({}) does not match the version of ludtwig ({}). You should update your config file and set it to the same version when you are done. To update you should carefully read the changelog or generate a new config with 'ludtwig -C' to not miss out on new features.",
-                    c.version, LUDTWIG_VERSION
-                );
-            } else if !version_field_regex.is_match(&raw_user_config) {
-                // ToDo #119: this edge case should be removed in future versions, the version field was introduced in 0.9.0
-                println!(
-                    "Warning: The version of the config file (UNKNOWN) does not match the version of ludtwig ({LUDTWIG_VERSION}). You should update your config file and set it to the same version when you are done. To update you should carefully read the changelog or generate a new config with 'ludtwig -C' to not miss out on new features.",
+/// Write the default config file to the config path and return the process exit code.
+pub fn create_default_config(opts: &Opts) -> i32 {
+    let config_path = opts
+        .config_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+    if Path::exists(config_path.as_ref()) {
+        println!("The configuration file already exists at the location {}. \
+        Try choosing a different location with '-c my-path' or make a backup of your current config file (rename it).", config_path.display());
+        return 1;
+    }
+
+    let config_raw = DEFAULT_RAW_CONFIG.replace("{{LUDTWIG_VERSION}}", LUDTWIG_VERSION);
+    std::fs::write(&config_path, config_raw).expect("can't write default config");
+    println!(
+        "Default config was written to {}",
+        config_path.to_string_lossy()
+    );
+
+    0
+}
+
+pub fn handle_config_or_exit(opts: &Opts) -> ConfigResolver {
+    let config_path = opts
+        .config_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH));
+
+    match ActiveConfig::load(&config_path) {
+        Ok(active) => {
+            if active.path.is_none() {
+                info!(
+                    "Using default config, because no config file found at {}",
+                    config_path.to_string_lossy()
                 );
             }
+            report_loaded_config(&active, opts.verbose);
 
-            if opts.verbose {
-                println!("Used config values: \n{c:#?}");
+            for (k, v) in env::vars() {
+                if let Some(variable) = k.strip_prefix(ENV_PREFIX) {
+                    if is_config_key(&env_config_key(variable)) {
+                        info!("Found environment variable for overriding config: {k}={v}");
+                    }
+                }
             }
 
-            c
+            // only look for the nearest config of each file if no config path was given explicitly
+            ConfigResolver::new(active, opts.config_path.is_none(), opts.verbose)
         }
         Err(e) => {
-            println!("Error reading config:");
-            println!("{e}");
-            std::process::exit(1)
+            error!("Error reading config:");
+            error!("{e}");
+            std::process::exit(exit_code::CONFIG_ERROR)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use clap::Parser;
+    use figment::Figment;
+
+    use super::{
+        env_config_key, find_nearest_config_file, handle_config_command, is_config_key,
+        merge_extends, ActiveConfig, Config, ConfigResolver, IndentationMode, CONFIG_FILE_NAME,
+        DEFAULT_CONFIG_PATH,
+    };
+    use crate::check::rules::RULE_DEFINITIONS;
+    use crate::error::ConfigurationError;
+    use crate::output::exit_code;
+    use crate::project::TemplateReferenceKind;
+    use crate::test_util::temp_dir;
+    use crate::{ConfigCommand, Opts};
+
+    #[test]
+    fn config_extends_preset() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, "extends = [\"ludtwig:drupal\"]\n").unwrap();
+
+        let config = Config::new(&path).unwrap();
+        assert_eq!(config.format.indentation_count, 2);
+        assert!(!config
+            .general
+            .active_rules
+            .contains(&"twig-prefer-shopware-extends".to_string()));
+    }
+
+    #[test]
+    fn config_extends_file_and_overrides_it() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("plugin")).unwrap();
+        fs::write(
+            dir.join("base.toml"),
+            "extends = [\"ludtwig:symfony\"]\n[format]\nindentation-mode = \"tab\"\nindentation-count = 1\n",
+        )
+        .unwrap();
+        let path = dir.join("plugin").join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            "extends = [\"../base.toml\"]\n[format]\nindentation-count = 2\n",
+        )
+        .unwrap();
+
+        let config = Config::new(&path).unwrap();
+        assert_eq!(config.format.indentation_mode, IndentationMode::Tab);
+        assert_eq!(config.format.indentation_count, 2);
+        assert!(!config.format.linebreaks_around_blocks);
+    }
+
+    #[test]
+    fn config_namespaces_are_relative_to_the_extended_config() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("shared/views")).unwrap();
+        fs::write(dir.join("shared/views/base.html.twig"), "").unwrap();
+        fs::write(
+            dir.join("shared").join(CONFIG_FILE_NAME),
+            "[templates.namespaces]\nShared = [\"views\"]\n",
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("project")).unwrap();
+        let path = dir.join("project").join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            "extends = [\"../shared/ludtwig-config.toml\"]\n[templates.namespaces]\nProject = [\"views\"]\n",
+        )
+        .unwrap();
+
+        let active = ActiveConfig::load(&path).unwrap();
+        let project = active.project();
+        let resolve = |name: &str| {
+            project.resolve(
+                name,
+                TemplateReferenceKind::Include,
+                &dir.join("project/views/page.html.twig"),
+            )
+        };
+        assert_eq!(
+            resolve("@Shared/base.html.twig"),
+            Some(fs::canonicalize(dir.join("shared/views/base.html.twig")).unwrap())
+        );
+        assert_eq!(resolve("@Project/base.html.twig"), None);
+    }
+
+    #[test]
+    fn config_extends_cycle() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::write(dir.join("a.toml"), "extends = [\"b.toml\"]\n").unwrap();
+        fs::write(dir.join("b.toml"), "extends = [\"a.toml\"]\n").unwrap();
+
+        assert!(matches!(
+            Config::new(dir.join("a.toml")),
+            Err(ConfigurationError::ExtendsCycle { .. })
+        ));
+    }
+
+    #[test]
+    fn config_extends_unknown_preset() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, "extends = [\"ludtwig:wordpress\"]\n").unwrap();
+
+        assert!(matches!(
+            Config::new(&path),
+            Err(ConfigurationError::PresetNotFound { name }) if name == "wordpress"
+        ));
+    }
+
+    #[test]
+    fn config_preset_with_relative_extends() {
+        let error = merge_extends(
+            Figment::new(),
+            Path::new("ludtwig:broken"),
+            "extends = [\"base.toml\"]\n",
+            "ludtwig:broken".to_string(),
+            None,
+            &mut vec![],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "relative extends not allowed in presets: ludtwig:broken extends 'base.toml'"
+        );
+    }
+
+    #[test]
+    fn config_ignores_unrelated_env_variables() {
+        assert!(is_config_key("format.indentation-count"));
+        assert!(is_config_key("templates.namespaces.storefront"));
+        assert!(!is_config_key("log"));
+        assert_eq!(
+            env_config_key("FORMAT__INDENTATION_COUNT"),
+            "format.indentation-count"
+        );
+
+        let vars = [
+            ("LUDTWIG_UNRELATED_TOOL_SETTING", "1"),
+            ("LUDTWIG_FORMAT__INDENTATION_COUNT", "2"),
+            ("LUDTWIG_FORMAT__INDENTATION_MODE", "tab"),
+            ("FORMAT__INDENTATION_COUNT", "3"),
+        ]
+        .map(|(variable, value)| (variable.to_string(), value.to_string()));
+        let config = Config::with_env(Path::new(DEFAULT_CONFIG_PATH), vars).unwrap();
+        assert_eq!(config.format.indentation_count, 2);
+        assert_eq!(config.format.indentation_mode, IndentationMode::Tab);
+    }
+
+    #[test]
+    fn config_migrate_failures_are_config_errors() {
+        let temp = temp_dir();
+        let missing = temp.path().join(CONFIG_FILE_NAME);
+        let opts = Opts::try_parse_from([
+            "ludtwig".as_ref(),
+            "-c".as_ref(),
+            missing.as_os_str(),
+            "config".as_ref(),
+            "migrate".as_ref(),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            handle_config_command(&ConfigCommand::Migrate, &opts),
+            exit_code::CONFIG_ERROR
+        );
+    }
+
+    #[test]
+    fn config_unknown_key_suggestion() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let path = dir.join(CONFIG_FILE_NAME);
+        fs::write(&path, "[format]\nindentaton-count = 2\n").unwrap();
+
+        assert!(matches!(
+            Config::new(&path),
+            Err(ConfigurationError::UnknownKey { key, suggestion: Some(suggestion), .. })
+                if key == "format.indentaton-count" && suggestion == "indentation-count"
+        ));
+    }
+
+    #[test]
+    fn nearest_config_file_is_found() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("plugin/views/page")).unwrap();
+        fs::write(dir.join(CONFIG_FILE_NAME), "").unwrap();
+        fs::write(dir.join("plugin").join(CONFIG_FILE_NAME), "").unwrap();
+
+        assert_eq!(
+            find_nearest_config_file(&dir.join("plugin/views/page/index.html.twig")),
+            Some(dir.join("plugin").join(CONFIG_FILE_NAME))
+        );
+        assert_eq!(
+            find_nearest_config_file(&dir.join("index.html.twig")),
+            Some(dir.join(CONFIG_FILE_NAME))
+        );
+    }
+
+    #[test]
+    fn resolver_with_rules_uses_discovered_config_values() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[format]\nindentation-count = 2\n",
+        )
+        .unwrap();
+        let rule = Arc::clone(&RULE_DEFINITIONS[0]);
+        let fallback = ActiveConfig::new(Config::new(DEFAULT_CONFIG_PATH).unwrap(), None).unwrap();
+        let config_resolver = ConfigResolver::new(fallback, true, false).with_rules(vec![rule]);
+
+        let rule_names = |active: &ActiveConfig| -> Vec<String> {
+            active
+                .rule_definitions
+                .iter()
+                .map(|r| r.name().to_string())
+                .collect()
+        };
+        let expected = vec![RULE_DEFINITIONS[0].name().to_string()];
+        let active = config_resolver.resolve(&dir.join("a.html.twig")).unwrap();
+        assert_eq!(active.config.format.indentation_count, 2);
+        assert_eq!(rule_names(&active), expected);
+        assert_eq!(rule_names(&config_resolver.fallback()), expected);
+    }
+}
@@ -0,0 +1,380 @@
+use std::path::Path;
+
+use toml_edit::{value, Array, DocumentMut, Item, Key, Table, Value};
+
+use crate::config::LUDTWIG_VERSION;
+use crate::error::ConfigurationError;
+
+/// Changes to the config file format which were introduced by a ludtwig version.
+struct Migration {
+    version: &'static str,
+    /// Rules which were added in this version and should be activated.
+    added_rules: &'static [&'static str],
+    /// Rules which were removed in this version.
+    removed_rules: &'static [&'static str],
+    /// Config keys (dotted paths) which were renamed in this version (old, new).
+    renamed_keys: &'static [(&'static str, &'static str)],
+}
+
+/// All config migrations in ascending version order, add a new entry here when adding rules or changing keys!
+/// Use the version of the release which introduces the change, which must not be newer than ludtwig itself.
+static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: "0.8.0",
+        added_rules: &[
+            "twig-use-is-not-same-as",
+            "twig-use-is-same-as",
+            "twig-prefer-shopware-extends",
+        ],
+        removed_rules: &[],
+        renamed_keys: &[],
+    },
+    Migration {
+        version: "0.9.0",
+        added_rules: &["twig-block-duplicate"],
+        removed_rules: &["unknown-token"],
+        renamed_keys: &[],
+    },
+    Migration {
+        version: "0.10.0",
+        added_rules: &["ludtwig-directive-unused"],
+        removed_rules: &[],
+        renamed_keys: &[],
+    },
+];
+
+#[derive(Debug)]
+pub struct Migrated {
+    /// The migrated config file content (including all the comments of the original).
+    pub source: String,
+    /// Human-readable description of each change.
+    pub changes: Vec<String>,
+}
+
+/// Rewrite the raw content of a config file to the current ludtwig version.
+pub fn migrate(path: &Path, raw: &str) -> Result<Migrated, ConfigurationError> {
+    migrate_with(path, raw, MIGRATIONS, LUDTWIG_VERSION)
+}
+
+fn migrate_with(
+    path: &Path,
+    raw: &str,
+    migrations: &[Migration],
+    target_version: &str,
+) -> Result<Migrated, ConfigurationError> {
+    let mut doc: DocumentMut = raw.parse().map_err(|error| ConfigurationError::Syntax {
+        path: path.to_owned(),
+        error: Box::new(error),
+    })?;
+    let mut changes = vec![];
+
+    // the version field was introduced in 0.9.0, so configs without it are older than that
+    let config_version = match doc.get("version") {
+        Some(v) => {
+            let raw_version = v.as_str().unwrap_or_default();
+            parse_version(raw_version).ok_or_else(|| ConfigurationError::UnsupportedVersion {
+                path: path.to_owned(),
+                version: raw_version.to_string(),
+            })?
+        }
+        None => (0, 0, 0),
+    };
+    let target = parse_version(target_version).expect("ludtwig version should be valid");
+    if config_version > target {
+        return Err(ConfigurationError::UnsupportedVersion {
+            path: path.to_owned(),
+            version: doc["version"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+
+    for migration in migrations {
+        let version = parse_version(migration.version).expect("migration version should be valid");
+        if version <= config_version {
+            continue;
+        }
+
+        for (from, to) in migration.renamed_keys {
+            if rename_key(doc.as_table_mut(), from, to) {
+                changes.push(format!("renamed '{from}' to '{to}'"));
+            }
+        }
+
+        if let Some(active_rules) = doc
+            .get_mut("general")
+            .and_then(|g| g.get_mut("active-rules"))
+            .and_then(Item::as_array_mut)
+        {
+            for rule in migration.removed_rules {
+                if remove_rule(active_rules, rule) {
+                    changes.push(format!("removed rule '{rule}' (it no longer exists)"));
+                }
+            }
+
+            for rule in migration.added_rules {
+                if active_rules.iter().any(|r| r.as_str() == Some(rule)) {
+                    continue;
+                }
+
+                // copy the indentation of the existing entries (for example one rule per line) but no comments
+                let mut new_rule = Value::from(*rule);
+                if let Some(last) = active_rules.iter().last() {
+                    let prefix = last
+                        .decor()
+                        .prefix()
+                        .and_then(|p| p.as_str())
+                        .unwrap_or_default();
+                    let indentation = prefix.rfind('\n').map_or(" ", |i| &prefix[i..]);
+
+                    // keep a comment behind the last entry on its line
+                    let trailing = active_rules.trailing().as_str().unwrap_or_default();
+                    let (line_comment, rest) = trailing.split_at(trailing.find('\n').unwrap_or(0));
+                    new_rule
+                        .decor_mut()
+                        .set_prefix(format!("{line_comment}{indentation}"));
+                    let rest = rest.to_string();
+                    active_rules.set_trailing(rest);
+                }
+                active_rules.push_formatted(new_rule);
+                changes.push(format!(
+                    "activated rule '{rule}' (new in {})",
+                    migration.version
+                ));
+            }
+        }
+    }
+
+    if doc.get("version").and_then(Item::as_str) != Some(target_version) {
+        match doc.get_mut("version").and_then(Item::as_value_mut) {
+            Some(v) => {
+                let decor = v.decor().clone();
+                *v = Value::from(target_version);
+                *v.decor_mut() = decor;
+            }
+            None => {
+                doc["version"] = value(target_version);
+            }
+        }
+        changes.push(format!("set version to {target_version}"));
+    }
+
+    Ok(Migrated {
+        source: doc.to_string(),
+        changes,
+    })
+}
+
+/// Remove a rule from the array while keeping the comments in front of it.
+/// Returns true if the rule existed.
+fn remove_rule(active_rules: &mut Array, rule: &str) -> bool {
+    let Some(index) = active_rules.iter().position(|r| r.as_str() == Some(rule)) else {
+        return false;
+    };
+
+    let removed = active_rules.remove(index);
+    let removed_prefix = removed
+        .decor()
+        .prefix()
+        .and_then(|p| p.as_str())
+        .unwrap_or_default();
+    let comments = removed_prefix
+        .rfind('\n')
+        .map_or("", |i| &removed_prefix[..i]);
+
+    if comments.is_empty() {
+        return true;
+    }
+
+    if let Some(next) = active_rules.get_mut(index) {
+        let next_prefix = next
+            .decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or_default();
+        let prefix = format!("{comments}{next_prefix}");
+        next.decor_mut().set_prefix(prefix);
+    } else {
+        let trailing = active_rules.trailing().as_str().unwrap_or_default();
+        let trailing = format!("{comments}{trailing}");
+        active_rules.set_trailing(trailing);
+    }
+
+    true
+}
+
+/// Rename a dotted key path inside the table, keeping comments and position information of the key.
+/// Returns true if the key existed.
+fn rename_key(table: &mut Table, from: &str, to: &str) -> bool {
+    let Some((from_parent, from_name)) = split_key_path(from) else {
+        return false;
+    };
+    let Some(parent) = from_parent
+        .iter()
+        .try_fold(&mut *table, |t, segment| t.get_mut(segment)?.as_table_mut())
+    else {
+        return false;
+    };
+    let Some((old_key, item)) = parent.remove_entry(from_name) else {
+        return false;
+    };
+
+    let (to_parent, to_name) = split_key_path(to).expect("renamed key should not be empty");
+    let mut target = &mut *table;
+    for segment in to_parent {
+        target = target
+            .entry(segment)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .expect("renamed key parent should be a table");
+    }
+
+    let new_key = Key::new(to_name).with_leaf_decor(old_key.leaf_decor().clone());
+    target.insert_formatted(&new_key, item);
+    true
+}
+
+fn split_key_path(path: &str) -> Option<(Vec<&str>, &str)> {
+    let mut segments: Vec<&str> = path.split('.').collect();
+    let last = segments.pop()?;
+    Some((segments, last))
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.trim().split('.').map(str::parse::<u64>);
+    let version = (
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    );
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use expect_test::expect;
+
+    use super::{migrate, migrate_with, parse_version, Migration, MIGRATIONS};
+    use crate::config::LUDTWIG_VERSION;
+
+    #[test]
+    fn migrate_adds_rules_and_keeps_comments() {
+        let raw = r#"# my comment
+[general]
+active-rules = [
+    "indentation", # important
+    # we don't like this one
+    # "twig-block-line-breaks",
+    "unknown-token",
+]
+
+[format]
+# keep tabs
+indentation-mode = "tab"
+"#;
+
+        let migrated = migrate_with(Path::new("test.toml"), raw, MIGRATIONS, "0.10.0").unwrap();
+        expect![[r#"
+            version = "0.10.0"
+            # my comment
+            [general]
+            active-rules = [
+                "indentation", # important
+                # we don't like this one
+                # "twig-block-line-breaks",
+                "twig-use-is-not-same-as",
+                "twig-use-is-same-as",
+                "twig-prefer-shopware-extends",
+                "twig-block-duplicate",
+                "ludtwig-directive-unused",
+            ]
+
+            [format]
+            # keep tabs
+            indentation-mode = "tab"
+        "#]]
+        .assert_eq(&migrated.source);
+        expect![[r#"
+            [
+                "activated rule 'twig-use-is-not-same-as' (new in 0.8.0)",
+                "activated rule 'twig-use-is-same-as' (new in 0.8.0)",
+                "activated rule 'twig-prefer-shopware-extends' (new in 0.8.0)",
+                "removed rule 'unknown-token' (it no longer exists)",
+                "activated rule 'twig-block-duplicate' (new in 0.9.0)",
+                "activated rule 'ludtwig-directive-unused' (new in 0.10.0)",
+                "set version to 0.10.0",
+            ]
+        "#]]
+        .assert_debug_eq(&migrated.changes);
+    }
+
+    #[test]
+    fn migrate_only_applies_newer_migrations() {
+        let raw = "version = \"0.8.5\" # keep me\n\n[general]\nactive-rules = [\"indentation\"]\n";
+
+        let migrated = migrate_with(Path::new("test.toml"), raw, MIGRATIONS, "0.10.0").unwrap();
+        expect![[r#"
+            version = "0.10.0" # keep me
+
+            [general]
+            active-rules = ["indentation", "twig-block-duplicate", "ludtwig-directive-unused"]
+        "#]]
+        .assert_eq(&migrated.source);
+    }
+
+    #[test]
+    fn migrate_renames_keys() {
+        let migrations = [Migration {
+            version: "0.2.0",
+            added_rules: &[],
+            removed_rules: &[],
+            renamed_keys: &[("format.old-name", "format.new-name")],
+        }];
+        let raw = "version = \"0.1.0\"\n\n[format]\n# explanation\nold-name = 4\nother = true\n";
+
+        let migrated = migrate_with(Path::new("test.toml"), raw, &migrations, "0.2.0").unwrap();
+        expect![[r#"
+            version = "0.2.0"
+
+            [format]
+            other = true
+            # explanation
+            new-name = 4
+        "#]]
+        .assert_eq(&migrated.source);
+    }
+
+    #[test]
+    fn migrate_twice_has_no_changes() {
+        let raw = "version = \"0.9.0\"\n\n[general]\nactive-rules = [\"indentation\"]\n";
+
+        let migrated = migrate(Path::new("test.toml"), raw).unwrap();
+        assert!(!migrated.changes.is_empty());
+
+        let again = migrate(Path::new("test.toml"), &migrated.source).unwrap();
+        assert!(again.changes.is_empty());
+        assert_eq!(again.source, migrated.source);
+    }
+
+    #[test]
+    fn migrations_are_not_newer_than_ludtwig() {
+        let current = parse_version(LUDTWIG_VERSION).unwrap();
+        for migration in MIGRATIONS {
+            assert!(parse_version(migration.version).unwrap() <= current);
+        }
+    }
+
+    #[test]
+    fn migrate_up_to_date_has_no_changes() {
+        let raw = "version = \"0.10.0\"\n";
+
+        let migrated = migrate_with(Path::new("test.toml"), raw, MIGRATIONS, "0.10.0").unwrap();
+        assert!(migrated.changes.is_empty());
+        assert_eq!(migrated.source, raw);
+    }
+}
@@ -2,6 +2,8 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 
+use ludtwig_parser::syntax::untyped::TextSize;
+
 #[derive(Debug)]
 pub enum FileProcessingError {
     FileRead {
@@ -12,10 +14,28 @@ pub enum FileProcessingError {
         path: PathBuf,
         io_error: std::io::Error,
     },
-    MaxApplyIteration,
+    StdinRead {
+        io_error: std::io::Error,
+    },
+    StdoutWrite {
+        io_error: std::io::Error,
+    },
+    MaxApplyIteration {
+        path: PathBuf,
+        /// The fixes which were applied in the last iterations
+        last_iterations: Vec<Vec<AppliedFix>>,
+    },
     OverlappingSuggestionInSingleRule {
         rule_name: String,
     },
+    FixChangedStructure {
+        path: PathBuf,
+        rule_names: Vec<String>,
+    },
+    Configuration {
+        path: PathBuf,
+        error: ConfigurationError,
+    },
 }
 
 impl Display for FileProcessingError {
@@ -27,12 +47,65 @@ impl Display for FileProcessingError {
             FileProcessingError::FileWrite { path, .. } => {
                 write!(f, "file {} can't be written", path.to_string_lossy())
             }
-            FileProcessingError::MaxApplyIteration => {
-                write!(f, "max suggestion apply iteration encountered. This may be caused by fighting rules (programmer error) or too many conflicting suggestions at once")
+            FileProcessingError::StdinRead { .. } => {
+                write!(f, "stdin can't be read")
+            }
+            FileProcessingError::StdoutWrite { .. } => {
+                write!(f, "stdout can't be written")
+            }
+            FileProcessingError::MaxApplyIteration {
+                path,
+                last_iterations,
+            } => {
+                write!(f, "max suggestion apply iteration encountered for {}. This may be caused by fighting rules (programmer error) or too many conflicting suggestions at once", path.to_string_lossy())?;
+                if !last_iterations.is_empty() {
+                    write!(
+                        f,
+                        "\nThe rules kept changing these locations in the last iterations:"
+                    )?;
+                }
+                for (i, fixes) in last_iterations.iter().enumerate() {
+                    let fixes: Vec<String> = fixes.iter().map(ToString::to_string).collect();
+                    write!(f, "\n  {}. {}", i + 1, fixes.join(", "))?;
+                }
+                Ok(())
             }
             FileProcessingError::OverlappingSuggestionInSingleRule { rule_name } => {
                 write!(f, "Suggestion collision inside the same rule, check rule {rule_name} or write bug report - this is a programmer error")
             }
+            FileProcessingError::FixChangedStructure { path, rule_names } => {
+                write!(
+                    f,
+                    "the fixes of the rules {} would change the structure of {} (found by --verify-fixes), the file was not changed",
+                    rule_names.join(", "),
+                    path.to_string_lossy()
+                )
+            }
+            FileProcessingError::Configuration { path, error } => {
+                write!(
+                    f,
+                    "can't load the config for file {}: {error}",
+                    path.to_string_lossy()
+                )
+            }
+        }
+    }
+}
+
+/// The kind of failure an error is, which decides the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Like a file which can't be read or fixes which can't be applied
+    Processing,
+    Configuration,
+}
+
+impl FileProcessingError {
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            FileProcessingError::Configuration { .. } => ErrorKind::Configuration,
+            _ => ErrorKind::Processing,
         }
     }
 }
@@ -41,17 +114,90 @@ impl Error for FileProcessingError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             FileProcessingError::FileRead { io_error, .. }
-            | FileProcessingError::FileWrite { io_error, .. } => Some(io_error),
-            FileProcessingError::MaxApplyIteration
-            | FileProcessingError::OverlappingSuggestionInSingleRule { .. } => None,
+            | FileProcessingError::FileWrite { io_error, .. }
+            | FileProcessingError::StdinRead { io_error }
+            | FileProcessingError::StdoutWrite { io_error } => Some(io_error),
+            FileProcessingError::Configuration { error, .. } => Some(error),
+            FileProcessingError::MaxApplyIteration { .. }
+            | FileProcessingError::OverlappingSuggestionInSingleRule { .. }
+            | FileProcessingError::FixChangedStructure { .. } => None,
         }
     }
 }
 
+/// A fix which was applied in a single iteration, with the location in the source code before that iteration.
+#[derive(Debug)]
+pub struct AppliedFix {
+    pub rule_name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl AppliedFix {
+    #[must_use]
+    pub fn new(rule_name: &str, source_code: &str, offset: TextSize) -> Self {
+        let before = &source_code[..usize::from(offset)];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Self {
+            rule_name: rule_name.to_string(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl Display for AppliedFix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.rule_name, self.line, self.column)
+    }
+}
+
 /// Error related to configuration
 #[derive(Debug)]
 pub enum ConfigurationError {
-    RuleNotFound { name: String },
+    RuleNotFound {
+        name: String,
+    },
+    FileRead {
+        path: PathBuf,
+        io_error: std::io::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        error: Box<figment::Error>,
+    },
+    UnknownKey {
+        origin: String,
+        key: String,
+        suggestion: Option<String>,
+    },
+    Syntax {
+        path: PathBuf,
+        error: Box<toml_edit::TomlError>,
+    },
+    UnsupportedVersion {
+        path: PathBuf,
+        version: String,
+    },
+    PresetNotFound {
+        name: String,
+    },
+    RelativeExtendsInPreset {
+        preset: String,
+        entry: String,
+    },
+    ExtendsCycle {
+        chain: Vec<String>,
+    },
+    ScriptRule {
+        path: PathBuf,
+        message: String,
+    },
+    CustomRule {
+        name: String,
+        message: String,
+    },
 }
 
 impl Display for ConfigurationError {
@@ -60,8 +206,81 @@ impl Display for ConfigurationError {
             ConfigurationError::RuleNotFound { name } => {
                 write!(f, "Can't find active rule {name}")
             }
+            ConfigurationError::FileRead { path, .. } => {
+                write!(f, "config file {} can't be read", path.to_string_lossy())
+            }
+            ConfigurationError::Invalid { path, error } => {
+                write!(
+                    f,
+                    "config file {} is invalid: {error}",
+                    path.to_string_lossy()
+                )
+            }
+            ConfigurationError::UnknownKey {
+                origin,
+                key,
+                suggestion,
+            } => {
+                write!(f, "unknown config key '{key}' in {origin}")?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean '{suggestion}'?")?;
+                }
+                Ok(())
+            }
+            ConfigurationError::Syntax { path, error } => {
+                write!(
+                    f,
+                    "config file {} is invalid: {error}",
+                    path.to_string_lossy()
+                )
+            }
+            ConfigurationError::UnsupportedVersion { path, version } => {
+                write!(
+                    f,
+                    "config file {} has the unsupported version '{version}'",
+                    path.to_string_lossy()
+                )
+            }
+            ConfigurationError::PresetNotFound { name } => {
+                write!(f, "Can't find config preset {name}")
+            }
+            ConfigurationError::RelativeExtendsInPreset { preset, entry } => {
+                write!(
+                    f,
+                    "relative extends not allowed in presets: {preset} extends '{entry}'"
+                )
+            }
+            ConfigurationError::ExtendsCycle { chain } => {
+                write!(
+                    f,
+                    "config files extend each other in a cycle: {}",
+                    chain.join(" -> ")
+                )
+            }
+            ConfigurationError::ScriptRule { path, message } => {
+                write!(f, "script rule {} is invalid: {message}", path.display())
+            }
+            ConfigurationError::CustomRule { name, message } => {
+                write!(f, "custom rule '{name}' is invalid: {message}")
+            }
         }
     }
 }
 
-impl Error for ConfigurationError {}
+impl Error for ConfigurationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigurationError::FileRead { io_error, .. } => Some(io_error),
+            ConfigurationError::Invalid { error, .. } => Some(error.as_ref()),
+            ConfigurationError::Syntax { error, .. } => Some(error.as_ref()),
+            ConfigurationError::RuleNotFound { .. }
+            | ConfigurationError::UnknownKey { .. }
+            | ConfigurationError::UnsupportedVersion { .. }
+            | ConfigurationError::PresetNotFound { .. }
+            | ConfigurationError::RelativeExtendsInPreset { .. }
+            | ConfigurationError::ExtendsCycle { .. }
+            | ConfigurationError::ScriptRule { .. }
+            | ConfigurationError::CustomRule { .. } => None,
+        }
+    }
+}
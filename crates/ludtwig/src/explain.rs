@@ -0,0 +1,127 @@
+use crate::check::rule::{Rule, RuleCategory};
+use crate::check::rules::RULE_DEFINITIONS;
+use crate::config::{ActiveConfig, Config};
+use crate::output::error;
+
+/// Print all rules with their status under the given config and return the process exit code.
+pub fn print_rules(active: &ActiveConfig) -> i32 {
+    let name_width = all_rules(active)
+        .map(|r| r.name().len())
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "{:name_width$}  {:8}  {:11}  {:7}  description",
+        "rule", "status", "category", "fixable"
+    );
+    for rule in all_rules(active) {
+        let metadata = rule.metadata();
+        println!(
+            "{:name_width$}  {:8}  {:11}  {:7}  {}",
+            rule.name(),
+            if is_active(rule, active) {
+                "active"
+            } else {
+                "inactive"
+            },
+            metadata.category.to_string(),
+            if metadata.fixable { "yes" } else { "no" },
+            metadata.description
+        );
+    }
+
+    println!("\nRun 'ludtwig explain <rule>' for more details about a rule.");
+    0
+}
+
+/// Print everything known about a single rule and return the process exit code.
+pub fn explain_rule(name: &str, active: &ActiveConfig) -> i32 {
+    let Some(rule) = all_rules(active).find(|r| r.name() == name) else {
+        error!("Error: there is no rule named '{name}'");
+        if let Some(suggestion) = similar_rule_name(name) {
+            error!("Did you mean '{suggestion}'?");
+        }
+        error!("Run 'ludtwig rules' to list all rules.");
+        return 1;
+    };
+    let metadata = rule.metadata();
+
+    println!(
+        "{} ({}, {}, {})",
+        rule.name(),
+        metadata.category,
+        if metadata.fixable {
+            "fixable"
+        } else {
+            "not fixable"
+        },
+        if is_active(rule, active) {
+            "active"
+        } else {
+            "inactive"
+        }
+    );
+    println!("\n{}", metadata.description);
+    println!("\nWhy?\n{}", metadata.rationale);
+
+    if !metadata.config_keys.is_empty() {
+        println!("\nConfiguration:");
+        for key in metadata.config_keys {
+            println!(
+                "    {key} = {}",
+                config_value(&active.config, key).unwrap_or_default()
+            );
+        }
+    }
+
+    println!("\nBad:\n{}", indent(metadata.bad_example));
+    println!("\nGood:\n{}", indent(metadata.good_example));
+    0
+}
+
+/// Find the rule name which is most similar to the given (unknown) one for a "did you mean" hint.
+pub fn similar_rule_name(name: &str) -> Option<&'static str> {
+    RULE_DEFINITIONS
+        .iter()
+        .map(|r| (strsim::jaro_winkler(name, r.name()), r.name()))
+        .filter(|(confidence, _)| *confidence > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, r)| r)
+}
+
+/// The built-in rules followed by the custom rules of the config.
+fn all_rules(active: &ActiveConfig) -> impl Iterator<Item = &dyn Rule> {
+    RULE_DEFINITIONS
+        .iter()
+        .chain(
+            active
+                .rule_definitions
+                .iter()
+                .filter(|r| r.metadata().category == RuleCategory::Custom),
+        )
+        .map(AsRef::as_ref)
+}
+
+fn is_active(rule: &dyn Rule, active: &ActiveConfig) -> bool {
+    active
+        .rule_definitions
+        .iter()
+        .any(|r| r.name() == rule.name())
+}
+
+/// Get the current value of a config key (like `format.indentation-count`) for displaying it.
+pub fn config_value(config: &Config, key: &str) -> Option<String> {
+    let value = serde_json::to_value(config).ok()?;
+    key.split('.')
+        .try_fold(&value, |value, segment| value.get(segment))
+        .map(ToString::to_string)
+}
+
+/// Indent the example code and make invisible characters like `\r` visible.
+fn indent(code: &str) -> String {
+    code.trim_end_matches('\n')
+        .split('\n')
+        .map(|line| format!("    {}", line.replace('\r', "\\r").replace('\t', "\\t")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,451 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Files which changed since a git revision (`--changed-since`), including staged, unstaged and untracked files.
+/// Like in a pull request, only the changes since the merge base with the revision are included.
+#[derive(Debug, Default)]
+pub struct ChangedFiles {
+    /// The changed lines by the canonical path of each file
+    files: HashMap<PathBuf, ChangedLines>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangedLines {
+    /// The whole file is new (untracked)
+    All,
+    /// Line numbers (starting at 1) of the added or modified lines
+    Ranges(Vec<RangeInclusive<usize>>),
+}
+
+#[derive(Debug)]
+pub enum GitError {
+    Spawn(std::io::Error),
+    Failed { command: String, stderr: String },
+}
+
+impl Display for GitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GitError::Spawn(e) => write!(f, "can't run git: {e}"),
+            GitError::Failed { command, stderr } => {
+                write!(f, "'{command}' failed: {}", stderr.trim())
+            }
+        }
+    }
+}
+
+impl ChangedFiles {
+    /// Ask git (in the current directory) which files changed since the revision.
+    pub fn since(revision: &str) -> Result<Self, GitError> {
+        Self::since_in(Path::new("."), revision)
+    }
+
+    fn since_in(dir: &Path, revision: &str) -> Result<Self, GitError> {
+        let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let root = PathBuf::from(root.trim_end_matches(['\n', '\r']));
+        let root = fs::canonicalize(&root).unwrap_or(root);
+
+        // changes on the revision (like a newer main branch) since the current branch started are not ours
+        let merge_base = git(&root, &["merge-base", revision, "HEAD"])?;
+
+        // compares the merge base with the working tree, which includes staged and unstaged changes.
+        // the prefixes are explicit, because the user config may change or remove them
+        let diff = git(
+            &root,
+            &[
+                "-c",
+                "core.quotePath=false",
+                "diff",
+                "--no-color",
+                "--no-ext-diff",
+                "--no-relative",
+                "--src-prefix=a/",
+                "--dst-prefix=b/",
+                "--unified=0",
+                merge_base.trim(),
+                "--",
+            ],
+        )?;
+        let untracked = git(&root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+
+        let mut changed = Self::from_diff(&root, &diff);
+        for path in untracked.split('\0').filter(|p| !p.is_empty()) {
+            changed.files.insert(root.join(path), ChangedLines::All);
+        }
+        Ok(changed)
+    }
+
+    /// Collect the files and their added or modified lines from a unified diff (with paths relative to `root`).
+    fn from_diff(root: &Path, diff: &str) -> Self {
+        let mut files = HashMap::new();
+        let mut current: Option<&mut ChangedLines> = None;
+        for line in diff.lines() {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                // deleted files have no new side
+                current = unquote_path(path).strip_prefix("b/").map(|path| {
+                    files
+                        .entry(root.join(path))
+                        .or_insert(ChangedLines::Ranges(vec![]))
+                });
+            } else if let (Some(hunk), Some(ChangedLines::Ranges(ranges))) =
+                (line.strip_prefix("@@ "), current.as_deref_mut())
+            {
+                if let Some(range) = parse_hunk_new_lines(hunk) {
+                    ranges.push(range);
+                }
+            }
+        }
+
+        Self { files }
+    }
+
+    /// Did the file change?
+    pub fn contains(&self, path: &Path) -> bool {
+        self.lines(path).is_some()
+    }
+
+    /// The changed lines of the file, `None` if it didn't change.
+    pub fn lines(&self, path: &Path) -> Option<&ChangedLines> {
+        let path = fs::canonicalize(path).ok()?;
+        self.files.get(&path)
+    }
+}
+
+impl ChangedLines {
+    /// Was any line between `start` and `end` (inclusive) changed?
+    pub fn intersects(&self, start: usize, end: usize) -> bool {
+        match self {
+            ChangedLines::All => true,
+            ChangedLines::Ranges(ranges) => ranges
+                .iter()
+                .any(|range| *range.start() <= end && start <= *range.end()),
+        }
+    }
+
+    /// The changed lines after replacing the byte ranges of the source code (sorted and not overlapping).
+    /// The lines move with the added and removed line breaks and the lines of a replacement are changed
+    /// if it touches a changed line.
+    #[must_use]
+    pub fn after_edits(&self, source_code: &str, edits: &[(Range<usize>, &str)]) -> ChangedLines {
+        let ChangedLines::Ranges(ranges) = self else {
+            return ChangedLines::All;
+        };
+        let line_number = |offset: usize| source_code[..offset].matches('\n').count() + 1;
+        // the old lines of each edit and the difference in the number of lines it makes
+        let edits: Vec<(usize, usize, usize)> = edits
+            .iter()
+            .map(|(range, replace_with)| {
+                (
+                    line_number(range.start),
+                    line_number(range.end),
+                    replace_with.matches('\n').count(),
+                )
+            })
+            .collect();
+
+        // the new line of an old line, which is the first (or last) line of a replacement inside of it
+        let new_line = |line: usize, last: bool| {
+            let mut moved = line;
+            for &(start, end, new_breaks) in &edits {
+                if start > line {
+                    break;
+                }
+                let old_breaks = end - start;
+                if end < line {
+                    moved = moved + new_breaks - old_breaks;
+                } else if last {
+                    moved = moved + new_breaks - (line - start);
+                    // the following edits on the same line start behind this one
+                } else {
+                    return moved - (line - start);
+                }
+            }
+            moved
+        };
+
+        ChangedLines::Ranges(
+            ranges
+                .iter()
+                .map(|range| new_line(*range.start(), false)..=new_line(*range.end(), true))
+                .collect(),
+        )
+    }
+}
+
+/// Get the new lines of a hunk header like `-3,2 +3,4 @@` (without the leading `@@ `).
+/// Hunks which only delete lines have no new lines.
+fn parse_hunk_new_lines(hunk: &str) -> Option<RangeInclusive<usize>> {
+    let new = hunk.split(' ').find_map(|part| part.strip_prefix('+'))?;
+    let (start, count) = match new.split_once(',') {
+        Some((start, count)) => (start.parse().ok()?, count.parse().ok()?),
+        None => (new.parse().ok()?, 1),
+    };
+    if count == 0 {
+        return None;
+    }
+    Some(start..=start + count - 1)
+}
+
+/// Git quotes paths with special characters (like quotes, backslashes or control characters) like C strings
+/// and adds a tab behind paths with spaces.
+fn unquote_path(path: &str) -> String {
+    let Some(quoted) = path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    else {
+        return path.strip_suffix('\t').unwrap_or(path).to_string();
+    };
+
+    let mut bytes = vec![];
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        match chars.next() {
+            Some('a') => bytes.push(0x07),
+            Some('b') => bytes.push(0x08),
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('v') => bytes.push(0x0B),
+            Some('f') => bytes.push(0x0C),
+            Some('r') => bytes.push(b'\r'),
+            // an octal escaped byte, like each byte of non-ASCII characters with core.quotePath
+            Some(first @ '0'..='3') => {
+                let octal: String = std::iter::once(first)
+                    .chain(chars.by_ref().take(2))
+                    .collect();
+                bytes.push(u8::from_str_radix(&octal, 8).unwrap_or_default());
+            }
+            Some(other) => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buffer).as_bytes());
+            }
+            None => {}
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(GitError::Spawn)?;
+    if !output.status.success() {
+        return Err(GitError::Failed {
+            command: format!("git {}", args.join(" ")),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use crate::test_util::temp_dir;
+
+    use super::{git, parse_hunk_new_lines, unquote_path, ChangedFiles, ChangedLines};
+
+    /// Run git in the repository with a fixed author.
+    fn run_git(repo: &Path, args: &[&str]) {
+        let mut all_args = vec![
+            "-c",
+            "user.name=ludtwig",
+            "-c",
+            "user.email=ludtwig@example.com",
+            "-c",
+            "commit.gpgsign=false",
+        ];
+        all_args.extend_from_slice(args);
+        git(repo, &all_args).unwrap();
+    }
+
+    fn commit_all(repo: &Path, message: &str) {
+        run_git(repo, &["add", "-A"]);
+        run_git(repo, &["commit", "-q", "-m", message]);
+    }
+
+    #[test]
+    fn hunk_new_lines() {
+        assert_eq!(parse_hunk_new_lines("-3,2 +3,4 @@"), Some(3..=6));
+        assert_eq!(parse_hunk_new_lines("-3 +5 @@ {% block a %}"), Some(5..=5));
+        assert_eq!(parse_hunk_new_lines("-3,2 +2,0 @@"), None);
+    }
+
+    #[test]
+    fn changed_lines_after_edits() {
+        let source_code = "a\nb\nc\nd\ne\n";
+        let lines = ChangedLines::Ranges(vec![2..=2, 4..=5]);
+
+        // line 1 becomes three lines and "c\nd" on line 3 and 4 becomes one line
+        let edits = [(0..1, "a\na\na"), (4..7, "cd")];
+        assert_eq!(
+            lines.after_edits(source_code, &edits),
+            ChangedLines::Ranges(vec![4..=4, 5..=6])
+        );
+        assert_eq!(
+            ChangedLines::All.after_edits(source_code, &edits),
+            ChangedLines::All
+        );
+    }
+
+    #[test]
+    fn changed_files_from_diff() {
+        let diff = r#"diff --git a/a.html.twig b/a.html.twig
+index 1111111..2222222 100644
+--- a/a.html.twig
++++ b/a.html.twig
+@@ -2 +2 @@ <div>
+-    <span>
++    <span class="a">
+@@ -10,0 +11,3 @@
++<p>
++</p>
++
+diff --git a/deleted.html.twig b/deleted.html.twig
+deleted file mode 100644
+--- a/deleted.html.twig
++++ /dev/null
+@@ -1 +0,0 @@
+-<div></div>
+diff --git a/sub/b.html.twig b/sub/b.html.twig
+--- a/sub/b.html.twig
++++ b/sub/b.html.twig
+@@ -4,2 +3,0 @@
+-<p>
+-</p>
+"#;
+        let root = Path::new("/repo");
+        let changed = ChangedFiles::from_diff(root, diff);
+
+        assert_eq!(
+            changed.files.get(&root.join("a.html.twig")),
+            Some(&ChangedLines::Ranges(vec![2..=2, 11..=13]))
+        );
+        assert_eq!(
+            changed.files.get(&root.join("sub/b.html.twig")),
+            Some(&ChangedLines::Ranges(vec![]))
+        );
+        assert!(!changed.files.contains_key(&root.join("deleted.html.twig")));
+    }
+
+    #[test]
+    fn changed_files_from_diff_with_quoted_paths() {
+        let diff = "+++ \"b/quote\\\"d.html.twig\"
+@@ -1 +1 @@
++++ b/with space.html.twig\t
+@@ -2 +2,2 @@
++++ \"b/tab\\there/\\303\\274ber.html.twig\"
+@@ -0,0 +1 @@
+";
+        let root = Path::new("/repo");
+        let changed = ChangedFiles::from_diff(root, diff);
+
+        assert_eq!(
+            changed.files.get(&root.join("quote\"d.html.twig")),
+            Some(&ChangedLines::Ranges(vec![1..=1]))
+        );
+        assert_eq!(
+            changed.files.get(&root.join("with space.html.twig")),
+            Some(&ChangedLines::Ranges(vec![2..=3]))
+        );
+        assert_eq!(
+            changed.files.get(&root.join("tab\there/über.html.twig")),
+            Some(&ChangedLines::Ranges(vec![1..=1]))
+        );
+    }
+
+    #[test]
+    fn unquote_git_paths() {
+        assert_eq!(unquote_path("b/plain.twig"), "b/plain.twig");
+        assert_eq!(unquote_path("b/a b.twig\t"), "b/a b.twig");
+        assert_eq!(
+            unquote_path(r#""b/back\\slash\n\"quote\".twig""#),
+            "b/back\\slash\n\"quote\".twig"
+        );
+        assert_eq!(unquote_path(r#""b/\342\202\254.twig""#), "b/€.twig");
+    }
+
+    #[test]
+    fn changed_since_ignores_the_diff_prefix_config() {
+        let temp = temp_dir();
+        let repo = temp.path();
+        run_git(repo, &["init", "-q"]);
+        run_git(repo, &["config", "diff.noprefix", "true"]);
+        run_git(repo, &["config", "diff.mnemonicPrefix", "true"]);
+        fs::write(repo.join("a.html.twig"), "<div>\n</div>\n").unwrap();
+        fs::write(repo.join("quote\"d.html.twig"), "<p>\n</p>\n").unwrap();
+        commit_all(repo, "initial");
+
+        fs::write(repo.join("a.html.twig"), "<div>\n<span></span>\n</div>\n").unwrap();
+        fs::write(repo.join("quote\"d.html.twig"), "<p class=\"a\">\n</p>\n").unwrap();
+        fs::write(repo.join("new.html.twig"), "").unwrap();
+        let changed = ChangedFiles::since_in(repo, "HEAD").unwrap();
+
+        assert_eq!(
+            changed.lines(&repo.join("a.html.twig")),
+            Some(&ChangedLines::Ranges(vec![2..=2]))
+        );
+        assert_eq!(
+            changed.lines(&repo.join("quote\"d.html.twig")),
+            Some(&ChangedLines::Ranges(vec![1..=1]))
+        );
+        assert_eq!(
+            changed.lines(&repo.join("new.html.twig")),
+            Some(&ChangedLines::All)
+        );
+    }
+
+    #[test]
+    fn changed_since_compares_with_the_merge_base() {
+        let temp = temp_dir();
+        let repo = temp.path();
+        run_git(repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("main.html.twig"), "<div></div>\n").unwrap();
+        fs::write(repo.join("branch.html.twig"), "<div></div>\n").unwrap();
+        commit_all(repo, "initial");
+
+        run_git(repo, &["checkout", "-q", "-b", "feature"]);
+        fs::write(repo.join("branch.html.twig"), "<div></div>\n<p></p>\n").unwrap();
+        commit_all(repo, "feature");
+
+        // a newer main branch changed a file which the feature branch doesn't touch
+        run_git(repo, &["checkout", "-q", "main"]);
+        fs::write(repo.join("main.html.twig"), "<span></span>\n").unwrap();
+        commit_all(repo, "main");
+        run_git(repo, &["checkout", "-q", "feature"]);
+
+        let changed = ChangedFiles::since_in(repo, "main").unwrap();
+        assert_eq!(
+            changed.lines(&repo.join("branch.html.twig")),
+            Some(&ChangedLines::Ranges(vec![2..=2]))
+        );
+        assert!(!changed.contains(&repo.join("main.html.twig")));
+    }
+
+    #[test]
+    fn changed_lines_intersect() {
+        let lines = ChangedLines::Ranges(vec![2..=2, 11..=13]);
+        assert!(lines.intersects(2, 2));
+        assert!(lines.intersects(1, 3));
+        assert!(lines.intersects(13, 20));
+        assert!(!lines.intersects(3, 10));
+        assert!(!lines.intersects(14, 14));
+        assert!(ChangedLines::All.intersects(1, 1));
+    }
+}
@@ -0,0 +1,659 @@
+#![warn(clippy::pedantic)]
+#![allow(clippy::module_name_repetitions)]
+//! # Ludtwig
+//! Linter / Formatter for Twig template files which respects HTML and your time.
+//!
+//! Besides the command line interface, the [`Linter`] can check and fix templates from other Rust code
+//! (like a build time template validator) without printing anything:
+//! ```
+//! use std::path::Path;
+//! use ludtwig::{Config, Linter, Severity};
+//!
+//! // the default config (with overrides from `ludtwig-config.toml` in the current directory, if it exists)
+//! let config = Config::new("ludtwig-config.toml").unwrap();
+//! let linter = Linter::new(config).unwrap();
+//!
+//! let diagnostics = linter.check_source(Path::new("a.html.twig"), "<div>\n<span></span>\n</div>\n");
+//! assert_eq!(diagnostics[0].code, "indentation");
+//! assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 1));
+//!
+//! let fixed = linter.fix_source(Path::new("a.html.twig"), "<div>\n<span></span>\n</div>\n").unwrap();
+//! assert_eq!(fixed.source_code, "<div>\n    <span></span>\n</div>\n");
+//! assert!(fixed.diagnostics.iter().all(|d| d.severity != Severity::Error));
+//! ```
+
+use crate::cache::ResultCache;
+use crate::config::ConfigResolver;
+use crate::git::ChangedFiles;
+use crate::output::{
+    error, exit_code, info, ColorMode, FailOn, GroupBy, OutputOptions, ProcessingEvent,
+};
+use crate::process::interactive::InteractiveSession;
+use crate::timings::Timings;
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand};
+use ignore::types::TypesBuilder;
+use ignore::{WalkBuilder, WalkState};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+mod cache;
+mod check;
+mod config;
+mod error;
+mod explain;
+mod git;
+mod linter;
+mod lsp;
+mod output;
+mod process;
+mod project;
+mod ssr;
+#[cfg(test)]
+mod test_util;
+mod timings;
+mod watch;
+
+pub use check::rule::{
+    Applicability, CheckNote, CheckResult, CheckSuggestion, Rule, RuleCategory, RuleExt,
+    RuleMetadata, RuleRunContext, Severity, TextEdit, TreeTraversalContext,
+};
+pub use check::rules::RULE_DEFINITIONS;
+pub use config::{
+    Config, CustomRule, CustomRuleSeverity, Format, General, IndentationMode, LineEnding,
+    Quotation, Templates,
+};
+pub use error::{AppliedFix, ConfigurationError, ErrorKind, FileProcessingError};
+pub use linter::{Diagnostic, DiagnosticLabel, FixResult, Linter};
+pub use project::TemplateReferenceKind;
+
+// uses author, version and description from Cargo.toml
+#[derive(Parser, Debug, Clone)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true
+)]
+pub(crate) struct Opts {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Checking files is the default command
+    #[command(flatten)]
+    check: CheckOpts,
+
+    /// Specify where the ludtwig configuration file is. By default ludtwig uses the nearest 'ludtwig-config.toml'
+    /// in the directory of each file or its parents and falls back to the current directory.
+    #[arg(short = 'c', long, global = true)]
+    config_path: Option<PathBuf>,
+
+    /// Verbose output, for example the exact configuration values used are displayed
+    #[arg(short = 'v', long, global = true)]
+    verbose: bool,
+
+    /// When to use colors. 'auto' uses them only for terminals and respects the `NO_COLOR` environment variable.
+    #[arg(long, value_name = "WHEN", default_value = "auto", global = true)]
+    color: ColorMode,
+}
+
+#[derive(Args, Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+#[command(group(
+    ArgGroup::new("fix_mode")
+        .args(["fix", "check", "diff", "fix_interactive"])
+        .multiple(true)
+))]
+pub(crate) struct CheckOpts {
+    /// Files or directories to scan. Paths named like a subcommand (for example a 'rules' directory) are
+    /// the subcommand, so write them as './rules' or after '--' (like 'ludtwig -- rules').
+    #[arg(
+        value_name = "FILE",
+        num_args = 1..,
+        required_unless_present = "stdin",
+        conflicts_with = "create_config",
+        name = "files"
+    )]
+    files: Vec<PathBuf>,
+
+    /// Read a single template from stdin instead of files. Diagnostics and messages are written to stderr
+    /// and with '--fix' the fixed template is written to stdout (the file is never changed). If the fixing fails,
+    /// the unchanged template is written.
+    #[arg(long, conflicts_with_all = ["files", "create_config"])]
+    stdin: bool,
+
+    /// Path of the template read from stdin. It is used to find the config and in diagnostics, but doesn't need to exist.
+    #[arg(long, value_name = "PATH", requires = "stdin")]
+    stdin_filename: Option<PathBuf>,
+
+    /// Apply all code suggestions automatically. This changes the original files!
+    #[arg(short = 'f', long)]
+    fix: bool,
+
+    /// Exit with a non-zero code if applying the code suggestions would change any file, without changing them
+    #[arg(long, conflicts_with = "fix")]
+    check: bool,
+
+    /// Print a unified diff of the changes applying the code suggestions would make, without changing any file
+    #[arg(long, conflicts_with = "fix")]
+    diff: bool,
+
+    /// Review each code suggestion and decide whether to apply it. This changes the original files!
+    #[arg(long, conflicts_with_all = ["fix", "check", "diff", "stdin"])]
+    fix_interactive: bool,
+
+    /// Only apply the code suggestions of these rules (comma separated), the others are only reported.
+    /// Works together with '--fix', '--fix-interactive', '--check' and '--diff'.
+    #[arg(
+        long,
+        value_name = "RULES",
+        value_delimiter = ',',
+        requires = "fix_mode"
+    )]
+    fix_only: Vec<String>,
+
+    /// Also apply unsafe code suggestions, which may change the rendered output of the template.
+    /// Works together with '--fix', '--fix-interactive', '--check' and '--diff'.
+    #[arg(long, requires = "fix_mode")]
+    fix_unsafe: bool,
+
+    /// Don't change a file if a safe code suggestion would change the structure of the template
+    /// (ignoring formatting like whitespace and quotes). Works together with '--fix', '--fix-interactive',
+    /// '--check' and '--diff'.
+    #[arg(long, requires = "fix_mode")]
+    verify_fixes: bool,
+
+    /// Create the default configuration file in the config path. Defaults to the current directory.
+    #[arg(short = 'C', long, name = "create_config")]
+    create_config: bool,
+
+    /// Print out the parsed syntax tree for each file
+    #[arg(short = 'i', long)]
+    inspect: bool,
+
+    /// Store the results of each file and report unchanged files from this cache in the next runs
+    #[arg(long)]
+    cache: bool,
+
+    /// Directory of the cache. Defaults to '.ludtwig-cache' in the current directory.
+    #[arg(long, value_name = "DIR", requires = "cache")]
+    cache_dir: Option<PathBuf>,
+
+    /// Group the diagnostics by their rule or severity. By default they are sorted by file and location.
+    #[arg(long, value_name = "GROUP")]
+    group_by: Option<GroupBy>,
+
+    /// Keep running and check the files again whenever they change
+    #[arg(short = 'w', long, conflicts_with_all = ["stdin", "fix_interactive"])]
+    watch: bool,
+
+    /// Only check files which changed since this git revision (like 'origin/main'),
+    /// including staged, unstaged and untracked changes. Like in a pull request, the files are compared
+    /// with the merge base of the revision and HEAD
+    #[arg(long, value_name = "REV", conflicts_with_all = ["stdin", "watch"])]
+    changed_since: Option<String>,
+
+    /// Only report results on lines which changed since the revision of '--changed-since'
+    #[arg(long, requires = "changed_since")]
+    changed_lines_only: bool,
+
+    /// Only print the diagnostics (and errors), without informational messages and the summary
+    #[arg(short = 'q', long)]
+    quiet: bool,
+
+    /// Only print the summary, without the diagnostics
+    #[arg(long, conflicts_with_all = ["quiet", "max_diagnostics", "short"])]
+    summary_only: bool,
+
+    /// Print at most this many diagnostics. The summary still counts all of them.
+    #[arg(long, value_name = "N")]
+    max_diagnostics: Option<usize>,
+
+    /// Print each diagnostic as a single line like 'path:line:col: severity[rule] message'
+    #[arg(long)]
+    short: bool,
+
+    /// The lowest severity of diagnostics which fails the run (exit code 1).
+    /// Syntax errors (exit code 3), processing errors (4) and configuration errors (5) always fail it.
+    #[arg(long, value_name = "SEVERITY", default_value = "help")]
+    fail_on: FailOn,
+
+    /// Fail the run if there are more than this many warnings, regardless of '--fail-on'
+    #[arg(long, value_name = "N")]
+    max_warnings: Option<usize>,
+
+    /// Measure where the time is spent (reading, lexing, parsing, each rule, fixing and writing)
+    /// and print the slowest rules and files
+    #[arg(long, conflicts_with = "watch")]
+    timings: bool,
+
+    /// Only print the diffs and errors, without the other diagnostics and the summary (`ssr --dry-run`)
+    #[arg(skip)]
+    diff_only: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum Command {
+    /// Check files or directories (the default command, same as 'ludtwig <FILE>...')
+    Check(CheckOpts),
+    /// List all rules and whether they are active in the configuration
+    Rules,
+    /// Explain what a rule checks and why
+    Explain {
+        /// Name of the rule, for example 'twig-block-name-snake-case'
+        rule: String,
+    },
+    /// Work with the ludtwig configuration file
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Start a language server (LSP) over stdio for editors, which reports diagnostics while typing,
+    /// offers the code suggestions as quick fixes and formats documents
+    Lsp,
+    /// Structural search and replace, like `ludtwig ssr "include $tpl with $args" "=>" "sw_include $tpl with $args" <FILE>...`
+    Ssr(SsrOpts),
+}
+
+#[derive(Args, Debug, Clone)]
+pub(crate) struct SsrOpts {
+    /// Twig or html code to search for, where placeholders like '$tpl' match any expression or node and
+    /// whitespace is ignored. Twig tags can be written without '{% %}' and expressions without '{{ }}'.
+    pattern: String,
+
+    /// Separates the pattern from the replacement, must be '=>'
+    arrow: String,
+
+    /// Code which replaces each match, where the placeholders are replaced by the code they matched
+    replacement: String,
+
+    /// Files or directories to search
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<PathBuf>,
+
+    /// Print a unified diff of the replacements without changing any file
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub(crate) enum ConfigCommand {
+    /// Print the JSON Schema of the configuration file (for validation and autocompletion in editors)
+    Schema,
+    /// Update the configuration file to the current ludtwig version while keeping your changes and comments
+    Migrate,
+}
+
+/// Context to pass to every processing thead (can be cloned)
+#[derive(Debug)]
+pub(crate) struct CliContext {
+    /// Channel sender for transmitting messages back to the CLI.
+    pub output_tx: Sender<ProcessingEvent>,
+    /// Shared Data
+    pub data: Arc<CliSharedData>,
+}
+
+#[derive(Debug)]
+#[allow(clippy::struct_excessive_bools)]
+pub(crate) struct CliSharedData {
+    /// Apply all code suggestions automatically. This changes the original files!
+    pub fix: bool,
+    /// Report files which would change by applying all code suggestions (without changing them)
+    pub check: bool,
+    /// Print the changes applying all code suggestions would make as a diff (without changing the files)
+    pub diff: bool,
+    /// Only apply the code suggestions of these rules (all rules if empty)
+    pub fix_only: Vec<String>,
+    /// Also apply code suggestions which may change the rendered output
+    pub fix_unsafe: bool,
+    /// Fail the file if a safe code suggestion changes the structure of the template
+    pub verify_fixes: bool,
+    /// Ask the user for each code suggestion whether to apply it (`--fix-interactive`)
+    pub interactive: Option<Mutex<InteractiveSession>>,
+    /// Print out the parsed syntax tree for each file
+    pub inspect: bool,
+    /// Results of unchanged files from previous runs (`--cache`)
+    pub cache: Option<ResultCache>,
+    /// Only check these files (`--changed-since`)
+    pub changed_files: Option<ChangedFiles>,
+    /// Only report results on changed lines of the `changed_files`
+    pub changed_lines_only: bool,
+    /// Where the time is spent (`--timings`)
+    pub timings: Option<Timings>,
+    /// Finds the config values and active rules to use for each file.
+    pub config_resolver: ConfigResolver,
+}
+
+impl Clone for CliContext {
+    fn clone(&self) -> Self {
+        Self {
+            output_tx: self.output_tx.clone(),
+            data: Arc::clone(&self.data),
+        }
+    }
+}
+
+impl CliContext {
+    /// # Panics
+    /// if the output channel was already closed on the other side.
+    pub fn send_processing_output(&self, event: ProcessingEvent) {
+        self.output_tx
+            .send(event)
+            .expect("output should still receive ProcessingEvents");
+    }
+}
+
+/// Parse the CLI arguments and run the command line interface. Returns the exit code of the process.
+#[must_use]
+pub fn run_cli() -> i32 {
+    let opts: Opts = Opts::parse();
+    output::set_color_mode(opts.color);
+    let command = opts
+        .command
+        .clone()
+        .unwrap_or_else(|| Command::Check(opts.check.clone()));
+
+    match command {
+        Command::Check(check_opts) => {
+            if check_opts.stdin || check_opts.diff {
+                output::reserve_stdout();
+            }
+            if check_opts.quiet {
+                output::set_quiet();
+            }
+            info!(concat!("Ludtwig ", env!("CARGO_PKG_VERSION")));
+            if check_opts.create_config {
+                std::process::exit(config::create_default_config(&opts));
+            }
+
+            let config_resolver = config::handle_config_or_exit(&opts);
+            app(check_opts, config_resolver)
+        }
+        Command::Rules => {
+            let config_resolver = config::handle_config_or_exit(&opts);
+            explain::print_rules(&config_resolver.fallback())
+        }
+        Command::Explain { rule } => {
+            let config_resolver = config::handle_config_or_exit(&opts);
+            explain::explain_rule(&rule, &config_resolver.fallback())
+        }
+        Command::Config(command) => config::handle_config_command(&command, &opts),
+        Command::Lsp => {
+            // stdout is used for the messages of the protocol
+            output::reserve_stdout();
+            let config_resolver = config::handle_config_or_exit(&opts);
+            lsp::run(config_resolver)
+        }
+        Command::Ssr(ssr_opts) => {
+            if ssr_opts.dry_run {
+                output::reserve_stdout();
+                output::set_quiet();
+            }
+            let rule = if ssr_opts.arrow == ssr::ARROW {
+                ssr::SsrRule::new(&ssr_opts.pattern, &ssr_opts.replacement)
+            } else {
+                Err(format!(
+                    "expected '{}' between the pattern and the replacement but found '{}'",
+                    ssr::ARROW,
+                    ssr_opts.arrow
+                ))
+            };
+            let rule = rule.unwrap_or_else(|message| {
+                let kind = clap::error::ErrorKind::InvalidValue;
+                let mut command = Opts::command();
+                match command.find_subcommand_mut("ssr") {
+                    Some(ssr_command) => ssr_command.error(kind, message).exit(),
+                    None => command.error(kind, message).exit(),
+                }
+            });
+            info!(concat!("Ludtwig ", env!("CARGO_PKG_VERSION")));
+
+            // the config values are used for the files, but only the replacements are applied
+            let config_resolver =
+                config::handle_config_or_exit(&opts).with_rules(vec![Arc::new(rule)]);
+            app(
+                CheckOpts {
+                    files: ssr_opts.files,
+                    fix: !ssr_opts.dry_run,
+                    diff: ssr_opts.dry_run,
+                    diff_only: ssr_opts.dry_run,
+                    fail_on: FailOn::Never,
+                    ..CheckOpts::default()
+                },
+                config_resolver,
+            )
+        }
+    }
+}
+
+/// The entry point of the async application.
+fn app(opts: CheckOpts, config_resolver: ConfigResolver) -> i32 {
+    let start = Instant::now();
+    let fallback_config = config_resolver.fallback();
+    for rule in &opts.fix_only {
+        if !RULE_DEFINITIONS
+            .iter()
+            .chain(&fallback_config.rule_definitions)
+            .any(|r| r.name() == rule)
+        {
+            error!("Error: there is no rule named '{rule}' (used in --fix-only)");
+            if let Some(suggestion) = explain::similar_rule_name(rule) {
+                error!("Did you mean '{suggestion}'?");
+            }
+            return exit_code::CONFIG_ERROR;
+        }
+    }
+
+    let cache = if opts.cache {
+        let dir = opts
+            .cache_dir
+            .unwrap_or_else(|| PathBuf::from(cache::DEFAULT_CACHE_DIR));
+        match ResultCache::open(dir.clone()) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                error!(
+                    "Error: can't use the cache directory {}: {e}",
+                    dir.display()
+                );
+                return exit_code::PROCESSING_ERROR;
+            }
+        }
+    } else {
+        None
+    };
+
+    let changed_files = match opts.changed_since.as_deref().map(ChangedFiles::since) {
+        Some(Ok(changed_files)) => Some(changed_files),
+        Some(Err(e)) => {
+            error!("Error: can't find the changed files (used in --changed-since): {e}");
+            return exit_code::PROCESSING_ERROR;
+        }
+        None => None,
+    };
+
+    info!("Scanning files...");
+
+    let data = Arc::new(CliSharedData {
+        fix: opts.fix,
+        check: opts.check,
+        diff: opts.diff,
+        fix_only: opts.fix_only,
+        fix_unsafe: opts.fix_unsafe,
+        verify_fixes: opts.verify_fixes,
+        interactive: opts
+            .fix_interactive
+            .then(|| Mutex::new(InteractiveSession::default())),
+        inspect: opts.inspect,
+        cache,
+        changed_files,
+        changed_lines_only: opts.changed_lines_only,
+        timings: opts.timings.then(Timings::default),
+        config_resolver,
+    });
+
+    let output_options = OutputOptions {
+        group_by: opts.group_by,
+        quiet: opts.quiet,
+        summary_only: opts.summary_only,
+        diff_only: opts.diff_only,
+        max_diagnostics: opts.max_diagnostics,
+        short: opts.short,
+        fail_on: opts.fail_on,
+        max_warnings: opts.max_warnings,
+    };
+
+    if opts.watch {
+        return watch::watch(&opts.files, &data, &output_options);
+    }
+
+    // sender and receiver channels for the communication between tasks and the user.
+    let (tx, rx) = mpsc::channel();
+
+    let cli_context = CliContext {
+        output_tx: tx,
+        data: Arc::clone(&data),
+    };
+
+    let output_handler =
+        thread::spawn(move || output::handle_processing_output(&rx, &output_options));
+
+    if opts.stdin {
+        let path = opts
+            .stdin_filename
+            .unwrap_or_else(|| PathBuf::from(process::STDIN_FILENAME));
+        if let Err(e) = process::process_stdin(path, cli_context.clone()) {
+            cli_context.send_processing_output(ProcessingEvent::Error(e.kind()));
+            error!("Error: {e}");
+        }
+    } else {
+        // work on each user specified file / directory path concurrently
+        handle_input_paths(&opts.files, cli_context.clone());
+    }
+
+    drop(cli_context); // drop this tx channel
+
+    // the output_handler will finish execution if all the tx (sending channel) ends are closed.
+    let exit_code = output_handler
+        .join()
+        .expect("Error: can't join output_handler thread");
+
+    if let Some(timings) = &data.timings {
+        eprint!("{}", timings.report(start.elapsed()));
+    }
+
+    exit_code
+}
+
+/// Walker over all the template files in the user specified paths, which respects the ignore files.
+fn input_walker(paths: &[PathBuf]) -> WalkBuilder {
+    let types = TypesBuilder::new()
+        .add_defaults()
+        .select("twig")
+        .select("html")
+        .build()
+        .unwrap();
+
+    // create walker over all the user specified paths
+    let mut walker = WalkBuilder::new(&paths[0]);
+    for path in paths.iter().skip(1) {
+        walker.add(path);
+    }
+
+    walker
+        .add_custom_ignore_filename(".ludtwig-ignore")
+        .types(types);
+
+    // maybe consider .ludtwig-ignore in cwd (current working directory) just like the ludtwig-config.toml
+    let cwd_ignore_path = Path::new("./.ludtwig-ignore");
+    if cwd_ignore_path.exists() {
+        if let Some(e) = walker.add_ignore(cwd_ignore_path) {
+            panic!("Error: can't use ./.ludtwig-ignore: {e}");
+        }
+    }
+
+    walker
+}
+
+/// Process a directory path.
+fn handle_input_paths(paths: &[PathBuf], cli_context: CliContext) {
+    let walker = input_walker(paths).build_parallel();
+
+    // parallel directory traversal but move the work for each file to a different thread in the thread pool.
+    rayon::scope(move |s| {
+        walker.run(|| {
+            let cli_context = cli_context.clone();
+
+            Box::new(move |entry| {
+                let entry = match entry {
+                    Ok(e) => e,
+                    Err(e) => {
+                        error!("Error: walking over the file path: {e}");
+                        cli_context
+                            .send_processing_output(ProcessingEvent::Error(ErrorKind::Processing));
+                        return WalkState::Continue;
+                    }
+                };
+
+                // filter out directories
+                if entry.file_type().map_or(true, |t| t.is_dir()) {
+                    return WalkState::Continue;
+                }
+
+                // filter out unchanged files (--changed-since)
+                if let Some(changed_files) = &cli_context.data.changed_files {
+                    if !changed_files.contains(entry.path()) {
+                        return WalkState::Continue;
+                    }
+                }
+
+                let clone = cli_context.clone();
+                let tx_clone = cli_context.output_tx.clone();
+                s.spawn(
+                    move |_s1| match process::process_file(entry.path().into(), clone) {
+                        Ok(()) => {}
+                        Err(e) => {
+                            tx_clone
+                                .send(ProcessingEvent::Error(e.kind()))
+                                .expect("output should still receive ProcessingEvents");
+                            error!("Error: {e}");
+                        }
+                    },
+                );
+
+                WalkState::Continue
+            })
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use clap::Parser;
+
+    use super::{Command, Opts};
+
+    #[test]
+    fn paths_named_like_subcommands() {
+        let files = |args: &[&str]| {
+            let opts = Opts::try_parse_from(args).unwrap();
+            assert!(opts.command.is_none(), "{args:?} should check files");
+            opts.check.files
+        };
+
+        assert!(matches!(
+            Opts::try_parse_from(["ludtwig", "rules"]).unwrap().command,
+            Some(Command::Rules)
+        ));
+        assert_eq!(
+            files(&["ludtwig", "--", "rules"]),
+            vec![PathBuf::from("rules")]
+        );
+        assert_eq!(
+            files(&["ludtwig", "./rules", "config"]),
+            vec![PathBuf::from("./rules"), PathBuf::from("config")]
+        );
+    }
+}
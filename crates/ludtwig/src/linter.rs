@@ -0,0 +1,274 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{mpsc, Arc};
+
+use codespan_reporting::diagnostic::LabelStyle;
+use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+use crate::check::rule::Severity;
+use crate::check::{produce_diagnostics, run_rules};
+use crate::config::{ActiveConfig, Config, ConfigResolver};
+use crate::error::{ConfigurationError, FileProcessingError};
+use crate::output::{FileDiagnostic, ProcessingEvent};
+use crate::process::{iteratively_apply_suggestions, FileContext};
+use crate::{CliContext, CliSharedData};
+
+/// Checks and fixes templates with a config, without printing anything (for using ludtwig as a library).
+#[derive(Debug)]
+pub struct Linter {
+    data: Arc<CliSharedData>,
+}
+
+/// A problem the parser or a rule found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    /// The rule name (or `SyntaxError` for parser errors)
+    pub code: String,
+    pub severity: Severity,
+    pub message: String,
+    pub range: TextRange,
+    /// Line number (starting at 1) of the start of the range
+    pub line: usize,
+    /// Column number (starting at 1) of the start of the range
+    pub column: usize,
+    /// Messages for locations in the template, like the primary location and suggested changes
+    pub labels: Vec<DiagnosticLabel>,
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiagnosticLabel {
+    pub range: TextRange,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// The result of applying the (safe) suggestions of all rules to a template.
+#[derive(Debug, Clone)]
+pub struct FixResult {
+    /// The fixed source code (unchanged if there was nothing to fix)
+    pub source_code: String,
+    /// How many times suggestions were applied before the template was checked without new suggestions
+    pub iterations: usize,
+    /// The remaining diagnostics of the fixed source code and fixes which were rejected
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    /// Use the config values (for example from [`Config::new`]) and the rules they activate.
+    ///
+    /// # Errors
+    /// if the config activates a rule which doesn't exist.
+    pub fn new(config: Config) -> Result<Self, ConfigurationError> {
+        let active = ActiveConfig::new(config, None)?;
+
+        Ok(Self::with_resolver(ConfigResolver::new(
+            active, false, false,
+        )))
+    }
+
+    /// Use the configs found by the resolver, for example the nearest config of each file (`ludtwig lsp`).
+    pub(crate) fn with_resolver(config_resolver: ConfigResolver) -> Self {
+        Self {
+            data: Arc::new(CliSharedData {
+                fix: false,
+                check: false,
+                diff: false,
+                fix_only: vec![],
+                fix_unsafe: false,
+                verify_fixes: false,
+                interactive: None,
+                inspect: false,
+                cache: None,
+                changed_files: None,
+                changed_lines_only: false,
+                timings: None,
+                config_resolver,
+            }),
+        }
+    }
+
+    pub(crate) fn config_resolver(&self) -> &ConfigResolver {
+        &self.data.config_resolver
+    }
+
+    /// Check the source code of a template (the path is only used in the diagnostics and for `ludtwig-ignore-file`).
+    /// The diagnostics are sorted by their location.
+    #[must_use]
+    pub fn check_source(&self, path: &Path, source_code: &str) -> Vec<Diagnostic> {
+        let (file_context, rx) =
+            self.file_context(path, source_code, self.data.config_resolver.fallback());
+        let results = run_rules(&file_context);
+        produce_diagnostics(&file_context, results);
+        drop(file_context);
+
+        collect_diagnostics(&rx)
+    }
+
+    /// Apply the safe suggestions of all rules to the source code of a template until nothing changes anymore.
+    ///
+    /// # Errors
+    /// if the suggestions don't converge (like rules which keep changing the same location)
+    /// or a single rule suggests overlapping changes.
+    pub fn fix_source(
+        &self,
+        path: &Path,
+        source_code: &str,
+    ) -> Result<FixResult, FileProcessingError> {
+        let (file_context, rx) =
+            self.file_context(path, source_code, self.data.config_resolver.fallback());
+        let results = run_rules(&file_context);
+        let (file_context, results, _, iterations) =
+            iteratively_apply_suggestions(file_context, results)?;
+        let source_code = file_context.source_code.clone();
+        produce_diagnostics(&file_context, results);
+        drop(file_context);
+
+        Ok(FixResult {
+            source_code,
+            iterations,
+            diagnostics: collect_diagnostics(&rx),
+        })
+    }
+
+    /// Parse the source code with the config. The receiver gets the events of the checks and fixes
+    /// and must be kept until they are done.
+    pub(crate) fn file_context(
+        &self,
+        path: &Path,
+        source_code: &str,
+        config: Arc<ActiveConfig>,
+    ) -> (FileContext, Receiver<ProcessingEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let cli_context = CliContext {
+            output_tx: tx,
+            data: Arc::clone(&self.data),
+        };
+        let file_context =
+            FileContext::parse(cli_context, path.to_owned(), source_code.to_owned(), config);
+
+        (file_context, rx)
+    }
+}
+
+/// Receive the diagnostics until all senders are dropped and sort them by their location.
+fn collect_diagnostics(rx: &Receiver<ProcessingEvent>) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = rx
+        .iter()
+        .filter_map(|event| match event {
+            ProcessingEvent::Diagnostic(diagnostic) => Some(Diagnostic::from(&diagnostic)),
+            _ => None,
+        })
+        .collect();
+    diagnostics.sort_by(|a, b| {
+        (&a.path, a.range.start(), a.range.end(), &a.code).cmp(&(
+            &b.path,
+            b.range.start(),
+            b.range.end(),
+            &b.code,
+        ))
+    });
+
+    diagnostics
+}
+
+impl From<&FileDiagnostic> for Diagnostic {
+    fn from(file_diagnostic: &FileDiagnostic) -> Self {
+        let (line, column) = file_diagnostic.location();
+        let diagnostic = file_diagnostic.diagnostic();
+
+        Self {
+            path: file_diagnostic.path.clone(),
+            code: file_diagnostic.code.clone(),
+            severity: file_diagnostic.severity,
+            message: diagnostic.message.clone(),
+            range: file_diagnostic.range,
+            line,
+            column,
+            labels: diagnostic
+                .labels
+                .iter()
+                .map(|label| DiagnosticLabel {
+                    range: TextRange::new(
+                        TextSize::try_from(label.range.start).unwrap_or_default(),
+                        TextSize::try_from(label.range.end).unwrap_or_default(),
+                    ),
+                    message: label.message.clone(),
+                    primary: label.style == LabelStyle::Primary,
+                })
+                .collect(),
+            notes: diagnostic.notes.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::check::rule::Severity;
+    use crate::config::{Config, DEFAULT_CONFIG_PATH};
+
+    use super::Linter;
+
+    fn linter() -> Linter {
+        Linter::new(Config::new(DEFAULT_CONFIG_PATH).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn linter_checks_source() {
+        let diagnostics = linter().check_source(
+            Path::new("a.html.twig"),
+            "<div>\n<span></span>\n</div>\n{% if a === 5 %}{% endif %}\n<p>\n",
+        );
+
+        let found: Vec<(&str, usize, usize)> = diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.line, d.column))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("indentation", 2, 1),
+                ("twig-use-is-same-as", 4, 9),
+                ("SyntaxError", 5, 4),
+            ]
+        );
+        let primary = diagnostics[1]
+            .labels
+            .iter()
+            .find(|label| label.primary)
+            .unwrap();
+        assert_eq!(
+            &"<div>\n<span></span>\n</div>\n{% if a === 5 %}{% endif %}\n<p>\n"
+                [primary.range.start().into()..primary.range.end().into()],
+            "==="
+        );
+        assert_eq!(diagnostics[2].severity, Severity::Error);
+    }
+
+    #[test]
+    fn linter_fixes_source() {
+        let linter = linter();
+        let fixed = linter
+            .fix_source(Path::new("a.html.twig"), "<div>\n<span></span>\n</div>\n")
+            .unwrap();
+        assert_eq!(fixed.source_code, "<div>\n    <span></span>\n</div>\n");
+        assert_eq!(fixed.iterations, 1);
+        assert!(fixed.diagnostics.is_empty());
+
+        let unchanged = linter
+            .fix_source(Path::new("a.html.twig"), &fixed.source_code)
+            .unwrap();
+        assert_eq!(unchanged.source_code, fixed.source_code);
+        assert_eq!(unchanged.iterations, 0);
+    }
+
+    #[test]
+    fn linter_rejects_unknown_rules() {
+        let mut config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        config.general.active_rules.push("no-such-rule".to_string());
+        assert!(Linter::new(config).is_err());
+    }
+}
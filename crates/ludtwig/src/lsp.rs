@@ -0,0 +1,870 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::mpsc::Receiver;
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics, ShowMessage,
+};
+use lsp_types::request::{
+    CodeActionRequest, DocumentSymbolRequest, FoldingRangeRequest, Formatting, GotoDefinition,
+    Request as LspRequest,
+};
+use lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, DiagnosticRelatedInformation, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, DocumentSymbolParams, DocumentSymbolResponse, FoldingRange,
+    FoldingRangeParams, FoldingRangeProviderCapability, GotoDefinitionParams,
+    GotoDefinitionResponse, InitializeResult, Location, MessageType, NumberOrString, OneOf,
+    PublishDiagnosticsParams, Range, ServerCapabilities, ServerInfo, ShowMessageParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url, WorkspaceEdit,
+};
+use ludtwig_parser::syntax::untyped::{SyntaxNode, TextRange};
+
+use crate::check::rule::{Applicability, CheckResult, CheckSuggestion, Severity};
+use crate::check::{run_rules, SYNTAX_ERROR_CODE};
+use crate::config::ConfigResolver;
+use crate::error::ConfigurationError;
+use crate::linter::Linter;
+use crate::output::{error, exit_code, ProcessingEvent};
+use crate::process::{iteratively_apply_suggestions, FileContext};
+use crate::project::template_references;
+
+mod line_index;
+mod navigation;
+
+use line_index::LineIndex;
+
+/// Run the language server (`ludtwig lsp`) on stdin and stdout until the client exits.
+pub fn run(config_resolver: ConfigResolver) -> i32 {
+    let (connection, io_threads) = Connection::stdio();
+    let result = serve(&connection, config_resolver);
+    // the writer thread only stops after all senders are dropped
+    drop(connection);
+
+    match result.and_then(|()| io_threads.join().map_err(Into::into)) {
+        Ok(()) => exit_code::SUCCESS,
+        Err(e) => {
+            error!("Error: the language server stopped: {e}");
+            exit_code::PROCESSING_ERROR
+        }
+    }
+}
+
+/// Initialize the connection and answer the messages of the client until it shuts the server down.
+pub fn serve(
+    connection: &Connection,
+    config_resolver: ConfigResolver,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (id, _) = connection.initialize_start()?;
+    let initialize_result = InitializeResult {
+        capabilities: server_capabilities(),
+        server_info: Some(ServerInfo {
+            name: "ludtwig".to_string(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }),
+    };
+    connection.initialize_finish(id, serde_json::to_value(initialize_result)?)?;
+
+    let mut server = Server {
+        linter: Linter::with_resolver(config_resolver),
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(server.handle_request(request)))?;
+            }
+            Message::Notification(notification) => {
+                for message in server.handle_notification(notification) {
+                    connection.sender.send(message)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn server_capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// The open documents, which are checked with the config of their path.
+struct Server {
+    linter: Linter,
+    documents: HashMap<Url, Document>,
+}
+
+struct Document {
+    version: i32,
+    source_code: String,
+}
+
+/// A parsed document with the results of its rules.
+struct Analysis {
+    file_context: FileContext,
+    results: Vec<CheckResult>,
+    /// The default config is used if the config of the document can't be loaded
+    config_error: Option<ConfigurationError>,
+    /// Receives the events of the rules and fixes, which are not needed here
+    _rx: Receiver<ProcessingEvent>,
+}
+
+impl Server {
+    fn handle_request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            CodeActionRequest::METHOD => {
+                respond::<CodeActionRequest>(request, |p| Ok(self.code_actions(&p)))
+            }
+            Formatting::METHOD => respond::<Formatting>(request, |p| self.format(&p)),
+            DocumentSymbolRequest::METHOD => {
+                respond::<DocumentSymbolRequest>(request, |p| Ok(self.document_symbols(&p)))
+            }
+            FoldingRangeRequest::METHOD => {
+                respond::<FoldingRangeRequest>(request, |p| Ok(self.folding_ranges(&p)))
+            }
+            GotoDefinition::METHOD => {
+                respond::<GotoDefinition>(request, |p| Ok(self.definition(&p)))
+            }
+            _ => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request '{}'", request.method),
+            ),
+        }
+    }
+
+    /// Update the documents and return the messages for the client (like new diagnostics).
+    fn handle_notification(&mut self, notification: Notification) -> Vec<Message> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) =
+                    notification.extract::<DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                else {
+                    return vec![];
+                };
+                let document = params.text_document;
+                self.documents.insert(
+                    document.uri.clone(),
+                    Document {
+                        version: document.version,
+                        source_code: document.text,
+                    },
+                );
+                self.publish_diagnostics(document.uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(mut params) = notification
+                    .extract::<DidChangeTextDocumentParams>(DidChangeTextDocument::METHOD)
+                else {
+                    return vec![];
+                };
+                // the whole document is sent with every change (full sync)
+                let Some(change) = params.content_changes.pop() else {
+                    return vec![];
+                };
+                let uri = params.text_document.uri;
+                self.documents.insert(
+                    uri.clone(),
+                    Document {
+                        version: params.text_document.version,
+                        source_code: change.text,
+                    },
+                );
+                self.publish_diagnostics(uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = notification
+                    .extract::<DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                else {
+                    return vec![];
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                vec![notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+                    uri,
+                    diagnostics: vec![],
+                    version: None,
+                })]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn analyze(&self, uri: &Url) -> Option<Analysis> {
+        let document = self.documents.get(uri)?;
+        let path = document_path(uri);
+        let resolver = self.linter.config_resolver();
+        let (config, config_error) = match resolver.resolve(&path) {
+            Ok(config) => (config, None),
+            Err(e) => (resolver.fallback(), Some(e)),
+        };
+
+        let (file_context, rx) = self
+            .linter
+            .file_context(&path, &document.source_code, config);
+        let results = run_rules(&file_context);
+
+        Some(Analysis {
+            file_context,
+            results,
+            config_error,
+            _rx: rx,
+        })
+    }
+
+    fn publish_diagnostics(&self, uri: Url) -> Vec<Message> {
+        let Some(analysis) = self.analyze(&uri) else {
+            return vec![];
+        };
+        let mut messages = vec![];
+        if let Some(e) = &analysis.config_error {
+            messages.push(notify::<ShowMessage>(ShowMessageParams {
+                typ: MessageType::ERROR,
+                message: format!("ludtwig: the config can't be loaded, using the default: {e}"),
+            }));
+        }
+
+        let source_code = &analysis.file_context.source_code;
+        let index = LineIndex::new(source_code);
+        let mut diagnostics: Vec<lsp_types::Diagnostic> = analysis
+            .file_context
+            .parse_errors
+            .iter()
+            .map(|parse_error| lsp_types::Diagnostic {
+                range: index.range(parse_error.range),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: Some(NumberOrString::String(SYNTAX_ERROR_CODE.to_string())),
+                source: Some("ludtwig".to_string()),
+                message: parse_error.expected_message(),
+                ..lsp_types::Diagnostic::default()
+            })
+            .collect();
+        diagnostics.extend(
+            analysis
+                .results
+                .iter()
+                .map(|result| to_lsp_diagnostic(result, &uri, &index)),
+        );
+
+        messages.push(notify::<PublishDiagnostics>(PublishDiagnosticsParams {
+            version: self.documents.get(&uri).map(|document| document.version),
+            uri,
+            diagnostics,
+        }));
+        messages
+    }
+
+    /// A quick fix for every suggestion which intersects the requested range.
+    fn code_actions(&self, params: &CodeActionParams) -> Option<Vec<CodeActionOrCommand>> {
+        let uri = &params.text_document.uri;
+        let analysis = self.analyze(uri)?;
+        let index = LineIndex::new(&analysis.file_context.source_code);
+        let requested = TextRange::new(
+            index.offset(params.range.start),
+            index.offset(params.range.end),
+        );
+
+        let actions = analysis
+            .results
+            .iter()
+            .flat_map(|result| {
+                result
+                    .suggestions()
+                    .iter()
+                    .map(move |suggestion| (result, suggestion))
+            })
+            .filter(|(_, suggestion)| suggestion.syntax_range().intersect(requested).is_some())
+            .map(|(result, suggestion)| {
+                let safe = suggestion.applicability == Applicability::Safe;
+                let edits = suggestion
+                    .edits
+                    .iter()
+                    .map(|edit| lsp_types::TextEdit {
+                        range: index.range(edit.syntax_range),
+                        new_text: edit.replace_with.clone(),
+                    })
+                    .collect();
+
+                CodeActionOrCommand::CodeAction(CodeAction {
+                    title: format!(
+                        "{}: {}{}",
+                        result.rule_name(),
+                        suggestion.message,
+                        if safe { "" } else { " (unsafe)" }
+                    ),
+                    kind: Some(CodeActionKind::QUICKFIX),
+                    diagnostics: Some(vec![to_lsp_diagnostic(result, uri, &index)]),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(HashMap::from([(uri.clone(), edits)])),
+                        ..WorkspaceEdit::default()
+                    }),
+                    is_preferred: Some(safe),
+                    ..CodeAction::default()
+                })
+            })
+            .collect();
+
+        Some(actions)
+    }
+
+    /// Apply the safe suggestions of all rules (like `--fix`) and replace the whole document if it changed.
+    fn format(
+        &self,
+        params: &DocumentFormattingParams,
+    ) -> Result<Option<Vec<lsp_types::TextEdit>>, String> {
+        let Some(analysis) = self.analyze(&params.text_document.uri) else {
+            return Ok(None);
+        };
+        let original = analysis.file_context.source_code.clone();
+        let (fixed, _, _, _) =
+            iteratively_apply_suggestions(analysis.file_context, analysis.results)
+                .map_err(|e| e.to_string())?;
+        if fixed.source_code == original {
+            return Ok(Some(vec![]));
+        }
+
+        Ok(Some(vec![lsp_types::TextEdit {
+            range: LineIndex::new(&original).full_range(),
+            new_text: fixed.source_code,
+        }]))
+    }
+
+    fn document_symbols(&self, params: &DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let analysis = self.analyze(&params.text_document.uri)?;
+        let index = LineIndex::new(&analysis.file_context.source_code);
+
+        Some(DocumentSymbolResponse::Nested(
+            navigation::document_symbols(&analysis.file_context.tree_root, &index),
+        ))
+    }
+
+    fn folding_ranges(&self, params: &FoldingRangeParams) -> Option<Vec<FoldingRange>> {
+        let analysis = self.analyze(&params.text_document.uri)?;
+        let index = LineIndex::new(&analysis.file_context.source_code);
+
+        Some(navigation::folding_ranges(
+            &analysis.file_context.tree_root,
+            &index,
+        ))
+    }
+
+    /// Jump from the template name of an `extends`, `include`, `embed`, ... to the template file.
+    fn definition(&self, params: &GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = &params.text_document_position_params;
+        let uri = &position.text_document.uri;
+        let document = self.documents.get(uri)?;
+        let tree_root =
+            SyntaxNode::new_root(ludtwig_parser::parse(&document.source_code).green_node);
+        let offset = LineIndex::new(&document.source_code).offset(position.position);
+
+        let reference = template_references(&tree_root)
+            .into_iter()
+            .find(|reference| reference.range.contains_inclusive(offset))?;
+        let path = uri.to_file_path().ok()?;
+        let resolver = self.linter.config_resolver();
+        let config = resolver
+            .resolve(&path)
+            .unwrap_or_else(|_| resolver.fallback());
+        let found = config
+            .project()
+            .resolve(&reference.name, reference.kind, &path)?;
+
+        Some(GotoDefinitionResponse::Scalar(Location {
+            uri: Url::from_file_path(found).ok()?,
+            range: Range::default(),
+        }))
+    }
+}
+
+/// Answer a request with the result of the handler (or an error if its params are invalid or it failed).
+fn respond<R: LspRequest>(
+    request: Request,
+    handler: impl FnOnce(R::Params) -> Result<R::Result, String>,
+) -> Response {
+    let id = request.id.clone();
+    match request.extract::<R::Params>(R::METHOD) {
+        Ok((id, params)) => match handler(params) {
+            Ok(result) => Response::new_ok(id, result),
+            Err(message) => Response::new_err(id, ErrorCode::RequestFailed as i32, message),
+        },
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+fn notify<N: LspNotification>(params: N::Params) -> Message {
+    Message::Notification(Notification::new(N::METHOD.to_string(), params))
+}
+
+/// The file path of the document, which is used to find its config and in `ludtwig-ignore-file`.
+/// Documents which are not saved yet (like `untitled:` URIs) use the path of the URI.
+fn document_path(uri: &Url) -> PathBuf {
+    uri.to_file_path()
+        .unwrap_or_else(|()| PathBuf::from(uri.path()))
+}
+
+fn to_lsp_diagnostic(result: &CheckResult, uri: &Url, index: &LineIndex) -> lsp_types::Diagnostic {
+    let range = result
+        .primary()
+        .or_else(|| result.secondary().first())
+        .map(|note| note.syntax_range)
+        .or_else(|| {
+            result
+                .suggestions()
+                .first()
+                .map(CheckSuggestion::syntax_range)
+        })
+        .unwrap_or_default();
+    let message = match result.primary() {
+        Some(primary) if !primary.message.is_empty() => {
+            format!("{}\n{}", result.message(), primary.message)
+        }
+        _ => result.message().to_string(),
+    };
+    let related_information: Vec<DiagnosticRelatedInformation> = result
+        .secondary()
+        .iter()
+        .map(|note| DiagnosticRelatedInformation {
+            location: Location {
+                uri: uri.clone(),
+                range: index.range(note.syntax_range),
+            },
+            message: note.message.clone(),
+        })
+        .collect();
+
+    lsp_types::Diagnostic {
+        range: index.range(range),
+        severity: Some(match result.severity() {
+            Severity::Error => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+            Severity::Help => DiagnosticSeverity::HINT,
+            Severity::Info => DiagnosticSeverity::INFORMATION,
+        }),
+        code: Some(NumberOrString::String(result.rule_name().to_string())),
+        source: Some("ludtwig".to_string()),
+        message,
+        related_information: (!related_information.is_empty()).then_some(related_information),
+        ..lsp_types::Diagnostic::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::fmt::Write;
+    use std::fs;
+    use std::path::Path;
+    use std::thread::{self, JoinHandle};
+
+    use expect_test::expect;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId};
+    use lsp_types::notification::{
+        DidOpenTextDocument, Exit, Initialized, Notification as LspNotification, PublishDiagnostics,
+    };
+    use lsp_types::request::{
+        CodeActionRequest, DocumentSymbolRequest, FoldingRangeRequest, Formatting, GotoDefinition,
+        Initialize, Request as LspRequest, Shutdown,
+    };
+    use lsp_types::{
+        CodeActionContext, CodeActionOrCommand, CodeActionParams, DidOpenTextDocumentParams,
+        DocumentFormattingParams, DocumentSymbolParams, DocumentSymbolResponse, FoldingRangeParams,
+        FormattingOptions, GotoDefinitionParams, GotoDefinitionResponse, InitializeParams,
+        Position, PublishDiagnosticsParams, Range, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, Url,
+    };
+
+    use crate::config::{ActiveConfig, Config, ConfigResolver, DEFAULT_CONFIG_PATH};
+    use crate::test_util::temp_dir;
+
+    use super::serve;
+
+    const PAGE: &str = "{% sw_extends '@Storefront/base.html.twig' %}\n\
+        {% block content %}\n\
+        <div id=\"main\">\n\
+        <span></span>\n\
+        </div>\n\
+        {% if a === 5 %}{% endif %}\n\
+        {% endblock %}\n\
+        {% macro field(name) %}{% endmacro %}\n\
+        <p>\n";
+
+    /// Talks to the server like an editor, one message after the other.
+    struct Client {
+        connection: Connection,
+        server: JoinHandle<Result<(), String>>,
+        next_id: i32,
+        /// Notifications which arrived while waiting for a response
+        notifications: VecDeque<Notification>,
+    }
+
+    impl Client {
+        /// Start a server with the default config and initialize it.
+        fn start() -> Self {
+            let (server_connection, client_connection) = Connection::memory();
+            let fallback =
+                ActiveConfig::new(Config::new(DEFAULT_CONFIG_PATH).unwrap(), None).unwrap();
+            let server = thread::spawn(move || {
+                serve(
+                    &server_connection,
+                    ConfigResolver::new(fallback, false, false),
+                )
+                .map_err(|e| e.to_string())
+            });
+            let mut client = Client {
+                connection: client_connection,
+                server,
+                next_id: 0,
+                notifications: VecDeque::new(),
+            };
+
+            let initialized = client.request::<Initialize>(InitializeParams::default());
+            assert_eq!(initialized.server_info.unwrap().name, "ludtwig");
+            client.notify::<Initialized>(lsp_types::InitializedParams {});
+            client
+        }
+
+        /// Open a document and return its first diagnostics.
+        fn open(&mut self, uri: &Url, source_code: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "twig".to_string(),
+                    1,
+                    source_code.to_string(),
+                ),
+            });
+
+            let published = self.published_diagnostics();
+            assert_eq!(&published.uri, uri);
+            assert_eq!(published.version, Some(1));
+            published
+        }
+
+        fn shutdown(mut self) {
+            self.request::<Shutdown>(());
+            self.notify::<Exit>(());
+            assert_eq!(self.server.join().unwrap(), Ok(()));
+        }
+
+        fn request<R: LspRequest>(&mut self, params: R::Params) -> R::Result {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Message::Request(Request::new(
+                    id.clone(),
+                    R::METHOD.to_string(),
+                    params,
+                )))
+                .unwrap();
+
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) if response.id == id => {
+                        assert!(response.error.is_none(), "{:?}", response.error);
+                        return serde_json::from_value(response.result.unwrap_or_default())
+                            .unwrap();
+                    }
+                    Message::Notification(notification) => {
+                        self.notifications.push_back(notification);
+                    }
+                    message => panic!("unexpected message {message:?}"),
+                }
+            }
+        }
+
+        fn notify<N: LspNotification>(&self, params: N::Params) {
+            self.connection
+                .sender
+                .send(Message::Notification(Notification::new(
+                    N::METHOD.to_string(),
+                    params,
+                )))
+                .unwrap();
+        }
+
+        fn published_diagnostics(&mut self) -> PublishDiagnosticsParams {
+            loop {
+                let notification = match self.notifications.pop_front() {
+                    Some(notification) => notification,
+                    None => match self.connection.receiver.recv().unwrap() {
+                        Message::Notification(notification) => notification,
+                        message => panic!("unexpected message {message:?}"),
+                    },
+                };
+                if notification.method == PublishDiagnostics::METHOD {
+                    return serde_json::from_value(notification.params).unwrap();
+                }
+            }
+        }
+
+        /// The titles of the code actions at the position and whether they are preferred.
+        fn code_action_titles(
+            &mut self,
+            uri: &Url,
+            position: Position,
+        ) -> Vec<(String, Option<bool>)> {
+            self.request::<CodeActionRequest>(CodeActionParams {
+                text_document: document(uri),
+                range: Range::new(position, position),
+                context: CodeActionContext::default(),
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+                partial_result_params: lsp_types::PartialResultParams::default(),
+            })
+            .unwrap()
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => (action.title, action.is_preferred),
+                CodeActionOrCommand::Command(command) => (command.title, None),
+            })
+            .collect()
+        }
+    }
+
+    fn document(uri: &Url) -> TextDocumentIdentifier {
+        TextDocumentIdentifier::new(uri.clone())
+    }
+
+    /// The URI of a template below the directory, which doesn't need to exist.
+    fn page_uri(dir: &Path) -> Url {
+        Url::from_file_path(dir.join("page/index.html.twig")).unwrap()
+    }
+
+    fn format_diagnostics(published: &PublishDiagnosticsParams) -> String {
+        let mut diagnostics = String::new();
+        for d in &published.diagnostics {
+            writeln!(
+                diagnostics,
+                "{}:{}-{}:{} {:?} {:?} {}",
+                d.range.start.line,
+                d.range.start.character,
+                d.range.end.line,
+                d.range.end.character,
+                d.severity.unwrap(),
+                d.code.clone().unwrap(),
+                d.message.replace('\n', " | ")
+            )
+            .unwrap();
+        }
+        diagnostics
+    }
+
+    #[test]
+    fn lsp_server_publishes_diagnostics() {
+        let temp = temp_dir();
+        let uri = page_uri(temp.path());
+        let mut client = Client::start();
+
+        let published = client.open(&uri, PAGE);
+        expect![[r#"
+            8:3-9:0 Error String("SyntaxError") expected </p> ending tag but reached end of file
+            2:0-2:0 Hint String("indentation") Missing indentation | Expected indentation of 4 spaces before this
+            3:0-3:0 Hint String("indentation") Missing indentation | Expected indentation of 8 spaces before this
+            4:0-4:0 Hint String("indentation") Missing indentation | Expected indentation of 4 spaces before this
+            5:0-5:0 Hint String("indentation") Missing indentation | Expected indentation of 4 spaces before this
+            0:45-1:0 Hint String("twig-block-line-breaks") Wrong line break around block | Expected 2 line breaks here
+            6:14-7:0 Hint String("twig-block-line-breaks") Wrong line break around block | Expected 2 line breaks here
+            5:8-5:11 Error String("twig-use-is-same-as") === is not a valid twig operator | This is not a valid Twig operator, try 'is same as(condition)' instead
+        "#]].assert_eq(&format_diagnostics(&published));
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn lsp_server_offers_code_actions() {
+        let temp = temp_dir();
+        let uri = page_uri(temp.path());
+        let mut client = Client::start();
+        client.open(&uri, PAGE);
+
+        // the unsafe fix of '===' is offered for the cursor on the operator
+        expect![[r#"
+            [
+                (
+                    "twig-use-is-same-as: Try this instead (unsafe)",
+                    Some(
+                        false,
+                    ),
+                ),
+            ]
+        "#]]
+        .assert_debug_eq(&client.code_action_titles(&uri, Position::new(5, 9)));
+        // but not next to it
+        assert_eq!(client.code_action_titles(&uri, Position::new(5, 3)), vec![]);
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn lsp_server_formats_documents() {
+        let temp = temp_dir();
+        let uri = page_uri(temp.path());
+        let mut client = Client::start();
+        client.open(&uri, PAGE);
+
+        let edits = client
+            .request::<Formatting>(DocumentFormattingParams {
+                text_document: document(&uri),
+                options: FormattingOptions::default(),
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+            })
+            .unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(9, 0))
+        );
+        expect![[r#"
+            {% sw_extends '@Storefront/base.html.twig' %}
+
+            {% block content %}
+                <div id="main">
+                    <span></span>
+                </div>
+                {% if a === 5 %}{% endif %}
+            {% endblock %}
+
+            {% macro field(name) %}{% endmacro %}
+            <p>
+        "#]]
+        .assert_eq(&edits[0].new_text);
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn lsp_server_lists_symbols_and_folding_ranges() {
+        let temp = temp_dir();
+        let uri = page_uri(temp.path());
+        let mut client = Client::start();
+        client.open(&uri, PAGE);
+
+        let Some(DocumentSymbolResponse::Nested(symbols)) = client
+            .request::<DocumentSymbolRequest>(DocumentSymbolParams {
+                text_document: document(&uri),
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+                partial_result_params: lsp_types::PartialResultParams::default(),
+            })
+        else {
+            panic!("expected nested document symbols");
+        };
+        let outline: Vec<(String, u32, Vec<String>)> = symbols
+            .into_iter()
+            .map(|symbol| {
+                (
+                    symbol.name,
+                    symbol.range.start.line,
+                    symbol
+                        .children
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|child| child.name)
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                ("content".to_string(), 1, vec!["#main".to_string()]),
+                ("field".to_string(), 7, vec![]),
+            ]
+        );
+
+        let folding = client
+            .request::<FoldingRangeRequest>(FoldingRangeParams {
+                text_document: document(&uri),
+                work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+                partial_result_params: lsp_types::PartialResultParams::default(),
+            })
+            .unwrap();
+        let folding: Vec<(u32, u32)> = folding
+            .iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        assert_eq!(folding, vec![(1, 5), (2, 3)]);
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn lsp_server_goes_to_template_definition() {
+        let temp = temp_dir();
+        let dir = fs::canonicalize(temp.path()).unwrap();
+        // the temporary directory is outside of the project (working directory) of the default config,
+        // so relative names are only searched next to the page
+        fs::create_dir_all(dir.join("page")).unwrap();
+        fs::write(
+            dir.join("page/base.html.twig"),
+            "{% block content %}{% endblock %}\n",
+        )
+        .unwrap();
+        let uri = page_uri(&dir);
+        let mut client = Client::start();
+        client.open(&uri, PAGE);
+
+        let definition = client.request::<GotoDefinition>(GotoDefinitionParams {
+            text_document_position_params: TextDocumentPositionParams::new(
+                document(&uri),
+                Position::new(0, 20),
+            ),
+            work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+            partial_result_params: lsp_types::PartialResultParams::default(),
+        });
+        let Some(GotoDefinitionResponse::Scalar(location)) = definition else {
+            panic!("expected the location of the extended template");
+        };
+        assert_eq!(
+            location.uri,
+            Url::from_file_path(dir.join("page/base.html.twig")).unwrap()
+        );
+
+        client.shutdown();
+    }
+
+    #[test]
+    fn lsp_server_counts_columns_in_utf16() {
+        let temp = temp_dir();
+        let uri = page_uri(temp.path());
+        let mut client = Client::start();
+
+        // 'ä' is one UTF-16 code unit (but two bytes), '𝄞' two code units (but four bytes)
+        let published = client.open(&uri, "<p title=\"ä𝄞\">{% if a === 5 %}{% endif %}</p>\n");
+        expect![[r#"
+            0:23-0:26 Error String("twig-use-is-same-as") === is not a valid twig operator | This is not a valid Twig operator, try 'is same as(condition)' instead
+        "#]].assert_eq(&format_diagnostics(&published));
+
+        expect![[r#"
+            [
+                (
+                    "twig-use-is-same-as: Try this instead (unsafe)",
+                    Some(
+                        false,
+                    ),
+                ),
+            ]
+        "#]]
+        .assert_debug_eq(&client.code_action_titles(&uri, Position::new(0, 23)));
+
+        client.shutdown();
+    }
+}
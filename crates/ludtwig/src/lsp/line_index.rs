@@ -0,0 +1,112 @@
+use lsp_types::{Position, Range};
+use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+/// Converts between byte offsets in the source code and LSP positions,
+/// which count the characters of a line in UTF-16 code units.
+#[derive(Debug)]
+pub struct LineIndex<'a> {
+    source_code: &'a str,
+    /// Byte offset of the start of each line
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source_code: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source_code.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+
+        Self {
+            source_code,
+            line_starts,
+        }
+    }
+
+    pub fn position(&self, offset: TextSize) -> Position {
+        let offset = usize::from(offset).min(self.source_code.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character = self.source_code[self.line_starts[line]..offset]
+            .chars()
+            .map(char::len_utf16)
+            .sum::<usize>();
+
+        Position::new(to_u32(line), to_u32(character))
+    }
+
+    pub fn range(&self, range: TextRange) -> Range {
+        Range::new(self.position(range.start()), self.position(range.end()))
+    }
+
+    /// The byte offset of the position, which is clamped to the end of its line (or the source code).
+    pub fn offset(&self, position: Position) -> TextSize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return to_text_size(self.source_code.len());
+        };
+        let line_end = self
+            .line_starts
+            .get(position.line as usize + 1)
+            .map_or(self.source_code.len(), |next| next - 1);
+
+        let mut utf16_column = 0;
+        let mut offset = line_start;
+        for c in self.source_code[line_start..line_end].chars() {
+            if utf16_column >= position.character as usize {
+                break;
+            }
+            utf16_column += c.len_utf16();
+            offset += c.len_utf8();
+        }
+
+        to_text_size(offset)
+    }
+
+    /// The range from the start to the end of the source code.
+    pub fn full_range(&self) -> Range {
+        Range::new(
+            Position::new(0, 0),
+            self.position(to_text_size(self.source_code.len())),
+        )
+    }
+}
+
+fn to_u32(value: usize) -> u32 {
+    u32::try_from(value).unwrap_or(u32::MAX)
+}
+
+fn to_text_size(offset: usize) -> TextSize {
+    TextSize::from(to_u32(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, Range};
+    use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+    use super::LineIndex;
+
+    #[test]
+    fn line_index_converts_offsets_and_utf16_positions() {
+        // 'ä' is two bytes but one UTF-16 code unit, '𝄞' four bytes and two code units
+        let source_code = "<p>\näb𝄞c\n";
+        let index = LineIndex::new(source_code);
+
+        assert_eq!(index.position(TextSize::from(0)), Position::new(0, 0));
+        assert_eq!(index.position(TextSize::from(4)), Position::new(1, 0));
+        assert_eq!(index.position(TextSize::from(7)), Position::new(1, 2));
+        assert_eq!(index.position(TextSize::from(11)), Position::new(1, 4));
+        assert_eq!(index.position(TextSize::from(13)), Position::new(2, 0));
+
+        assert_eq!(index.offset(Position::new(1, 4)), TextSize::from(11));
+        assert_eq!(index.offset(Position::new(1, 99)), TextSize::from(12));
+        assert_eq!(index.offset(Position::new(9, 0)), TextSize::from(13));
+
+        assert_eq!(
+            index.range(TextRange::new(TextSize::from(1), TextSize::from(6))),
+            Range::new(Position::new(0, 1), Position::new(1, 1))
+        );
+        assert_eq!(
+            index.full_range(),
+            Range::new(Position::new(0, 0), Position::new(2, 0))
+        );
+    }
+}
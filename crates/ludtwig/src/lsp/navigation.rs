@@ -0,0 +1,138 @@
+use lsp_types::{DocumentSymbol, FoldingRange, FoldingRangeKind, SymbolKind};
+use ludtwig_parser::syntax::typed::{AstNode, HtmlTag, TwigBlock};
+use ludtwig_parser::syntax::untyped::{SyntaxElement, SyntaxKind, SyntaxNode, TextRange};
+use ludtwig_parser::T;
+
+use super::line_index::LineIndex;
+
+/// Outline of the template: the twig blocks, macros and html tags with an id, nested like in the template.
+pub fn document_symbols(root: &SyntaxNode, index: &LineIndex) -> Vec<DocumentSymbol> {
+    root.children()
+        .flat_map(|child| node_symbols(&child, index))
+        .collect()
+}
+
+/// The symbol of the node (containing the symbols of its children) or else the symbols of its children.
+fn node_symbols(node: &SyntaxNode, index: &LineIndex) -> Vec<DocumentSymbol> {
+    let children: Vec<DocumentSymbol> = node
+        .children()
+        .flat_map(|child| node_symbols(&child, index))
+        .collect();
+
+    let Some((name, detail, kind, selection_range)) = symbol(node) else {
+        return children;
+    };
+
+    #[allow(deprecated)] // the `deprecated` field has to be initialized
+    let symbol = DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: index.range(trimmed_range(node)),
+        selection_range: index.range(selection_range),
+        children: Some(children),
+    };
+    vec![symbol]
+}
+
+/// Name, detail, kind and the range of the name of a node which is a symbol.
+fn symbol(node: &SyntaxNode) -> Option<(String, Option<String>, SymbolKind, TextRange)> {
+    match node.kind() {
+        SyntaxKind::TWIG_BLOCK => {
+            let name = TwigBlock::cast(node.clone())?.name()?;
+            Some((
+                name.text().to_string(),
+                Some("block".to_string()),
+                SymbolKind::MODULE,
+                name.text_range(),
+            ))
+        }
+        SyntaxKind::TWIG_MACRO => {
+            let name = node
+                .children()
+                .find(|child| child.kind() == SyntaxKind::TWIG_MACRO_STARTING_BLOCK)?
+                .children_with_tokens()
+                .filter_map(SyntaxElement::into_token)
+                .find(|token| token.kind() == T![word])?;
+            Some((
+                name.text().to_string(),
+                Some("macro".to_string()),
+                SymbolKind::FUNCTION,
+                name.text_range(),
+            ))
+        }
+        SyntaxKind::HTML_TAG => {
+            let tag = HtmlTag::cast(node.clone())?;
+            let id = tag
+                .attributes()
+                .find(|attribute| attribute.name().is_some_and(|name| name.text() == "id"))?
+                .value()?
+                .get_inner()?;
+            let tag_name = tag.name().map(|name| name.text().to_string());
+            Some((
+                format!("#{}", id.syntax().text()),
+                tag_name.map(|name| format!("<{name}>")),
+                SymbolKind::OBJECT,
+                id.syntax().text_range(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Ranges of multi line blocks, tags and comments which can be folded.
+/// Blocks and tags are folded until the line before their closing part, so it stays visible.
+pub fn folding_ranges(root: &SyntaxNode, index: &LineIndex) -> Vec<FoldingRange> {
+    root.descendants()
+        .filter_map(|node| {
+            let comment = matches!(
+                node.kind(),
+                SyntaxKind::HTML_COMMENT | SyntaxKind::TWIG_COMMENT
+            );
+            let foldable = comment
+                || matches!(
+                    node.kind(),
+                    SyntaxKind::TWIG_BLOCK
+                        | SyntaxKind::TWIG_MACRO
+                        | SyntaxKind::TWIG_IF
+                        | SyntaxKind::TWIG_FOR
+                        | SyntaxKind::TWIG_SET
+                        | SyntaxKind::TWIG_APPLY
+                        | SyntaxKind::TWIG_EMBED
+                        | SyntaxKind::HTML_TAG
+                );
+            if !foldable {
+                return None;
+            }
+
+            let range = index.range(trimmed_range(&node));
+            let end_line = if comment {
+                range.end.line
+            } else {
+                range.end.line.saturating_sub(1)
+            };
+            (end_line > range.start.line).then(|| FoldingRange {
+                start_line: range.start.line,
+                start_character: None,
+                end_line,
+                end_character: None,
+                kind: comment.then_some(FoldingRangeKind::Comment),
+                collapsed_text: None,
+            })
+        })
+        .collect()
+}
+
+/// The range of the node without its leading whitespace and line breaks.
+fn trimmed_range(node: &SyntaxNode) -> TextRange {
+    let range = node.text_range();
+    let start = node
+        .descendants_with_tokens()
+        .filter_map(SyntaxElement::into_token)
+        .find(|token| !token.kind().is_trivia())
+        .map_or(range.start(), |token| token.text_range().start());
+
+    TextRange::new(start, range.end())
+}
@@ -1,70 +1,1009 @@
-use codespan_reporting::term::termcolor::{Buffer, BufferWriter, ColorChoice};
+use clap::ValueEnum;
+use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::files::{Files, SimpleFiles};
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{
+    Buffer, BufferWriter, Color, ColorChoice, ColorSpec, WriteColor,
+};
+use ludtwig_parser::syntax::untyped::TextRange;
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use crate::check::rule::Severity;
+use crate::check::SYNTAX_ERROR_CODE;
+use crate::error::ErrorKind;
+
+/// Set when stdout is reserved for the fixed source code (`--stdin`) or diffs (`--diff`),
+/// informational messages are written to stderr instead.
+static STDOUT_RESERVED: AtomicBool = AtomicBool::new(false);
+
+/// Held while printing to the terminal, so interactive prompts (`--fix-interactive`) are not interrupted.
+pub static TERMINAL_LOCK: Mutex<()> = Mutex::new(());
+
+/// The `ColorMode` as u8, set once from the CLI arguments.
+static COLOR_MODE: AtomicU8 = AtomicU8::new(ColorMode::Auto as u8);
+
+/// Set when only diagnostics should be printed (`--quiet`), which hides the informational messages.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_color_mode(mode: ColorMode) {
+    COLOR_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+fn color_mode() -> ColorMode {
+    match COLOR_MODE.load(Ordering::Relaxed) {
+        x if x == ColorMode::Always as u8 => ColorMode::Always,
+        x if x == ColorMode::Never as u8 => ColorMode::Never,
+        _ => ColorMode::Auto,
+    }
+}
+
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+pub fn reserve_stdout() {
+    STDOUT_RESERVED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_stdout_reserved() -> bool {
+    STDOUT_RESERVED.load(Ordering::Relaxed)
+}
+
+/// Like `println!` but for informational messages, which are written to stderr while stdout is reserved
+/// and not at all with `--quiet`.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::is_quiet() {
+        } else if $crate::output::is_stdout_reserved() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use info;
+
+/// Like `eprintln!` for errors, which are always printed (also with `--quiet`).
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!($($arg)*);
+    };
+}
+pub(crate) use error;
+
+/// Colors for stdout (like diffs), by default only if it is a terminal so diffs can be piped into a patch file.
+pub fn stdout_color_choice() -> ColorChoice {
+    color_choice(color_mode(), io::stdout().is_terminal(), no_color_env())
+}
+
+/// Colors for stderr (like diagnostics), by default only if it is a terminal so log files stay readable.
+pub fn stderr_color_choice() -> ColorChoice {
+    color_choice(color_mode(), io::stderr().is_terminal(), no_color_env())
+}
+
+/// See <https://no-color.org>
+fn no_color_env() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+fn color_choice(mode: ColorMode, is_terminal: bool, no_color: bool) -> ColorChoice {
+    match mode {
+        ColorMode::Always => ColorChoice::Always,
+        ColorMode::Auto if is_terminal && !no_color => ColorChoice::Auto,
+        ColorMode::Auto | ColorMode::Never => ColorChoice::Never,
+    }
+}
+
+/// Exit codes of the check command, so scripts can tell broken templates from style issues
+/// and failures of ludtwig itself. If there are multiple kinds of failures, the highest code is used.
+pub mod exit_code {
+    /// No diagnostics failed the run (see `--fail-on`)
+    pub const SUCCESS: i32 = 0;
+    /// Diagnostics of rules (or files which would be fixed with `--check`) failed the run
+    pub const LINT_FAILURE: i32 = 1;
+    // 2 is used for invalid command line arguments
+    /// Templates have syntax errors
+    pub const PARSE_ERROR: i32 = 3;
+    /// Files can't be read or written or fixing them failed
+    pub const PROCESSING_ERROR: i32 = 4;
+    /// The configuration is invalid
+    pub const CONFIG_ERROR: i32 = 5;
+}
 
 pub enum ProcessingEvent {
     FileProcessed,
-    Report(Severity),
-    OutputStderrMessage(Buffer),
+    /// Applying the code suggestions would change the file (`--check`).
+    FileNeedsFix,
+    /// An error which is not a diagnostic of a file (like a file which can't be read).
+    Error(ErrorKind),
+    Diagnostic(FileDiagnostic),
+    /// Output for stdout (like a diff) which belongs to the file.
+    OutputStdoutMessage(PathBuf, Buffer),
+    /// Other files (like extended templates) the results of the file depend on, which `--watch` uses to recheck it.
+    Dependencies(Vec<PathBuf>),
 }
 
-/// This function receives all the [`CliOutputMessage`] instances from the receiver channel and
-/// prints information to the command line interface.
-pub fn handle_processing_output(rx: &Receiver<ProcessingEvent>) -> i32 {
-    let mut file_count = 0;
-    let mut error_count = 0;
-    let mut warning_count = 0;
-    let mut help_count = 0;
-    let mut info_count = 0;
+/// How the diagnostics are grouped in the output (`--group-by`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+    Rule,
+    Severity,
+}
+
+/// The lowest severity of diagnostics which fails the run (`--fail-on`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    Error,
+    Warning,
+    #[default]
+    Help,
+    Info,
+    /// Only syntax and processing errors fail the run
+    Never,
+}
+
+impl FailOn {
+    fn includes(self, severity: Severity) -> bool {
+        let threshold = match self {
+            FailOn::Error => 0,
+            FailOn::Warning => 1,
+            FailOn::Help => 2,
+            FailOn::Info => 3,
+            FailOn::Never => return false,
+        };
+        let rank = match severity {
+            Severity::Error => 0,
+            Severity::Warning => 1,
+            Severity::Help => 2,
+            Severity::Info => 3,
+        };
+        rank <= threshold
+    }
+}
+
+/// When colors are used in the output (`--color`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorMode {
+    /// Only if the output is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
 
-    let stderr_writer = BufferWriter::stderr(ColorChoice::Always);
+/// Options for printing the results, which apply to all files.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct OutputOptions {
+    pub group_by: Option<GroupBy>,
+    /// Only print the diagnostics (without informational messages and the summary)
+    pub quiet: bool,
+    /// Only print the summary
+    pub summary_only: bool,
+    /// Only print the diffs and errors, without the other diagnostics and the summary
+    pub diff_only: bool,
+    /// Print at most this many diagnostics (all are counted in the summary)
+    pub max_diagnostics: Option<usize>,
+    /// Print a single line per diagnostic
+    pub short: bool,
+    pub fail_on: FailOn,
+    /// Fail the run if there are more warnings than this, regardless of `fail_on`
+    pub max_warnings: Option<usize>,
+}
+
+/// The name and source code of a file, which is shared by all its diagnostics.
+pub type DiagnosticFiles = Arc<SimpleFiles<String, String>>;
+
+/// A single diagnostic of a file. All diagnostics are collected and printed sorted by their location,
+/// so the output doesn't depend on the order in which the files were processed.
+pub struct FileDiagnostic {
+    pub path: PathBuf,
+    /// The location of the diagnostic in the file, which is used for sorting
+    pub range: TextRange,
+    /// The rule name or the kind of the diagnostic (like `SyntaxError`)
+    pub code: String,
+    pub severity: Severity,
+    files: DiagnosticFiles,
+    diagnostic: Diagnostic<usize>,
+}
+
+impl FileDiagnostic {
+    /// The labels of the diagnostic must point into the (only) file in `files`.
+    pub fn new(
+        path: &Path,
+        range: TextRange,
+        severity: Severity,
+        files: &DiagnosticFiles,
+        diagnostic: Diagnostic<usize>,
+    ) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            range,
+            code: diagnostic.code.clone().unwrap_or_default(),
+            severity,
+            files: Arc::clone(files),
+            diagnostic,
+        }
+    }
+
+    /// Create the files for the diagnostics of a single file.
+    pub fn files(path: &Path, source_code: &str) -> (DiagnosticFiles, usize) {
+        let mut files = SimpleFiles::new();
+        let file_id = files.add(path.to_string_lossy().into_owned(), source_code.to_owned());
+        (Arc::new(files), file_id)
+    }
+
+    pub fn render(&self, out: &mut impl WriteColor) -> io::Result<()> {
+        term::emit(
+            out,
+            &term::Config::default(),
+            &*self.files,
+            &self.diagnostic,
+        )
+        .map_err(io::Error::other)
+    }
+
+    /// Line and column number (starting at 1) of the start of the range.
+    pub fn location(&self) -> (usize, usize) {
+        let file_id = self
+            .diagnostic
+            .labels
+            .first()
+            .map_or(0, |label| label.file_id);
+        self.files
+            .location(file_id, self.range.start().into())
+            .map_or((1, 1), |location| {
+                (location.line_number, location.column_number)
+            })
+    }
+
+    pub fn diagnostic(&self) -> &Diagnostic<usize> {
+        &self.diagnostic
+    }
+
+    /// Render the diagnostic as a single line like `path:line:col: severity[rule] message` (`--short`).
+    pub fn render_short(&self, out: &mut impl WriteColor) -> io::Result<()> {
+        let (line, column) = self.location();
+        let (severity, color) = match self.severity {
+            Severity::Error => ("error", Color::Red),
+            Severity::Warning => ("warning", Color::Yellow),
+            Severity::Help => ("help", Color::Cyan),
+            Severity::Info => ("info", Color::Green),
+        };
+
+        write!(out, "{}:{line}:{column}: ", self.path.display())?;
+        out.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+        write!(out, "{severity}[{}]", self.code)?;
+        out.reset()?;
+        writeln!(out, " {}", self.diagnostic.message)
+    }
+}
+
+/// Number of reported diagnostics by their severity.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SeverityCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub helps: usize,
+    pub infos: usize,
+}
+
+impl SeverityCounts {
+    pub fn add(&mut self, severity: Severity) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+            Severity::Help => self.helps += 1,
+            Severity::Info => self.infos += 1,
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.errors + self.warnings + self.helps + self.infos
+    }
+}
+
+impl AddAssign for SeverityCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        self.helps += other.helps;
+        self.infos += other.infos;
+    }
+}
+
+/// Counts of the processed files and reported diagnostics.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub file_count: usize,
+    pub needs_fix_count: usize,
+    pub counts: SeverityCounts,
+    /// Diagnostics by their rule name (or kind like `SyntaxError`)
+    pub by_rule: BTreeMap<String, SeverityCounts>,
+    pub by_file: BTreeMap<PathBuf, SeverityCounts>,
+    /// Errors like files which can't be read
+    pub processing_errors: usize,
+    /// Errors in config files
+    pub configuration_errors: usize,
+}
+
+impl Summary {
+    /// Count the event if it is a file or diagnostic.
+    pub fn record(&mut self, event: &ProcessingEvent) {
+        match event {
+            ProcessingEvent::FileProcessed => {
+                self.file_count += 1;
+            }
+            ProcessingEvent::FileNeedsFix => {
+                self.needs_fix_count += 1;
+            }
+            ProcessingEvent::Error(ErrorKind::Processing) => {
+                self.processing_errors += 1;
+            }
+            ProcessingEvent::Error(ErrorKind::Configuration) => {
+                self.configuration_errors += 1;
+            }
+            ProcessingEvent::Diagnostic(diagnostic) => {
+                self.counts.add(diagnostic.severity);
+                self.by_rule
+                    .entry(diagnostic.code.clone())
+                    .or_default()
+                    .add(diagnostic.severity);
+                self.by_file
+                    .entry(diagnostic.path.clone())
+                    .or_default()
+                    .add(diagnostic.severity);
+            }
+            ProcessingEvent::OutputStdoutMessage(..) | ProcessingEvent::Dependencies(_) => {}
+        }
+    }
+
+    /// The [`exit_code`] of the run, which depends on the most severe kind of failure.
+    pub fn exit_code(&self, fail_on: FailOn, max_warnings: Option<usize>) -> i32 {
+        if self.configuration_errors > 0 {
+            exit_code::CONFIG_ERROR
+        } else if self.processing_errors > 0 {
+            exit_code::PROCESSING_ERROR
+        } else if self.syntax_error_count() > 0 {
+            exit_code::PARSE_ERROR
+        } else if self.lint_failed(fail_on, max_warnings) {
+            exit_code::LINT_FAILURE
+        } else {
+            exit_code::SUCCESS
+        }
+    }
+
+    fn syntax_error_count(&self) -> usize {
+        self.by_rule
+            .get(SYNTAX_ERROR_CODE)
+            .map_or(0, SeverityCounts::total)
+    }
+
+    /// Are there diagnostics of rules or files which need fixing that fail the run?
+    fn lint_failed(&self, fail_on: FailOn, max_warnings: Option<usize>) -> bool {
+        if self.file_count == 0 {
+            return false;
+        }
+
+        // syntax errors have their own exit code
+        let counts = SeverityCounts {
+            errors: self.counts.errors - self.syntax_error_count(),
+            ..self.counts
+        };
+        let warnings_failed = match max_warnings {
+            Some(max) => counts.warnings > max,
+            None => fail_on.includes(Severity::Warning) && counts.warnings > 0,
+        };
+
+        (fail_on.includes(Severity::Error) && counts.errors > 0)
+            || warnings_failed
+            || (fail_on.includes(Severity::Help) && counts.helps > 0)
+            || (fail_on.includes(Severity::Info) && counts.infos > 0)
+            || (fail_on != FailOn::Never && self.needs_fix_count > 0)
+    }
+
+    /// A table of the diagnostics by rule and by file, followed by the total counts.
+    pub fn message(&self) -> String {
+        let total_label = format!("Total ({} files scanned)", self.file_count);
+        let name_width = self
+            .by_rule
+            .keys()
+            .map(|rule| rule.chars().count())
+            .chain(
+                self.by_file
+                    .keys()
+                    .map(|path| path.to_string_lossy().chars().count()),
+            )
+            .chain([total_label.len()])
+            .max()
+            .unwrap_or_default();
+
+        let header =
+            |name: &str| format!("{name:<name_width$}  Errors  Warnings  Helps  Info  Total\n");
+        let row = |name: &str, counts: &SeverityCounts| {
+            format!(
+                "{name:<name_width$}  {:>6}  {:>8}  {:>5}  {:>4}  {:>5}\n",
+                counts.errors,
+                counts.warnings,
+                counts.helps,
+                counts.infos,
+                counts.total()
+            )
+        };
+
+        let mut conclusion_msg = String::from("\n");
+        if !self.by_rule.is_empty() {
+            conclusion_msg.push_str(&header("Rule"));
+            for (rule, counts) in &self.by_rule {
+                conclusion_msg.push_str(&row(rule, counts));
+            }
+            conclusion_msg.push('\n');
+        }
+        if !self.by_file.is_empty() {
+            conclusion_msg.push_str(&header("File"));
+            for (path, counts) in &self.by_file {
+                conclusion_msg.push_str(&row(&path.to_string_lossy(), counts));
+            }
+            conclusion_msg.push('\n');
+        }
+        if self.by_rule.is_empty() && self.by_file.is_empty() {
+            conclusion_msg.push_str(&header(""));
+        }
+        conclusion_msg.push_str(&row(&total_label, &self.counts));
+
+        if self.needs_fix_count > 0 {
+            writeln!(
+                conclusion_msg,
+                "Files which would be fixed: {}",
+                self.needs_fix_count
+            )
+            .unwrap();
+        }
+        let error_count = self.processing_errors + self.configuration_errors;
+        if error_count > 0 {
+            writeln!(
+                conclusion_msg,
+                "Errors while processing files: {error_count}"
+            )
+            .unwrap();
+        }
+
+        conclusion_msg
+    }
+}
+
+impl AddAssign for Summary {
+    fn add_assign(&mut self, other: Self) {
+        self.file_count += other.file_count;
+        self.needs_fix_count += other.needs_fix_count;
+        self.processing_errors += other.processing_errors;
+        self.configuration_errors += other.configuration_errors;
+        self.counts += other.counts;
+        for (rule, counts) in other.by_rule {
+            *self.by_rule.entry(rule).or_default() += counts;
+        }
+        for (path, counts) in other.by_file {
+            *self.by_file.entry(path).or_default() += counts;
+        }
+    }
+}
+
+/// Write the diagnostics sorted by their path and location, optionally grouped by their rule or severity.
+pub fn write_diagnostics(
+    out: &mut impl WriteColor,
+    mut diagnostics: Vec<&FileDiagnostic>,
+    options: &OutputOptions,
+) -> io::Result<()> {
+    diagnostics.sort_by(|a, b| {
+        (&a.path, a.range.start(), a.range.end(), &a.code).cmp(&(
+            &b.path,
+            b.range.start(),
+            b.range.end(),
+            &b.code,
+        ))
+    });
+    let total = diagnostics.len();
+
+    let groups: Vec<(Option<&str>, Vec<&FileDiagnostic>)> = match options.group_by {
+        None => vec![(None, diagnostics)],
+        Some(group_by) => {
+            let mut groups: BTreeMap<(usize, &str), Vec<&FileDiagnostic>> = BTreeMap::new();
+            for diagnostic in diagnostics {
+                let key = match group_by {
+                    GroupBy::Rule => (0, diagnostic.code.as_str()),
+                    GroupBy::Severity => match diagnostic.severity {
+                        Severity::Error => (0, "Errors"),
+                        Severity::Warning => (1, "Warnings"),
+                        Severity::Help => (2, "Helps"),
+                        Severity::Info => (3, "Info"),
+                    },
+                };
+                groups.entry(key).or_default().push(diagnostic);
+            }
+            groups
+                .into_iter()
+                .map(|((_, name), diagnostics)| (Some(name), diagnostics))
+                .collect()
+        }
+    };
+
+    let mut remaining = options.max_diagnostics.unwrap_or(usize::MAX);
+    for (name, diagnostics) in groups {
+        if remaining == 0 {
+            break;
+        }
+        if let Some(name) = name {
+            writeln!(out, "=== {name} ({}) ===", diagnostics.len())?;
+            if !options.short {
+                writeln!(out)?;
+            }
+        }
+
+        for diagnostic in diagnostics.into_iter().take(remaining) {
+            if options.short {
+                diagnostic.render_short(out)?;
+            } else {
+                diagnostic.render(out)?;
+            }
+            remaining -= 1;
+        }
+    }
+
+    if let Some(max) = options.max_diagnostics.filter(|max| total > *max) {
+        writeln!(
+            out,
+            "... and {} more (limited by --max-diagnostics {max})",
+            total - max
+        )?;
+    }
+
+    Ok(())
+}
+
+/// This function receives all the [`ProcessingEvent`] instances from the receiver channel and
+/// prints information to the command line interface.
+pub fn handle_processing_output(rx: &Receiver<ProcessingEvent>, options: &OutputOptions) -> i32 {
+    let mut summary = Summary::default();
+    let mut diagnostics = vec![];
+    let mut stdout_messages = vec![];
 
     // receive all incoming messages until all sending ends are closed.
     while let Ok(msg) = rx.recv() {
+        summary.record(&msg);
         match msg {
-            ProcessingEvent::FileProcessed => {
-                file_count += 1;
-            }
-            ProcessingEvent::Report(severity) => match severity {
-                Severity::Error => {
-                    error_count += 1;
-                }
-                Severity::Warning => {
-                    warning_count += 1;
-                }
-                Severity::Help => {
-                    help_count += 1;
-                }
-                Severity::Info => {
-                    info_count += 1;
-                }
-            },
-            ProcessingEvent::OutputStderrMessage(buffer) => {
-                stderr_writer.print(&buffer).unwrap();
+            ProcessingEvent::Diagnostic(diagnostic) => diagnostics.push(diagnostic),
+            ProcessingEvent::OutputStdoutMessage(path, buffer) => {
+                stdout_messages.push((path, buffer));
             }
+            _ => {}
         }
     }
 
-    drop(stderr_writer); // finish writing to stderr
+    stdout_messages.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let exit_code = summary.exit_code(options.fail_on, options.max_warnings);
+    let printed = print_output(&stdout_messages, &diagnostics, &summary, options, exit_code);
+
+    exit_code_after_output(printed, exit_code)
+}
+
+/// Print the collected stdout messages (like fixed files or diffs), the diagnostics and the summary.
+fn print_output(
+    stdout_messages: &[(PathBuf, Buffer)],
+    diagnostics: &[FileDiagnostic],
+    summary: &Summary,
+    options: &OutputOptions,
+    exit_code: i32,
+) -> io::Result<()> {
+    {
+        let _terminal = TERMINAL_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let stdout_writer = BufferWriter::stdout(stdout_color_choice());
+        for (_, buffer) in stdout_messages {
+            stdout_writer.print(buffer)?;
+        }
 
-    let conclusion_msg = format!(
-        "\nFiles scanned: {}, Errors: {}, Warnings: {}, Helps: {}, Info: {}, Total: {}\n",
-        file_count,
-        error_count,
-        warning_count,
-        help_count,
-        info_count,
-        (error_count + warning_count + help_count + info_count)
-    );
+        if !options.summary_only {
+            let stderr_writer = BufferWriter::stderr(stderr_color_choice());
+            let mut buffer = stderr_writer.buffer();
+            let diagnostics = diagnostics
+                .iter()
+                .filter(|d| !options.diff_only || d.severity == Severity::Error)
+                .collect();
+            write_diagnostics(&mut buffer, diagnostics, options)?;
+            stderr_writer.print(&buffer)?;
+        }
+    }
 
-    if file_count > 0 && (error_count > 0 || warning_count > 0 || help_count > 0) {
-        io::stderr().write_all(conclusion_msg.as_bytes()).unwrap();
-        1 // return exit code 1 if there were errors, warnings or help.
+    let conclusion_msg = summary.message();
+    if options.quiet || options.diff_only {
+        // only diagnostics are printed
+    } else if exit_code != exit_code::SUCCESS || is_stdout_reserved() {
+        io::stderr().write_all(conclusion_msg.as_bytes())?;
     } else {
-        print!("{conclusion_msg}");
-        0
+        let mut stdout = io::stdout();
+        stdout.write_all(conclusion_msg.as_bytes())?;
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// The exit code after printing the output. A closed pipe (like `ludtwig ... | head`) stops the output normally.
+pub fn exit_code_after_output(printed: io::Result<()>, exit_code: i32) -> i32 {
+    match printed {
+        Ok(()) => exit_code,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => exit_code,
+        Err(e) => {
+            // stderr can be broken too, so don't panic like `eprintln!` would
+            let _ = writeln!(io::stderr(), "Error: can't print the output: {e}");
+            exit_code::PROCESSING_ERROR
+        }
+    }
+}
+
+/// Write a unified diff between the original and the fixed source code of a file.
+pub fn write_diff(
+    buffer: &mut impl WriteColor,
+    path: &Path,
+    original: &str,
+    fixed: &str,
+) -> io::Result<()> {
+    let path = path.display();
+    let diff = TextDiff::from_lines(original, fixed);
+
+    buffer.set_color(ColorSpec::new().set_bold(true))?;
+    writeln!(buffer, "--- {path}")?;
+    writeln!(buffer, "+++ {path}")?;
+    buffer.reset()?;
+
+    for hunk in diff.unified_diff().iter_hunks() {
+        buffer.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+        writeln!(buffer, "{}", hunk.header())?;
+        buffer.reset()?;
+
+        for change in hunk.iter_changes() {
+            let (sign, color) = match change.tag() {
+                ChangeTag::Delete => ('-', Some(Color::Red)),
+                ChangeTag::Insert => ('+', Some(Color::Green)),
+                ChangeTag::Equal => (' ', None),
+            };
+            buffer.set_color(ColorSpec::new().set_fg(color))?;
+            write!(buffer, "{sign}{}", change.value().trim_end_matches('\n'))?;
+            buffer.reset()?;
+            writeln!(buffer)?;
+
+            if change.missing_newline() {
+                writeln!(buffer, "\\ No newline at end of file")?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use codespan_reporting::diagnostic::Diagnostic;
+    use codespan_reporting::term::termcolor::{ColorChoice, NoColor};
+    use expect_test::expect;
+    use ludtwig_parser::syntax::untyped::TextRange;
+
+    use crate::check::rule::Severity;
+    use crate::check::SYNTAX_ERROR_CODE;
+    use crate::error::ErrorKind;
+
+    use super::{
+        color_choice, exit_code, exit_code_after_output, write_diagnostics, write_diff, ColorMode,
+        FailOn, FileDiagnostic, GroupBy, OutputOptions, ProcessingEvent, Summary,
+    };
+
+    fn diagnostic(path: &str, start: u32, code: &str, severity: Severity) -> FileDiagnostic {
+        let (files, _) = FileDiagnostic::files(Path::new(path), "0123456789\n".repeat(3).as_str());
+        let diagnostic = match severity {
+            Severity::Error => Diagnostic::error(),
+            Severity::Warning => Diagnostic::warning(),
+            Severity::Help => Diagnostic::help(),
+            Severity::Info => Diagnostic::note(),
+        }
+        .with_code(code)
+        .with_message(format!("{path}@{start}"));
+        FileDiagnostic::new(
+            Path::new(path),
+            TextRange::new(start.into(), start.into()),
+            severity,
+            &files,
+            diagnostic,
+        )
+    }
+
+    fn example_diagnostics() -> Vec<FileDiagnostic> {
+        vec![
+            diagnostic("b.twig", 5, "rule-a", Severity::Help),
+            diagnostic("a.twig", 20, "rule-b", Severity::Error),
+            diagnostic("b.twig", 1, "rule-b", Severity::Warning),
+            diagnostic("a.twig", 3, "rule-a", Severity::Help),
+        ]
+    }
+
+    fn render(diagnostics: &[FileDiagnostic], options: &OutputOptions) -> String {
+        let mut buffer = NoColor::new(vec![]);
+        write_diagnostics(&mut buffer, diagnostics.iter().collect(), options).unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn diagnostics_are_sorted_by_path_and_range() {
+        expect![[r"
+            help[rule-a]: a.twig@3
+
+            error[rule-b]: a.twig@20
+
+            warning[rule-b]: b.twig@1
+
+            help[rule-a]: b.twig@5
+
+        "]]
+        .assert_eq(&render(&example_diagnostics(), &OutputOptions::default()));
+    }
+
+    #[test]
+    fn diagnostics_grouped_by_rule_and_severity() {
+        expect![[r"
+            === rule-a (2) ===
+
+            help[rule-a]: a.twig@3
+
+            help[rule-a]: b.twig@5
+
+            === rule-b (2) ===
+
+            error[rule-b]: a.twig@20
+
+            warning[rule-b]: b.twig@1
+
+        "]]
+        .assert_eq(&render(
+            &example_diagnostics(),
+            &OutputOptions {
+                group_by: Some(GroupBy::Rule),
+                ..OutputOptions::default()
+            },
+        ));
+
+        expect![[r"
+            === Errors (1) ===
+
+            error[rule-b]: a.twig@20
+
+            === Warnings (1) ===
+
+            warning[rule-b]: b.twig@1
+
+            === Helps (2) ===
+
+            help[rule-a]: a.twig@3
+
+            help[rule-a]: b.twig@5
+
+        "]]
+        .assert_eq(&render(
+            &example_diagnostics(),
+            &OutputOptions {
+                group_by: Some(GroupBy::Severity),
+                ..OutputOptions::default()
+            },
+        ));
+    }
+
+    #[test]
+    fn diagnostics_short_and_limited() {
+        expect![[r"
+            a.twig:1:4: help[rule-a] a.twig@3
+            a.twig:2:10: error[rule-b] a.twig@20
+            b.twig:1:2: warning[rule-b] b.twig@1
+            b.twig:1:6: help[rule-a] b.twig@5
+        "]]
+        .assert_eq(&render(
+            &example_diagnostics(),
+            &OutputOptions {
+                short: true,
+                ..OutputOptions::default()
+            },
+        ));
+
+        expect![[r"
+            === rule-a (2) ===
+            a.twig:1:4: help[rule-a] a.twig@3
+            b.twig:1:6: help[rule-a] b.twig@5
+            === rule-b (2) ===
+            a.twig:2:10: error[rule-b] a.twig@20
+            ... and 1 more (limited by --max-diagnostics 3)
+        "]]
+        .assert_eq(&render(
+            &example_diagnostics(),
+            &OutputOptions {
+                group_by: Some(GroupBy::Rule),
+                short: true,
+                max_diagnostics: Some(3),
+                ..OutputOptions::default()
+            },
+        ));
+    }
+
+    #[test]
+    fn color_choice_respects_terminal_and_no_color() {
+        assert_eq!(
+            color_choice(ColorMode::Auto, true, false),
+            ColorChoice::Auto
+        );
+        assert_eq!(
+            color_choice(ColorMode::Auto, false, false),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            color_choice(ColorMode::Auto, true, true),
+            ColorChoice::Never
+        );
+        assert_eq!(
+            color_choice(ColorMode::Always, false, true),
+            ColorChoice::Always
+        );
+        assert_eq!(
+            color_choice(ColorMode::Never, true, false),
+            ColorChoice::Never
+        );
+    }
+
+    #[test]
+    fn summary_table_by_rule_and_file() {
+        let mut summary = Summary::default();
+        summary.record(&ProcessingEvent::FileProcessed);
+        summary.record(&ProcessingEvent::FileProcessed);
+        summary.record(&ProcessingEvent::FileProcessed);
+        summary.record(&ProcessingEvent::FileNeedsFix);
+        for diagnostic in example_diagnostics() {
+            summary.record(&ProcessingEvent::Diagnostic(diagnostic));
+        }
+
+        expect![[r"
+
+            Rule                     Errors  Warnings  Helps  Info  Total
+            rule-a                        0         0      2     0      2
+            rule-b                        1         1      0     0      2
+
+            File                     Errors  Warnings  Helps  Info  Total
+            a.twig                        1         0      1     0      2
+            b.twig                        0         1      1     0      2
+
+            Total (3 files scanned)       1         1      2     0      4
+            Files which would be fixed: 1
+        "]]
+        .assert_eq(&summary.message());
+
+        expect![[r"
+
+                                     Errors  Warnings  Helps  Info  Total
+            Total (0 files scanned)       0         0      0     0      0
+        "]]
+        .assert_eq(&Summary::default().message());
+    }
+
+    #[test]
+    fn exit_code_by_failure_kind_and_policy() {
+        let mut summary = Summary::default();
+        summary.record(&ProcessingEvent::FileProcessed);
+        assert_eq!(summary.exit_code(FailOn::Help, None), exit_code::SUCCESS);
+
+        summary.record(&ProcessingEvent::Diagnostic(diagnostic(
+            "a.twig",
+            1,
+            "rule-a",
+            Severity::Warning,
+        )));
+        summary.record(&ProcessingEvent::Diagnostic(diagnostic(
+            "a.twig",
+            2,
+            "rule-b",
+            Severity::Info,
+        )));
+        assert_eq!(
+            summary.exit_code(FailOn::Help, None),
+            exit_code::LINT_FAILURE
+        );
+        assert_eq!(summary.exit_code(FailOn::Error, None), exit_code::SUCCESS);
+        assert_eq!(summary.exit_code(FailOn::Help, Some(1)), exit_code::SUCCESS);
+        assert_eq!(
+            summary.exit_code(FailOn::Error, Some(0)),
+            exit_code::LINT_FAILURE
+        );
+        assert_eq!(
+            summary.exit_code(FailOn::Info, Some(1)),
+            exit_code::LINT_FAILURE
+        );
+        assert_eq!(summary.exit_code(FailOn::Never, None), exit_code::SUCCESS);
+
+        summary.record(&ProcessingEvent::Diagnostic(diagnostic(
+            "a.twig",
+            3,
+            SYNTAX_ERROR_CODE,
+            Severity::Error,
+        )));
+        assert_eq!(
+            summary.exit_code(FailOn::Error, Some(5)),
+            exit_code::PARSE_ERROR
+        );
+        // syntax errors fail the run regardless of the policy
+        assert_eq!(
+            summary.exit_code(FailOn::Never, None),
+            exit_code::PARSE_ERROR
+        );
+
+        summary.record(&ProcessingEvent::Error(ErrorKind::Processing));
+        assert_eq!(
+            summary.exit_code(FailOn::Never, None),
+            exit_code::PROCESSING_ERROR
+        );
+        summary.record(&ProcessingEvent::Error(ErrorKind::Configuration));
+        assert_eq!(
+            summary.exit_code(FailOn::Help, None),
+            exit_code::CONFIG_ERROR
+        );
+    }
+
+    #[test]
+    fn write_diff_unified() {
+        let original = "<div>\n<span class='a'></span>\n</div>\n<p>\na\nb\nc\nd\ne\n</p>";
+        let fixed = "<div>\n    <span class=\"a\"></span>\n</div>\n<p>\na\nb\nc\nd\ne\n</p>\n";
+
+        let mut buffer = NoColor::new(vec![]);
+        write_diff(&mut buffer, Path::new("a.html.twig"), original, fixed).unwrap();
+        expect![[r#"
+            --- a.html.twig
+            +++ a.html.twig
+            @@ -1,5 +1,5 @@
+             <div>
+            -<span class='a'></span>
+            +    <span class="a"></span>
+             </div>
+             <p>
+             a
+            @@ -7,4 +7,4 @@
+             c
+             d
+             e
+            -</p>
+            \ No newline at end of file
+            +</p>
+        "#]]
+        .assert_eq(&String::from_utf8(buffer.into_inner()).unwrap());
+    }
+
+    #[test]
+    fn closed_pipes_stop_the_output_normally() {
+        let error = |kind: io::ErrorKind| Err(io::Error::new(kind, "test"));
+
+        assert_eq!(exit_code_after_output(Ok(()), exit_code::LINT_FAILURE), 1);
+        assert_eq!(
+            exit_code_after_output(error(io::ErrorKind::BrokenPipe), exit_code::SUCCESS),
+            exit_code::SUCCESS
+        );
+        assert_eq!(
+            exit_code_after_output(error(io::ErrorKind::PermissionDenied), exit_code::SUCCESS),
+            exit_code::PROCESSING_ERROR
+        );
     }
 }
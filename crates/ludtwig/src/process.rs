@@ -1,21 +1,52 @@
-use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
 
-use codespan_reporting::term::termcolor::{BufferWriter, ColorChoice};
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::term::termcolor::BufferWriter;
 
-use ludtwig_parser::syntax::untyped::SyntaxNode;
+use ludtwig_parser::syntax::untyped::{SyntaxNode, TextRange};
 use ludtwig_parser::ParseError;
 
-use crate::check::rule::{CheckResult, CheckSuggestion, Rule};
+use crate::cache::ResultCache;
+use crate::check::rule::{Applicability, CheckResult, CheckSuggestion, Rule, Severity, TextEdit};
 use crate::check::rules::get_file_active_rule_definitions;
-use crate::check::{get_rule_context_suggestions, produce_diagnostics, run_rules};
-use crate::error::FileProcessingError;
-use crate::output::ProcessingEvent;
+use crate::check::{
+    get_rule_context_suggestions, produce_diagnostics, run_rules, run_rules_after_fix,
+    send_diagnostics, FileDependencies,
+};
+use crate::config::ActiveConfig;
+use crate::error::{AppliedFix, FileProcessingError};
+use crate::git::ChangedLines;
+use crate::output::{info, stdout_color_choice, write_diff, FileDiagnostic, ProcessingEvent};
 use crate::CliContext;
 
+pub mod interactive;
+pub mod verify;
+
+use interactive::FileReview;
+use verify::structural_fingerprint;
+
+/// How many of the last fixing iterations are reported if the fixing doesn't converge.
+const FIGHTING_RULES_ITERATIONS: usize = 3;
+
+/// File path which is used for source code from stdin if no `--stdin-filename` is given.
+pub const STDIN_FILENAME: &str = "<stdin>";
+
+/// Where the source code of a file comes from, which also decides where fixes are written to.
+pub enum SourceOrigin<'a> {
+    /// Read from the file path and fixes are written back to it
+    File,
+    /// Read from stdin and the (fixed) source code is written to the output (stdout)
+    Stdin(&'a mut dyn Write),
+}
+
 /// The context for a single file.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FileContext {
     pub cli_context: CliContext,
 
@@ -29,12 +60,57 @@ pub struct FileContext {
 
     pub parse_errors: Vec<ParseError>,
 
+    /// The config which applies to this file (nearest config file in its parent directories).
+    pub config: Arc<ActiveConfig>,
+
     /// active rules for this specific file (may contain less than global config definitions).
     /// these are defined after processing ludtwig-ignore-file directives
-    pub file_rule_definitions: Vec<&'static dyn Rule>,
+    pub file_rule_definitions: Vec<Arc<dyn Rule>>,
+
+    /// Other files the rules used while checking this file.
+    pub dependencies: FileDependencies,
+
+    /// Only results on these lines are reported (`--changed-lines-only`),
+    /// they move with the line breaks the applied suggestions add or remove.
+    pub changed_lines: Option<ChangedLines>,
 }
 
 impl FileContext {
+    /// Parse the source code and find the rules which are active for it.
+    pub fn parse(
+        cli_context: CliContext,
+        file_path: PathBuf,
+        source_code: String,
+        config: Arc<ActiveConfig>,
+    ) -> Self {
+        let (parse, parse_timings) = ludtwig_parser::parse_with_timings(&source_code);
+        if let Some(timings) = &cli_context.data.timings {
+            timings.record_parse(parse_timings);
+        }
+        let tree_root = SyntaxNode::new_root(parse.green_node);
+        let file_rule_definitions =
+            get_file_active_rule_definitions(&tree_root, &config.rule_definitions);
+        let data = &cli_context.data;
+        let changed_lines = data
+            .changed_files
+            .as_ref()
+            .filter(|_| data.changed_lines_only)
+            .and_then(|changed_files| changed_files.lines(&file_path))
+            .cloned();
+
+        Self {
+            cli_context,
+            file_path,
+            source_code,
+            tree_root,
+            parse_errors: parse.errors,
+            config,
+            file_rule_definitions,
+            dependencies: FileDependencies::default(),
+            changed_lines,
+        }
+    }
+
     pub fn send_processing_output(&self, event: ProcessingEvent) {
         self.cli_context.send_processing_output(event);
     }
@@ -45,55 +121,132 @@ pub fn process_file(path: PathBuf, cli_context: CliContext) -> Result<(), FilePr
     // notify the output about this file (to increase the processed file counter)
     cli_context.send_processing_output(ProcessingEvent::FileProcessed);
 
+    let start = Instant::now();
+    let data = Arc::clone(&cli_context.data);
     let file_content = match fs::read_to_string(&path) {
         Ok(content) => content,
         Err(e) => {
             return Err(FileProcessingError::FileRead { path, io_error: e });
         }
     };
+    if let Some(timings) = &data.timings {
+        timings.record_read(start.elapsed());
+    }
 
-    run_analysis(path, file_content, cli_context)
+    let result = run_analysis(path.clone(), file_content, SourceOrigin::File, cli_context);
+    if let Some(timings) = &data.timings {
+        timings.record_file(path, start.elapsed());
+    }
+    result
+}
+
+/// Process the source code from stdin, which is associated with the given (possibly non-existing) path.
+pub fn process_stdin(path: PathBuf, cli_context: CliContext) -> Result<(), FileProcessingError> {
+    process_source(
+        path,
+        &mut std::io::stdin(),
+        &mut std::io::stdout(),
+        cli_context,
+    )
+}
+
+/// Process the source code of the input like stdin: with `--fix` the whole (fixed) source code is written
+/// to the output. If that fails, the unchanged source code is written, so piping the output into the file
+/// never loses the template.
+pub(crate) fn process_source(
+    path: PathBuf,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    cli_context: CliContext,
+) -> Result<(), FileProcessingError> {
+    cli_context.send_processing_output(ProcessingEvent::FileProcessed);
+
+    let start = Instant::now();
+    let mut content = String::new();
+    if let Err(e) = input.read_to_string(&mut content) {
+        return Err(FileProcessingError::StdinRead { io_error: e });
+    }
+    if let Some(timings) = &cli_context.data.timings {
+        timings.record_read(start.elapsed());
+    }
+
+    let original = cli_context.data.fix.then(|| content.clone());
+    let result = run_analysis(path, content, SourceOrigin::Stdin(output), cli_context);
+    if let (Err(e), Some(original)) = (&result, original) {
+        if !matches!(e, FileProcessingError::StdoutWrite { .. }) {
+            if let Err(io_error) = output.write_all(original.as_bytes()) {
+                return Err(FileProcessingError::StdoutWrite { io_error });
+            }
+        }
+    }
+
+    result
 }
 
 fn run_analysis(
     path: PathBuf,
     original_file_content: String,
+    origin: SourceOrigin,
     cli_context: CliContext,
 ) -> Result<(), FileProcessingError> {
-    let parse = ludtwig_parser::parse(&original_file_content);
-    let root = SyntaxNode::new_root(parse.green_node);
+    let config = match cli_context.data.config_resolver.resolve(&path) {
+        Ok(c) => c,
+        Err(error) => return Err(FileProcessingError::Configuration { path, error }),
+    };
 
-    let file_rule_definitions =
-        get_file_active_rule_definitions(&root, &cli_context.data.rule_definitions);
+    let apply_suggestions = cli_context.data.fix || cli_context.data.interactive.is_some();
+    let preview_suggestions = cli_context.data.check || cli_context.data.diff;
+
+    // the syntax tree is needed for --inspect, the cache is only used for actual files
+    // and it doesn't know which lines changed
+    let use_cache = matches!(origin, SourceOrigin::File)
+        && !cli_context.data.inspect
+        && !cli_context.data.changed_lines_only;
+    if let Some(cache) = cli_context.data.cache.as_ref().filter(|_| use_cache) {
+        if report_cached_results(cache, &path, &original_file_content, &config, &cli_context) {
+            return Ok(());
+        }
+    }
 
-    let apply_suggestions = cli_context.data.fix;
-    let file_context = FileContext {
-        cli_context,
-        file_path: path,
-        source_code: original_file_content,
-        tree_root: root,
-        parse_errors: parse.errors,
-        file_rule_definitions,
-    };
+    let file_context = FileContext::parse(cli_context, path, original_file_content, config);
 
     // run all the rules
     let rule_result_context = run_rules(&file_context);
 
     // apply suggestions if needed
     let (file_context, rule_result_context) = if apply_suggestions {
+        let data = Arc::clone(&file_context.cli_context.data);
         let (file_context, rule_result_context, dirty, iterations) =
-            iteratively_apply_suggestions(file_context, rule_result_context)?;
-        if dirty {
-            match fs::write(&file_context.file_path, &file_context.source_code) {
-                Ok(()) => {}
-                Err(e) => {
-                    return Err(FileProcessingError::FileWrite {
-                        path: file_context.file_path,
-                        io_error: e,
-                    })
-                }
+            if let Some(session) = &data.interactive {
+                let mut review = FileReview::terminal(session);
+                iteratively_apply_suggestions_with(
+                    file_context,
+                    rule_result_context,
+                    |file_context, suggestions| review.select(file_context, suggestions),
+                )?
+            } else {
+                iteratively_apply_suggestions(file_context, rule_result_context)?
             };
-            println!(
+        let write_start = Instant::now();
+        if let SourceOrigin::Stdin(output) = origin {
+            // always write the whole source code, so it can be piped into the original file
+            if let Err(e) = output.write_all(file_context.source_code.as_bytes()) {
+                return Err(FileProcessingError::StdoutWrite { io_error: e });
+            }
+        } else if dirty {
+            if let Err(e) = fs::write(&file_context.file_path, &file_context.source_code) {
+                return Err(FileProcessingError::FileWrite {
+                    path: file_context.file_path,
+                    io_error: e,
+                });
+            }
+        }
+        if let Some(timings) = &data.timings {
+            timings.record_write(write_start.elapsed());
+        }
+
+        if dirty {
+            info!(
                 "fixed {:?} in {} iterations",
                 &file_context.file_path, iterations
             );
@@ -101,14 +254,102 @@ fn run_analysis(
 
         (file_context, rule_result_context)
     } else {
+        if preview_suggestions {
+            report_suggestion_changes(&file_context, &rule_result_context)?;
+        }
+
         (file_context, rule_result_context)
     };
 
-    // send processing events for rule check results + parser errors and output them to the terminal
-    let writer = BufferWriter::stderr(ColorChoice::Always);
-    let mut buffer = writer.buffer();
-    produce_diagnostics(&file_context, rule_result_context, &mut buffer);
-    file_context.send_processing_output(ProcessingEvent::OutputStderrMessage(buffer));
+    if let Some(cache) = file_context
+        .cli_context
+        .data
+        .cache
+        .as_ref()
+        .filter(|_| use_cache)
+    {
+        // store the results of the (fixed) source code, which is the file content in the next run.
+        // a cache which can't be written only makes the next run slower
+        let _ = cache.put(
+            &file_context.file_path,
+            ResultCache::key(&file_context.config, &file_context.source_code),
+            file_context.dependencies.paths(),
+            &file_context.parse_errors,
+            rule_result_context.clone(),
+        );
+    }
+
+    file_context.send_processing_output(ProcessingEvent::Dependencies(
+        file_context.dependencies.paths(),
+    ));
+    // send processing events for rule check results + parser errors to the output
+    produce_diagnostics(&file_context, rule_result_context);
+
+    Ok(())
+}
+
+/// Report the results of an unchanged file from the cache (`--cache`).
+/// Returns false if the file still needs to be processed.
+fn report_cached_results(
+    cache: &ResultCache,
+    path: &Path,
+    source_code: &str,
+    config: &ActiveConfig,
+    cli_context: &CliContext,
+) -> bool {
+    let Some(entry) = cache.get(path, &ResultCache::key(config, source_code)) else {
+        return false;
+    };
+    let Some(parse_errors) = entry.parse_errors() else {
+        return false;
+    };
+
+    // results with suggestions still need the whole processing to apply or preview them
+    let data = &cli_context.data;
+    if (data.fix || data.interactive.is_some() || data.check || data.diff)
+        && !get_rule_context_suggestions(&entry.results, data).is_empty()
+    {
+        return false;
+    }
+
+    cli_context.send_processing_output(ProcessingEvent::Dependencies(entry.dependencies()));
+    send_diagnostics(cli_context, path, source_code, &parse_errors, entry.results);
+    true
+}
+
+/// Apply the suggestions only in memory and report the changes they would make (`--check` and `--diff`).
+/// The diagnostics are still produced for the unchanged source code.
+fn report_suggestion_changes(
+    file_context: &FileContext,
+    check_results: &[CheckResult],
+) -> Result<(), FileProcessingError> {
+    let (fixed_context, _, _, _) =
+        iteratively_apply_suggestions(file_context.clone(), check_results.to_vec())?;
+    if fixed_context.source_code == file_context.source_code {
+        return Ok(());
+    }
+
+    let data = &file_context.cli_context.data;
+    if data.check {
+        info!("would fix {}", file_context.file_path.display());
+        file_context.send_processing_output(ProcessingEvent::FileNeedsFix);
+    }
+
+    if data.diff {
+        let mut buffer = BufferWriter::stdout(stdout_color_choice()).buffer();
+        if let Err(e) = write_diff(
+            &mut buffer,
+            &file_context.file_path,
+            &file_context.source_code,
+            &fixed_context.source_code,
+        ) {
+            return Err(FileProcessingError::StdoutWrite { io_error: e });
+        }
+        file_context.send_processing_output(ProcessingEvent::OutputStdoutMessage(
+            file_context.file_path.clone(),
+            buffer,
+        ));
+    }
 
     Ok(())
 }
@@ -117,65 +358,126 @@ pub fn iteratively_apply_suggestions(
     file_context: FileContext,
     check_results: Vec<CheckResult>,
 ) -> Result<(FileContext, Vec<CheckResult>, bool, usize), FileProcessingError> {
+    iteratively_apply_suggestions_with(file_context, check_results, |_, suggestions| suggestions)
+}
+
+/// Like [`iteratively_apply_suggestions`] but `select` decides which of the (non overlapping) suggestions
+/// of each iteration are applied. Stops when no suggestion is selected.
+pub fn iteratively_apply_suggestions_with<F>(
+    file_context: FileContext,
+    check_results: Vec<CheckResult>,
+    mut select: F,
+) -> Result<(FileContext, Vec<CheckResult>, bool, usize), FileProcessingError>
+where
+    F: for<'a> FnMut(
+        &FileContext,
+        Vec<(&'a str, &'a CheckSuggestion)>,
+    ) -> Vec<(&'a str, &'a CheckSuggestion)>,
+{
+    let start = Instant::now();
     let mut current_results = (file_context, check_results, false, 0);
+    // the fixes of the last iterations, to report which rules are fighting if the fixing doesn't converge
+    let mut applied_fixes: VecDeque<Vec<AppliedFix>> = VecDeque::new();
 
     // try at maximum 10 parsing iterations
     for i in 0..10 {
         if i >= 9 {
-            return Err(FileProcessingError::MaxApplyIteration);
+            return Err(FileProcessingError::MaxApplyIteration {
+                path: current_results.0.file_path,
+                last_iterations: applied_fixes.into(),
+            });
         }
 
-        let mut suggestions = get_rule_context_suggestions(&current_results.1);
+        let suggestions =
+            get_rule_context_suggestions(&current_results.1, &current_results.0.cli_context.data);
         if suggestions.is_empty() {
             break;
         }
 
-        // sort by syntax range
-        suggestions
-            .sort_by(|(_, sug_a), (_, sug_b)| sug_a.syntax_range.ordering(sug_b.syntax_range));
-
-        // filter out overlapping suggestions
-        let mut overlapping_rules = HashSet::new();
-        for ((rule_a, sug_a), (rule_b, sug_b)) in suggestions.iter().zip(suggestions.iter().skip(1))
-        {
-            if sug_a.syntax_range.ordering(sug_b.syntax_range).is_eq() {
-                if rule_a == rule_b {
-                    return Err(FileProcessingError::OverlappingSuggestionInSingleRule {
-                        rule_name: (*rule_a).to_string(),
-                    });
-                }
-
-                overlapping_rules.insert(*rule_b);
-            }
+        let mut suggestions = remove_conflicting_suggestions(suggestions)?;
+        // verify the safe fixes on their own, the unsafe ones are applied in a later iteration
+        let is_safe =
+            |(_, sug): &(&str, &CheckSuggestion)| sug.applicability == Applicability::Safe;
+        if current_results.0.cli_context.data.verify_fixes && suggestions.iter().any(is_safe) {
+            suggestions.retain(is_safe);
+        }
+        let suggestions = select(&current_results.0, suggestions);
+        if suggestions.is_empty() {
+            break;
         }
-        let suggestions = suggestions
-            .into_iter()
-            .filter_map(|(rule, suggestion)| {
-                if overlapping_rules.contains(&rule) {
-                    return None;
-                }
-
-                Some(suggestion)
-            })
-            .collect();
 
         // transform source code according to non overlapping suggestions
-        current_results.2 = true; // set dirty flag
-        let source_code = apply_suggestions_to_text(suggestions, current_results.0.source_code);
+        let edits = sorted_edits(suggestions.iter().map(|(_, sug)| *sug));
+        let source_code = apply_suggestions_to_text(&edits, current_results.0.source_code.clone());
+        let changed_lines = current_results.0.changed_lines.as_ref().map(|lines| {
+            let edits: Vec<(Range<usize>, &str)> = edits
+                .iter()
+                .map(|edit| (edit.syntax_range.into(), edit.replace_with.as_str()))
+                .collect();
+            lines.after_edits(&current_results.0.source_code, &edits)
+        });
 
         // Parse the new source code again
         let new_parse = ludtwig_parser::parse(&source_code);
         let tree_root = SyntaxNode::new_root(new_parse.green_node);
 
+        // a fix should never make the template worse, so stop with the previous iteration
+        if new_parse.errors.len() > current_results.0.parse_errors.len() {
+            report_rejected_fixes(
+                &current_results.0,
+                &suggestions,
+                new_parse.errors.len() - current_results.0.parse_errors.len(),
+            );
+            break;
+        }
+
+        // unsafe fixes are allowed to change the structure
+        if current_results.0.cli_context.data.verify_fixes
+            && suggestions
+                .iter()
+                .all(|(_, sug)| sug.applicability == Applicability::Safe)
+            && structural_fingerprint(&current_results.0.tree_root)
+                != structural_fingerprint(&tree_root)
+        {
+            let mut rule_names: Vec<String> = suggestions
+                .iter()
+                .map(|(rule, _)| (*rule).to_string())
+                .collect();
+            rule_names.sort_unstable();
+            rule_names.dedup();
+            return Err(FileProcessingError::FixChangedStructure {
+                path: current_results.0.file_path,
+                rule_names,
+            });
+        }
+
+        if applied_fixes.len() == FIGHTING_RULES_ITERATIONS {
+            applied_fixes.pop_front();
+        }
+        applied_fixes.push_back(
+            suggestions
+                .iter()
+                .map(|(rule_name, sug)| {
+                    AppliedFix::new(
+                        rule_name,
+                        &current_results.0.source_code,
+                        sug.syntax_range().start(),
+                    )
+                })
+                .collect(),
+        );
+
+        current_results.2 = true; // set dirty flag
         let file_context = FileContext {
             source_code,
             tree_root,
             parse_errors: new_parse.errors,
+            changed_lines,
             ..current_results.0
         };
 
         // Run all rules again
-        let rule_result_context = run_rules(&file_context);
+        let rule_result_context = run_rules_after_fix(&file_context);
         current_results = (
             file_context,
             rule_result_context,
@@ -184,19 +486,446 @@ pub fn iteratively_apply_suggestions(
         );
     }
 
+    if let Some(timings) = &current_results.0.cli_context.data.timings {
+        timings.record_fix(start.elapsed(), current_results.3);
+    }
     Ok(current_results)
 }
 
-fn apply_suggestions_to_text(
-    suggestions: Vec<&CheckSuggestion>,
-    mut source_code: String,
-) -> String {
-    suggestions.into_iter().rev().for_each(|suggestion| {
-        let start: usize = suggestion.syntax_range.start().into();
-        let end: usize = suggestion.syntax_range.end().into();
+/// Sort the suggestions by their range and remove the ones which overlap with a previous suggestion.
+/// The removed suggestions will be suggested again (if still needed) in the next iteration.
+fn remove_conflicting_suggestions<'a>(
+    mut suggestions: Vec<(&'a str, &'a CheckSuggestion)>,
+) -> Result<Vec<(&'a str, &'a CheckSuggestion)>, FileProcessingError> {
+    suggestions.sort_by_key(|(_, sug)| (sug.syntax_range().start(), sug.syntax_range().end()));
+
+    let mut accepted: Vec<(&'a str, &'a CheckSuggestion)> = Vec::with_capacity(suggestions.len());
+    for (rule, sug) in suggestions {
+        // the edits of a suggestion are not necessarily sorted
+        let edits = sorted_edits([sug]);
+        if edits
+            .iter()
+            .zip(edits.iter().skip(1))
+            .any(|(a, b)| ranges_overlap(a.syntax_range, b.syntax_range))
+        {
+            return Err(FileProcessingError::OverlappingSuggestionInSingleRule {
+                rule_name: rule.to_string(),
+            });
+        }
+
+        let conflict = accepted
+            .iter()
+            .rev()
+            .find(|(_, other)| suggestions_overlap(other, sug));
+
+        match conflict {
+            None => accepted.push((rule, sug)),
+            // the same edits suggested twice
+            Some((_, other)) if other.edits == sug.edits => {}
+            Some((other_rule, _)) if *other_rule == rule => {
+                return Err(FileProcessingError::OverlappingSuggestionInSingleRule {
+                    rule_name: rule.to_string(),
+                });
+            }
+            Some(_) => {}
+        }
+    }
+
+    Ok(accepted)
+}
+
+fn suggestions_overlap(a: &CheckSuggestion, b: &CheckSuggestion) -> bool {
+    a.edits.iter().any(|edit_a| {
+        b.edits
+            .iter()
+            .any(|edit_b| ranges_overlap(edit_a.syntax_range, edit_b.syntax_range))
+    })
+}
+
+/// Two edits conflict if they change the same text or insert text at the same position.
+fn ranges_overlap(a: TextRange, b: TextRange) -> bool {
+    if a.is_empty() && b.is_empty() {
+        return a.start() == b.start();
+    }
+
+    a.start() < b.end() && b.start() < a.end()
+}
+
+/// Report that the suggestions of an iteration were not applied, because they would introduce syntax errors.
+fn report_rejected_fixes(
+    file_context: &FileContext,
+    suggestions: &[(&str, &CheckSuggestion)],
+    new_error_count: usize,
+) {
+    let (files, file_id) =
+        FileDiagnostic::files(&file_context.file_path, &file_context.source_code);
+    let mut rule_names: Vec<&str> = suggestions.iter().map(|(rule, _)| *rule).collect();
+    rule_names.sort_unstable();
+    rule_names.dedup();
+
+    let diagnostic = Diagnostic::error()
+        .with_code("FixRejected")
+        .with_message(format!(
+            "fixes of the rules {} were not applied, because they would introduce {new_error_count} new syntax error(s)",
+            rule_names.join(", ")
+        ))
+        .with_labels(
+            suggestions
+                .iter()
+                .flat_map(|(rule, sug)| {
+                    sug.edits.iter().map(move |edit| {
+                        Label::secondary(file_id, edit.syntax_range)
+                            .with_message(format!("fix of {rule}: {}", edit.replace_with))
+                    })
+                })
+                .collect(),
+        );
+
+    file_context.send_processing_output(ProcessingEvent::Diagnostic(FileDiagnostic::new(
+        &file_context.file_path,
+        suggestions
+            .iter()
+            .map(|(_, sug)| sug.syntax_range())
+            .reduce(TextRange::cover)
+            .unwrap_or_default(),
+        Severity::Error,
+        &files,
+        diagnostic,
+    )));
+}
 
-        source_code.replace_range(start..end, &suggestion.replace_with);
+/// The edits of all suggestions, sorted by their range.
+fn sorted_edits<'a>(
+    suggestions: impl IntoIterator<Item = &'a CheckSuggestion>,
+) -> Vec<&'a TextEdit> {
+    let mut edits: Vec<&TextEdit> = suggestions.into_iter().flat_map(|s| &s.edits).collect();
+    edits.sort_by_key(|e| (e.syntax_range.start(), e.syntax_range.end()));
+    edits
+}
+
+/// Apply the (sorted and not overlapping) edits to the source code.
+fn apply_suggestions_to_text(edits: &[&TextEdit], mut source_code: String) -> String {
+    edits.iter().rev().for_each(|edit| {
+        let start: usize = edit.syntax_range.start().into();
+        let end: usize = edit.syntax_range.end().into();
+
+        source_code.replace_range(start..end, &edit.replace_with);
     });
 
     source_code
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::{mpsc, Arc};
+
+    use expect_test::expect;
+    use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+    use crate::check::rule::{Applicability, CheckSuggestion, TextEdit};
+    use crate::check::run_rules;
+    use crate::config::{
+        ActiveConfig, Config, ConfigResolver, CustomRule, CustomRuleSeverity, CONFIG_FILE_NAME,
+        DEFAULT_CONFIG_PATH,
+    };
+    use crate::error::{AppliedFix, FileProcessingError};
+    use crate::git::ChangedLines;
+    use crate::linter::Diagnostic;
+    use crate::output::ProcessingEvent;
+    use crate::test_util::temp_dir;
+    use crate::{CliContext, CliSharedData};
+
+    use super::{
+        apply_suggestions_to_text, iteratively_apply_suggestions, process_source,
+        remove_conflicting_suggestions, sorted_edits, FileContext,
+    };
+
+    const UNINDENTED: &str = "<div>\n<span></span>\n</div>\n";
+
+    /// The config is used for all files, unless `discover` looks for the nearest config file.
+    fn resolver(config: Config, discover: bool) -> ConfigResolver {
+        ConfigResolver::new(ActiveConfig::new(config, None).unwrap(), discover, false)
+    }
+
+    fn cli_context(
+        config_resolver: ConfigResolver,
+        fix: bool,
+    ) -> (CliContext, mpsc::Receiver<ProcessingEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let cli_context = CliContext {
+            output_tx: tx,
+            data: Arc::new(CliSharedData {
+                fix,
+                check: false,
+                diff: false,
+                fix_only: vec![],
+                fix_unsafe: false,
+                verify_fixes: false,
+                interactive: None,
+                inspect: false,
+                cache: None,
+                changed_files: None,
+                changed_lines_only: false,
+                timings: None,
+                config_resolver,
+            }),
+        };
+        (cli_context, rx)
+    }
+
+    /// Process the source code like `--stdin` (with `--fix`) and return the result, what was written to
+    /// stdout and the codes of the diagnostics.
+    fn stdin(
+        config_resolver: ConfigResolver,
+        filename: &Path,
+        fix: bool,
+        source_code: &str,
+    ) -> (Result<(), FileProcessingError>, String, Vec<String>) {
+        let (cli_context, rx) = cli_context(config_resolver, fix);
+        let mut output = vec![];
+        let result = process_source(
+            filename.to_owned(),
+            &mut source_code.as_bytes(),
+            &mut output,
+            cli_context,
+        );
+        let codes = rx
+            .iter()
+            .filter_map(|event| match event {
+                ProcessingEvent::Diagnostic(diagnostic) => Some(Diagnostic::from(&diagnostic).code),
+                _ => None,
+            })
+            .collect();
+
+        (result, String::from_utf8(output).unwrap(), codes)
+    }
+
+    #[test]
+    fn stdin_check_reports_without_output() {
+        let config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        let (result, output, codes) = stdin(
+            resolver(config, false),
+            Path::new("<stdin>"),
+            false,
+            UNINDENTED,
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output, "");
+        assert_eq!(codes, vec!["indentation".to_string()]);
+    }
+
+    #[test]
+    fn stdin_fix_writes_the_fixed_source() {
+        let config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        let (result, output, codes) = stdin(
+            resolver(config, false),
+            Path::new("<stdin>"),
+            true,
+            UNINDENTED,
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output, "<div>\n    <span></span>\n</div>\n");
+        assert!(codes.is_empty());
+    }
+
+    #[test]
+    fn stdin_failed_fix_writes_the_original_source() {
+        let mut config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        config.general.active_rules = vec![];
+        // never converges, because every replacement is matched again
+        config.custom_rules = vec![CustomRule {
+            name: "grow".to_string(),
+            query: "TWIG_LITERAL_NAME @name".to_string(),
+            message: "grow".to_string(),
+            severity: CustomRuleSeverity::Warning,
+            replacement: Some("$name$name".to_string()),
+        }];
+        let source_code = "{{ a }}\n";
+        let (result, output, _) = stdin(
+            resolver(config, false),
+            Path::new("<stdin>"),
+            true,
+            source_code,
+        );
+
+        assert!(matches!(
+            result,
+            Err(FileProcessingError::MaxApplyIteration { .. })
+        ));
+        assert_eq!(output, source_code);
+    }
+
+    #[test]
+    fn stdin_filename_finds_the_nearest_config() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[format]\nindentation-count = 2\n",
+        )
+        .unwrap();
+
+        let config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        let (result, output, _) = stdin(
+            resolver(config, true),
+            &dir.join("views/not-existing.html.twig"),
+            true,
+            UNINDENTED,
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output, "<div>\n  <span></span>\n</div>\n");
+    }
+
+    fn suggestion(start: u32, end: u32, replace_with: &str) -> CheckSuggestion {
+        CheckSuggestion {
+            edits: vec![TextEdit {
+                syntax_range: TextRange::new(TextSize::from(start), TextSize::from(end)),
+                replace_with: replace_with.to_string(),
+            }],
+            message: String::new(),
+            applicability: Applicability::Safe,
+        }
+    }
+
+    #[test]
+    fn conflicting_suggestions_are_removed() {
+        let outer = suggestion(0, 10, "a");
+        let inside = suggestion(5, 6, "b");
+        let partial = suggestion(8, 12, "c");
+        let insert_behind = suggestion(10, 10, "d");
+        let insert_same_position = suggestion(10, 10, "e");
+        let later = suggestion(12, 14, "f");
+        let same_edit = suggestion(12, 14, "f");
+
+        let remaining = remove_conflicting_suggestions(vec![
+            ("rule-f", &later),
+            ("rule-e", &insert_same_position),
+            ("rule-c", &partial),
+            ("rule-a", &outer),
+            ("rule-b", &inside),
+            ("rule-d", &insert_behind),
+            ("rule-f2", &same_edit),
+        ])
+        .unwrap();
+        let rules: Vec<_> = remaining.iter().map(|(rule, _)| *rule).collect();
+        assert_eq!(rules, vec!["rule-a", "rule-e", "rule-f"]);
+    }
+
+    #[test]
+    fn multi_edit_suggestions_are_applied_all_or_nothing() {
+        let quotes = CheckSuggestion {
+            edits: vec![
+                TextEdit {
+                    syntax_range: TextRange::new(TextSize::from(2), TextSize::from(3)),
+                    replace_with: "\"".to_string(),
+                },
+                TextEdit {
+                    syntax_range: TextRange::new(TextSize::from(8), TextSize::from(9)),
+                    replace_with: "\"".to_string(),
+                },
+            ],
+            ..suggestion(0, 0, "")
+        };
+        let conflicting = suggestion(7, 9, "x");
+        let independent = suggestion(4, 5, "y");
+
+        let remaining = remove_conflicting_suggestions(vec![
+            ("rule-b", &conflicting),
+            ("rule-a", &quotes),
+            ("rule-c", &independent),
+        ])
+        .unwrap();
+        let rules: Vec<_> = remaining.iter().map(|(rule, _)| *rule).collect();
+        assert_eq!(rules, vec!["rule-a", "rule-c"]);
+
+        let edits = sorted_edits(remaining.into_iter().map(|(_, sug)| sug));
+        let source_code = apply_suggestions_to_text(&edits, "a='bcdef'".to_string());
+        expect![[r#"a="bydef""#]].assert_eq(&source_code);
+    }
+
+    #[test]
+    fn conflicting_suggestions_in_same_rule_are_an_error() {
+        let first = suggestion(0, 10, "a");
+        let second = suggestion(5, 12, "b");
+
+        assert!(remove_conflicting_suggestions(vec![("rule", &first), ("rule", &second)]).is_err());
+    }
+
+    #[test]
+    fn overlapping_edits_of_one_suggestion_are_an_error() {
+        let edit = |start: u32, end: u32| TextEdit {
+            syntax_range: TextRange::new(TextSize::from(start), TextSize::from(end)),
+            replace_with: String::new(),
+        };
+        // the first and last edit overlap
+        let unsorted = CheckSuggestion {
+            edits: vec![edit(4, 8), edit(0, 2), edit(6, 10)],
+            ..suggestion(0, 0, "")
+        };
+
+        assert!(matches!(
+            remove_conflicting_suggestions(vec![("rule", &unsorted)]),
+            Err(FileProcessingError::OverlappingSuggestionInSingleRule { .. })
+        ));
+    }
+
+    #[test]
+    fn changed_lines_move_with_applied_fixes() {
+        let mut config = Config::new(DEFAULT_CONFIG_PATH).unwrap();
+        config.general.active_rules = vec![];
+        config.custom_rules = vec![
+            CustomRule {
+                name: "split".to_string(),
+                query: r#"HTML_TEXT[text="split"]"#.to_string(),
+                message: "split".to_string(),
+                severity: CustomRuleSeverity::Warning,
+                replacement: Some("one\ntwo".to_string()),
+            },
+            CustomRule {
+                name: "twig-no-raw".to_string(),
+                query: r#"TWIG_FILTER:has(> TWIG_OPERAND:first-child @value):has(> TWIG_OPERAND:last-child[text="raw"])"#.to_string(),
+                message: "Don't print $value without escaping".to_string(),
+                severity: CustomRuleSeverity::Warning,
+                replacement: Some("$value".to_string()),
+            },
+        ];
+        let config_resolver = resolver(config, false);
+        let config = config_resolver.fallback();
+        let (cli_context, _rx) = cli_context(config_resolver, true);
+        let mut file_context = FileContext::parse(
+            cli_context,
+            PathBuf::from("a.html.twig"),
+            "<p>split</p>\n{{ b|raw }}\n{{ a|raw|raw }}\n".to_string(),
+            config,
+        );
+        file_context.changed_lines = Some(ChangedLines::Ranges(vec![1..=1, 3..=3]));
+
+        // the second filter of line 3 is only removed after line 1 was split into two lines
+        let check_results = run_rules(&file_context);
+        let (file_context, ..) =
+            iteratively_apply_suggestions(file_context, check_results).unwrap();
+        expect![[r"
+            <p>one
+            two</p>
+            {{ b|raw }}
+            {{ a }}
+        "]]
+        .assert_eq(&file_context.source_code);
+        assert_eq!(
+            file_context.changed_lines,
+            Some(ChangedLines::Ranges(vec![1..=2, 4..=4]))
+        );
+    }
+
+    #[test]
+    fn applied_fix_location() {
+        let source_code = "<div>\n    <span>ä</span>\n</div>";
+        let fix = AppliedFix::new("rule", source_code, TextSize::from(19));
+        expect!["rule at 2:13"].assert_eq(&fix.to_string());
+    }
+}
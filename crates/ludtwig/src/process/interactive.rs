@@ -0,0 +1,342 @@
+use std::collections::HashSet;
+use std::io;
+use std::io::Stdin;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::files::SimpleFiles;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::{StandardStream, WriteColor};
+
+use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+use crate::check::rule::{CheckSuggestion, TextEdit};
+use crate::output::{stderr_color_choice, TERMINAL_LOCK};
+use crate::process::FileContext;
+
+/// Decisions of the user which apply to all files of an interactive session (`--fix-interactive`).
+#[derive(Debug, Default)]
+pub struct InteractiveSession {
+    /// Rules for which the user chose to apply all suggestions without asking again
+    applied_rules: HashSet<String>,
+    /// The user wants to stop reviewing, nothing else is applied afterwards
+    quit: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    Apply,
+    Skip,
+    ApplyAllForRule,
+    Quit,
+}
+
+/// A suggestion the user skipped, which should not be asked for again in the next iteration.
+#[derive(Debug)]
+struct Skipped {
+    rule_name: String,
+    edits: Vec<TextEdit>,
+}
+
+/// Where the answers of the user are read from (the terminal or a fixed input in tests).
+pub trait AnswerInput {
+    fn read_answer(&mut self, line: &mut String) -> io::Result<usize>;
+}
+
+impl AnswerInput for Stdin {
+    fn read_answer(&mut self, line: &mut String) -> io::Result<usize> {
+        self.read_line(line)
+    }
+}
+
+#[cfg(test)]
+impl AnswerInput for io::Cursor<String> {
+    fn read_answer(&mut self, line: &mut String) -> io::Result<usize> {
+        io::BufRead::read_line(self, line)
+    }
+}
+
+/// Asks the user about each suggestion of a single file.
+pub struct FileReview<'s, I, O> {
+    session: &'s Mutex<InteractiveSession>,
+    input: I,
+    output: O,
+    skipped: Vec<Skipped>,
+    /// Exclusive access to the terminal, which is taken with the first question and kept until
+    /// the file is reviewed completely
+    terminal: Option<MutexGuard<'static, ()>>,
+    lock_terminal: bool,
+}
+
+impl<'s> FileReview<'s, Stdin, StandardStream> {
+    /// Review on the terminal, which blocks other files from being reviewed or printed in the meantime.
+    pub fn terminal(session: &'s Mutex<InteractiveSession>) -> Self {
+        Self {
+            session,
+            input: io::stdin(),
+            output: StandardStream::stderr(stderr_color_choice()),
+            skipped: vec![],
+            terminal: None,
+            lock_terminal: true,
+        }
+    }
+}
+
+#[cfg(test)]
+impl<'s, I, O> FileReview<'s, I, O> {
+    fn new(session: &'s Mutex<InteractiveSession>, input: I, output: O) -> Self {
+        Self {
+            session,
+            input,
+            output,
+            skipped: vec![],
+            terminal: None,
+            lock_terminal: false,
+        }
+    }
+}
+
+impl<I: AnswerInput, O: WriteColor> FileReview<'_, I, O> {
+    /// Ask the user which of the (sorted and non overlapping) suggestions should be applied.
+    pub fn select<'a>(
+        &mut self,
+        file_context: &FileContext,
+        suggestions: Vec<(&'a str, &'a CheckSuggestion)>,
+    ) -> Vec<(&'a str, &'a CheckSuggestion)> {
+        let mut selected = vec![];
+
+        for (rule_name, suggestion) in suggestions {
+            // the session is not locked while asking, because another file may hold the terminal
+            let (quit, apply_all) = {
+                let session = self.session.lock().unwrap_or_else(PoisonError::into_inner);
+                (session.quit, session.applied_rules.contains(rule_name))
+            };
+            if quit {
+                break;
+            }
+
+            if self
+                .skipped
+                .iter()
+                .any(|s| s.rule_name == rule_name && s.edits == suggestion.edits)
+            {
+                continue;
+            }
+
+            let answer = if apply_all {
+                Answer::Apply
+            } else {
+                self.ask(file_context, rule_name, suggestion)
+            };
+
+            match answer {
+                Answer::Apply => selected.push((rule_name, suggestion)),
+                Answer::ApplyAllForRule => {
+                    self.session
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .applied_rules
+                        .insert(rule_name.to_string());
+                    selected.push((rule_name, suggestion));
+                }
+                Answer::Skip => self.skipped.push(Skipped {
+                    rule_name: rule_name.to_string(),
+                    edits: suggestion.edits.clone(),
+                }),
+                Answer::Quit => {
+                    self.session
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .quit = true;
+                }
+            }
+        }
+
+        self.shift_skipped(&selected);
+        selected
+    }
+
+    fn ask(
+        &mut self,
+        file_context: &FileContext,
+        rule_name: &str,
+        suggestion: &CheckSuggestion,
+    ) -> Answer {
+        if self.lock_terminal && self.terminal.is_none() {
+            self.terminal = Some(TERMINAL_LOCK.lock().unwrap_or_else(PoisonError::into_inner));
+        }
+
+        let mut files = SimpleFiles::new();
+        let file_id = files.add(
+            file_context.file_path.to_string_lossy(),
+            &file_context.source_code,
+        );
+        let diagnostic = Diagnostic::help()
+            .with_code(rule_name)
+            .with_message(&suggestion.message)
+            .with_labels(
+                suggestion
+                    .edits
+                    .iter()
+                    .map(|edit| {
+                        Label::primary(file_id, edit.syntax_range)
+                            .with_message(format!("replace with: {}", edit.replace_with))
+                    })
+                    .collect(),
+            );
+        term::emit(
+            &mut self.output,
+            &term::Config::default(),
+            &files,
+            &diagnostic,
+        )
+        .unwrap();
+
+        loop {
+            write!(
+                self.output,
+                "Apply this fix? [y]es, [n]o, [a]ll of '{rule_name}', [q]uit: "
+            )
+            .unwrap();
+            self.output.flush().unwrap();
+
+            let mut line = String::new();
+            match self.input.read_answer(&mut line) {
+                Ok(0) | Err(_) => {
+                    // no more input, stop asking
+                    writeln!(self.output).unwrap();
+                    return Answer::Quit;
+                }
+                Ok(_) => {}
+            }
+
+            match line.trim().to_lowercase().as_str() {
+                "y" | "yes" => return Answer::Apply,
+                "n" | "no" => return Answer::Skip,
+                "a" | "all" => return Answer::ApplyAllForRule,
+                "q" | "quit" => return Answer::Quit,
+                _ => {}
+            }
+        }
+    }
+
+    /// Move the ranges of the skipped suggestions behind the applied suggestions,
+    /// so they are still recognized after the source code changed.
+    fn shift_skipped(&mut self, applied: &[(&str, &CheckSuggestion)]) {
+        let applied_edits: Vec<&TextEdit> = applied.iter().flat_map(|(_, s)| &s.edits).collect();
+
+        self.skipped.retain_mut(|skipped| {
+            skipped
+                .edits
+                .iter_mut()
+                .all(|edit| shift_edit(edit, &applied_edits))
+        });
+    }
+}
+
+/// Move the edit behind the applied edits. Returns false if the code of the edit itself was changed.
+fn shift_edit(edit: &mut TextEdit, applied_edits: &[&TextEdit]) -> bool {
+    let mut start = i64::from(u32::from(edit.syntax_range.start()));
+    let mut end = i64::from(u32::from(edit.syntax_range.end()));
+
+    for applied in applied_edits {
+        let range = applied.syntax_range;
+        if range.end() <= edit.syntax_range.start() {
+            let delta = i64::try_from(applied.replace_with.len()).unwrap()
+                - i64::from(u32::from(range.len()));
+            start += delta;
+            end += delta;
+        } else if range.start() < edit.syntax_range.end() {
+            // the skipped code was changed, so ask again if it is still suggested
+            return false;
+        }
+    }
+
+    edit.syntax_range = TextRange::new(
+        TextSize::from(u32::try_from(start).unwrap()),
+        TextSize::from(u32::try_from(end).unwrap()),
+    );
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::Mutex;
+
+    use codespan_reporting::term::termcolor::NoColor;
+    use expect_test::expect;
+
+    use crate::check::rules::test::debug_rule;
+    use crate::process::iteratively_apply_suggestions_with;
+
+    use super::{FileReview, InteractiveSession};
+
+    fn review(source_code: &str, answers: &str) -> (String, String, InteractiveSession) {
+        let session = Mutex::new(InteractiveSession::default());
+        let (file_context, results, rx) = debug_rule("html-attribute-name-kebab-case", source_code);
+        let mut review = FileReview::new(
+            &session,
+            Cursor::new(answers.to_string()),
+            NoColor::new(vec![]),
+        );
+        let (file_context, _, _, _) =
+            iteratively_apply_suggestions_with(file_context, results, |ctx, suggestions| {
+                review.select(ctx, suggestions)
+            })
+            .unwrap();
+        let prompts = String::from_utf8(review.output.into_inner()).unwrap();
+        drop(rx);
+
+        (
+            file_context.source_code,
+            prompts,
+            session.into_inner().unwrap(),
+        )
+    }
+
+    #[test]
+    fn review_applies_accepted_and_skips_others() {
+        let (source_code, prompts, session) = review(
+            r#"<div dataFoo="a" dataBar="b" dataBaz="c"></div>"#,
+            "y\nmaybe\nn\ny\n",
+        );
+
+        expect![[r#"<div data-foo="a" dataBar="b" data-baz="c"></div>"#]].assert_eq(&source_code);
+        expect![[r#"
+            help[html-attribute-name-kebab-case]: Try this name instead
+              ┌─ ./debug-rule.html.twig:1:6
+              │
+            1 │ <div dataFoo="a" dataBar="b" dataBaz="c"></div>
+              │      ^^^^^^^ replace with: data-foo
+
+            Apply this fix? [y]es, [n]o, [a]ll of 'html-attribute-name-kebab-case', [q]uit: help[html-attribute-name-kebab-case]: Try this name instead
+              ┌─ ./debug-rule.html.twig:1:18
+              │
+            1 │ <div dataFoo="a" dataBar="b" dataBaz="c"></div>
+              │                  ^^^^^^^ replace with: data-bar
+
+            Apply this fix? [y]es, [n]o, [a]ll of 'html-attribute-name-kebab-case', [q]uit: Apply this fix? [y]es, [n]o, [a]ll of 'html-attribute-name-kebab-case', [q]uit: help[html-attribute-name-kebab-case]: Try this name instead
+              ┌─ ./debug-rule.html.twig:1:30
+              │
+            1 │ <div dataFoo="a" dataBar="b" dataBaz="c"></div>
+              │                              ^^^^^^^ replace with: data-baz
+
+            Apply this fix? [y]es, [n]o, [a]ll of 'html-attribute-name-kebab-case', [q]uit: "#]].assert_eq(&prompts);
+        assert!(!session.quit);
+    }
+
+    #[test]
+    fn review_apply_all_for_rule_and_quit() {
+        let source_code = r#"<div dataFoo="a" dataBar="b"></div>"#;
+        let (fixed, _, session) = review(source_code, "a\n");
+        expect![[r#"<div data-foo="a" data-bar="b"></div>"#]].assert_eq(&fixed);
+        assert!(session
+            .applied_rules
+            .contains("html-attribute-name-kebab-case"));
+
+        let (fixed, _, session) = review(source_code, "y\nq\n");
+        expect![[r#"<div data-foo="a" dataBar="b"></div>"#]].assert_eq(&fixed);
+        assert!(session.quit);
+    }
+}
@@ -0,0 +1,171 @@
+use ludtwig_parser::syntax::untyped::{SyntaxElement, SyntaxKind, SyntaxNode, WalkEvent};
+use ludtwig_parser::T;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintItem {
+    Enter(SyntaxKind),
+    Leave,
+    Token(SyntaxKind),
+}
+
+/// The structure of the syntax tree (node and token kinds) without whitespace, line breaks, quotes
+/// and the string nodes around them, which are expected to change by formatting fixes.
+/// Operators with the same meaning (like `&&` and `and`) are treated as equal.
+/// Ludtwig directives are skipped completely, because they don't belong to the template itself.
+pub fn structural_fingerprint(root: &SyntaxNode) -> Vec<FingerprintItem> {
+    let mut fingerprint = vec![];
+    let mut preorder = root.preorder_with_tokens();
+    while let Some(event) = preorder.next() {
+        let item = match event {
+            WalkEvent::Enter(SyntaxElement::Node(n)) if is_directive_node(n.kind()) => {
+                preorder.skip_subtree();
+                None
+            }
+            WalkEvent::Enter(SyntaxElement::Node(n)) if !is_string_node(n.kind()) => {
+                Some(FingerprintItem::Enter(n.kind()))
+            }
+            WalkEvent::Leave(SyntaxElement::Node(n))
+                if !is_string_node(n.kind()) && !is_directive_node(n.kind()) =>
+            {
+                Some(FingerprintItem::Leave)
+            }
+            WalkEvent::Enter(SyntaxElement::Token(t))
+                if !t.kind().is_trivia() && !matches!(t.kind(), T!["\""] | T!["'"]) =>
+            {
+                Some(FingerprintItem::Token(match t.kind() {
+                    T!["&&"] => T!["and"],
+                    T!["||"] => T!["or"],
+                    kind => kind,
+                }))
+            }
+            _ => None,
+        };
+        fingerprint.extend(item);
+    }
+
+    fingerprint
+}
+
+fn is_directive_node(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::LUDTWIG_DIRECTIVE_IGNORE
+            | SyntaxKind::LUDTWIG_DIRECTIVE_FILE_IGNORE
+            | SyntaxKind::LUDTWIG_DIRECTIVE_DISABLE
+            | SyntaxKind::LUDTWIG_DIRECTIVE_ENABLE
+    )
+}
+
+fn is_string_node(kind: SyntaxKind) -> bool {
+    matches!(
+        kind,
+        SyntaxKind::HTML_STRING
+            | SyntaxKind::HTML_STRING_INNER
+            | SyntaxKind::TWIG_LITERAL_STRING
+            | SyntaxKind::TWIG_LITERAL_STRING_INNER
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ludtwig_parser::parse;
+    use ludtwig_parser::syntax::untyped::SyntaxNode;
+
+    use ludtwig_parser::syntax::untyped::{TextRange, TextSize};
+
+    use crate::check::rule::{
+        Applicability, CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, Severity,
+    };
+    use crate::check::rules::test::debug_rule;
+    use crate::check::rules::RULE_DEFINITIONS;
+    use crate::error::FileProcessingError;
+    use crate::process::iteratively_apply_suggestions;
+
+    use super::structural_fingerprint;
+
+    fn fingerprint(source_code: &str) -> Vec<super::FingerprintItem> {
+        structural_fingerprint(&SyntaxNode::new_root(parse(source_code).green_node))
+    }
+
+    #[test]
+    fn fingerprint_ignores_formatting() {
+        assert_eq!(
+            fingerprint("<div class='a'>{{ 'b' }}</div>"),
+            fingerprint("<div class=\"a\">\n    {{ \"b\" }}\n</div>\n")
+        );
+        assert_ne!(
+            fingerprint("<div class='a'>{{ 'b' }}</div>"),
+            fingerprint("<div class='a'>{{ b }}</div>")
+        );
+    }
+
+    #[test]
+    fn safe_fixes_of_all_rules_keep_the_structure() {
+        for rule in RULE_DEFINITIONS.iter() {
+            let metadata = rule.metadata();
+            let (file_context, results, rx) = debug_rule(rule.name(), metadata.bad_example);
+            if results
+                .iter()
+                .flat_map(CheckResult::suggestions)
+                .any(|s| s.applicability == Applicability::Unsafe)
+            {
+                continue;
+            }
+
+            let before = structural_fingerprint(&file_context.tree_root);
+            let (file_context, _, _, _) =
+                iteratively_apply_suggestions(file_context, results).unwrap();
+            assert_eq!(
+                before,
+                structural_fingerprint(&file_context.tree_root),
+                "safe fix of rule {} should not change the structure",
+                rule.name()
+            );
+            drop(rx);
+        }
+    }
+
+    /// Only creates the results of the tests.
+    struct TestRule;
+
+    impl Rule for TestRule {
+        fn name(&self) -> &'static str {
+            "test-rule"
+        }
+
+        fn metadata(&self) -> RuleMetadata<'_> {
+            RuleMetadata {
+                description: "",
+                rationale: "",
+                category: RuleCategory::Custom,
+                fixable: true,
+                config_keys: &[],
+                bad_example: "",
+                good_example: "",
+            }
+        }
+    }
+
+    #[test]
+    fn safe_fixes_are_verified_next_to_unsafe_ones() {
+        let source_code = "<div>{{ a }}</div><span></span>";
+        let range =
+            |start: u32, end: u32| TextRange::new(TextSize::from(start), TextSize::from(end));
+        let results = vec![
+            TestRule
+                .create_result(Severity::Warning, "safe")
+                .suggestion(range(8, 9), "a.b", "changes the structure"),
+            TestRule
+                .create_result(Severity::Warning, "unsafe")
+                .unsafe_suggestion(range(18, 31), "", "remove the span"),
+        ];
+        let (file_context, _, rx) = debug_rule("indentation", source_code);
+
+        let error = iteratively_apply_suggestions(file_context, results).unwrap_err();
+        assert!(matches!(
+            error,
+            FileProcessingError::FixChangedStructure { rule_names, .. } if rule_names == ["test-rule"]
+        ));
+        drop(rx);
+    }
+}
@@ -0,0 +1,473 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ludtwig_parser::syntax::typed::{AstNode, TwigLiteralString};
+use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxNode, TextRange};
+
+use crate::config::Templates;
+
+/// Namespace of the twig template names without a `@Namespace/` prefix.
+const MAIN_NAMESPACE: &str = "__main__";
+
+/// The tags which reference other templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateReferenceKind {
+    Extends,
+    ShopwareExtends,
+    Include,
+    ShopwareInclude,
+    Embed,
+    Use,
+    Import,
+    From,
+}
+
+impl TemplateReferenceKind {
+    fn of(kind: SyntaxKind) -> Option<Self> {
+        match kind {
+            SyntaxKind::TWIG_EXTENDS => Some(Self::Extends),
+            SyntaxKind::SHOPWARE_TWIG_SW_EXTENDS => Some(Self::ShopwareExtends),
+            SyntaxKind::TWIG_INCLUDE => Some(Self::Include),
+            SyntaxKind::SHOPWARE_TWIG_SW_INCLUDE => Some(Self::ShopwareInclude),
+            SyntaxKind::TWIG_EMBED => Some(Self::Embed),
+            SyntaxKind::TWIG_USE => Some(Self::Use),
+            SyntaxKind::TWIG_IMPORT => Some(Self::Import),
+            SyntaxKind::TWIG_FROM => Some(Self::From),
+            _ => None,
+        }
+    }
+
+    /// `sw_extends` and `sw_include`, which use the shopware bundle inheritance.
+    #[must_use]
+    pub fn is_shopware(self) -> bool {
+        matches!(self, Self::ShopwareExtends | Self::ShopwareInclude)
+    }
+}
+
+/// A template name inside of a tag like `{% extends '@Storefront/storefront/base.html.twig' %}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateReference {
+    pub kind: TemplateReferenceKind,
+    pub name: String,
+    /// The range of the name (without the quotes)
+    pub range: TextRange,
+}
+
+/// All template names referenced by tags in the syntax tree.
+/// Tags with a list of templates (like `{% include ['a.html.twig', 'b.html.twig'] %}`) reference each of them.
+pub fn template_references(root: &SyntaxNode) -> Vec<TemplateReference> {
+    root.descendants()
+        .filter_map(|node| Some((TemplateReferenceKind::of(node.kind())?, node)))
+        .flat_map(|(kind, node)| {
+            let tag = if kind == TemplateReferenceKind::Embed {
+                node.children()
+                    .find(|child| child.kind() == SyntaxKind::TWIG_EMBED_STARTING_BLOCK)
+            } else {
+                Some(node)
+            };
+            // the template argument is the first expression, later ones are the 'with' hash or the alias
+            let argument = tag.and_then(|tag| {
+                tag.children().find(|child| {
+                    matches!(
+                        child.kind(),
+                        SyntaxKind::TWIG_EXPRESSION | SyntaxKind::TWIG_LITERAL_STRING
+                    )
+                })
+            });
+
+            argument
+                .map(|argument| {
+                    argument
+                        .descendants()
+                        .filter_map(TwigLiteralString::cast)
+                        .filter(|string| is_plain_string(string, &argument))
+                        .filter_map(|string| string.get_inner())
+                        .map(|inner| TemplateReference {
+                            kind,
+                            name: inner.syntax().text().to_string(),
+                            range: inner.syntax().text_range(),
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// The string is the argument or an item of a list in it,
+/// and not part of a concatenation, condition or function call.
+fn is_plain_string(string: &TwigLiteralString, argument: &SyntaxNode) -> bool {
+    let string = string.syntax();
+    string == argument
+        || string
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor != argument)
+            .chain(std::iter::once(argument.clone()))
+            .all(|ancestor| {
+                matches!(
+                    ancestor.kind(),
+                    SyntaxKind::TWIG_EXPRESSION
+                        | SyntaxKind::TWIG_LITERAL_ARRAY
+                        | SyntaxKind::TWIG_LITERAL_ARRAY_INNER
+                )
+            })
+}
+
+/// The templates of a project, which resolves template names to files like twig and shopware do.
+#[derive(Debug, Clone, Default)]
+pub struct Project {
+    /// The directories of each namespace (without the `@`)
+    namespaces: Vec<(String, Vec<PathBuf>)>,
+    /// The shopware bundle inheritance order (namespaces without the `@`)
+    bundles: Vec<String>,
+    /// The directory of the config (normalized), relative names are not searched above it
+    root: PathBuf,
+}
+
+impl Project {
+    /// The project of the `[templates]` config, where the directories are relative to `base_dir`.
+    pub fn new(templates: &Templates, base_dir: &Path) -> Self {
+        let namespaces = templates
+            .namespaces
+            .iter()
+            .map(|(namespace, dirs)| {
+                (
+                    namespace.trim_start_matches('@').to_string(),
+                    dirs.iter().map(|dir| base_dir.join(dir)).collect(),
+                )
+            })
+            .collect();
+        let bundles = templates
+            .bundles
+            .iter()
+            .map(|bundle| bundle.trim_start_matches('@').to_string())
+            .collect();
+
+        let root = if base_dir.as_os_str().is_empty() {
+            normalized(Path::new("."))
+        } else {
+            normalized(base_dir)
+        };
+
+        Self {
+            namespaces,
+            bundles,
+            root,
+        }
+    }
+
+    /// Find the file of a template name, which is referenced by a tag of the kind in the file `referenced_from`.
+    ///
+    /// - `sw_extends` and `sw_include` search all shopware bundles in their inheritance order.
+    ///   Extending the same template starts after the bundle of the referencing file (like shopware does).
+    /// - `@Namespace/name` is searched in the directories of the namespace
+    ///   (and names without a namespace in the ones of `__main__`).
+    /// - Otherwise (like unknown namespaces) the name is searched relative to the directory of the referencing
+    ///   file and all of its parents up to the directory of the config.
+    pub fn resolve(
+        &self,
+        name: &str,
+        kind: TemplateReferenceKind,
+        referenced_from: &Path,
+    ) -> Option<PathBuf> {
+        self.resolve_probing(name, kind, referenced_from, |_| {})
+    }
+
+    /// Like [`Project::resolve`], but `probe` is called with every candidate path (normalized) which was
+    /// looked at, including the ones that don't exist (the found file is the last one).
+    pub fn resolve_probing(
+        &self,
+        name: &str,
+        kind: TemplateReferenceKind,
+        referenced_from: &Path,
+        mut probe: impl FnMut(&Path),
+    ) -> Option<PathBuf> {
+        let (namespace, relative) = match name.strip_prefix('@') {
+            Some(namespaced) => namespaced.split_once('/')?,
+            None => (MAIN_NAMESPACE, name),
+        };
+        let referenced_from = normalized(referenced_from);
+        let mut is_other_file = |candidate: &PathBuf| {
+            let candidate = normalized(candidate);
+            if candidate == referenced_from {
+                return false;
+            }
+            probe(&candidate);
+            candidate.is_file()
+        };
+
+        if kind.is_shopware() && !self.bundles.is_empty() {
+            // shopware ignores the namespace and searches the template in all bundles
+            let start = self
+                .bundles
+                .iter()
+                .position(|bundle| {
+                    self.dirs(bundle).iter().any(|dir| {
+                        referenced_from.strip_prefix(normalized(dir)).ok()
+                            == Some(Path::new(relative))
+                    })
+                })
+                .map_or(0, |index| index + 1);
+            if let Some(found) = self.bundles[start..]
+                .iter()
+                .flat_map(|bundle| self.dirs(bundle))
+                .map(|dir| dir.join(relative))
+                .find(&mut is_other_file)
+            {
+                return Some(normalized(&found));
+            }
+        }
+
+        if self.has_namespace(namespace) {
+            return self
+                .dirs(namespace)
+                .iter()
+                .map(|dir| dir.join(relative))
+                .find(&mut is_other_file)
+                .map(|found| normalized(&found));
+        }
+
+        // files outside of the project only search their own directory
+        let mut dirs = referenced_from.ancestors().skip(1);
+        let first = dirs.next();
+        first
+            .into_iter()
+            .chain(dirs.take_while(|dir| dir.starts_with(&self.root)))
+            .map(|dir| dir.join(relative))
+            .find(&mut is_other_file)
+            .map(|found| normalized(&found))
+    }
+
+    fn has_namespace(&self, namespace: &str) -> bool {
+        self.namespaces.iter().any(|(name, _)| name == namespace)
+    }
+
+    fn dirs(&self, namespace: &str) -> &[PathBuf] {
+        self.namespaces
+            .iter()
+            .find(|(name, _)| name == namespace)
+            .map_or(&[], |(_, dirs)| dirs)
+    }
+}
+
+/// The absolute path without `.` / `..` and symlinks, so paths written differently can be compared.
+fn normalized(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::Path;
+
+    use expect_test::expect;
+    use ludtwig_parser::syntax::untyped::SyntaxNode;
+
+    use super::{template_references, Project, TemplateReferenceKind};
+    use crate::config::Templates;
+    use crate::test_util::temp_dir;
+
+    fn write(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn template_references_of_all_tags() {
+        let source_code = r"{% extends '@Storefront/a.html.twig' %}
+{% sw_extends 'b.html.twig' %}
+{% include 'c.html.twig' with { x: 'not-a-template' } %}
+{% sw_include 'd.html.twig' %}
+{% embed 'e.html.twig' %}{% endembed %}
+{% use 'f.html.twig' %}
+{% import 'g.html.twig' as g %}
+{% from 'h.html.twig' import x %}
+{% include ['i.html.twig', 'j.html.twig'] %}
+{% include 'prefix-' ~ name %}
+";
+        let root = SyntaxNode::new_root(ludtwig_parser::parse(source_code).green_node);
+        let references: Vec<String> = template_references(&root)
+            .into_iter()
+            .map(|r| format!("{:?} {} {:?}", r.kind, r.name, r.range))
+            .collect();
+        expect![[r#"
+            [
+                "Extends @Storefront/a.html.twig 12..35",
+                "ShopwareExtends b.html.twig 55..66",
+                "Include c.html.twig 83..94",
+                "ShopwareInclude d.html.twig 143..154",
+                "Embed e.html.twig 169..180",
+                "Use f.html.twig 207..218",
+                "Import g.html.twig 234..245",
+                "From h.html.twig 264..275",
+                "Include i.html.twig 302..313",
+                "Include j.html.twig 317..328",
+            ]
+        "#]]
+        .assert_debug_eq(&references);
+    }
+
+    #[test]
+    fn resolve_namespaces_bundles_and_relative_names() {
+        let temp = temp_dir();
+        // resolved paths are canonical
+        let dir = fs::canonicalize(temp.path()).unwrap();
+        let storefront = dir.join("vendor/shopware/storefront/Resources/views");
+        let theme = dir.join("custom/plugins/MyTheme/src/Resources/views");
+        for path in [
+            storefront.join("storefront/base.html.twig"),
+            storefront.join("storefront/page/content/index.html.twig"),
+            theme.join("storefront/base.html.twig"),
+            theme.join("storefront/page/product.html.twig"),
+            theme.join("storefront/component/price.html.twig"),
+            dir.join("templates/layout.html.twig"),
+        ] {
+            write(&path);
+        }
+        let project = Project::new(
+            &Templates {
+                namespaces: BTreeMap::from([
+                    (
+                        "@Storefront".to_string(),
+                        vec!["vendor/shopware/storefront/Resources/views".to_string()],
+                    ),
+                    (
+                        "MyTheme".to_string(),
+                        vec!["custom/plugins/MyTheme/src/Resources/views".to_string()],
+                    ),
+                    ("__main__".to_string(), vec!["templates".to_string()]),
+                ]),
+                bundles: vec!["MyTheme".to_string(), "Storefront".to_string()],
+            },
+            &dir,
+        );
+        let resolve = |name: &str, kind: TemplateReferenceKind, from: &Path| {
+            project
+                .resolve(name, kind, from)
+                .map(|path| path.strip_prefix(&dir).unwrap().display().to_string())
+        };
+
+        let base = "@Storefront/storefront/base.html.twig";
+        // the theme extends the same template of the next bundle
+        assert_eq!(
+            resolve(
+                base,
+                TemplateReferenceKind::ShopwareExtends,
+                &theme.join("storefront/base.html.twig")
+            ),
+            Some(
+                "vendor/shopware/storefront/Resources/views/storefront/base.html.twig".to_string()
+            )
+        );
+        // other templates get the most specific one
+        assert_eq!(
+            resolve(
+                base,
+                TemplateReferenceKind::ShopwareExtends,
+                &storefront.join("storefront/page/content/index.html.twig")
+            ),
+            Some(
+                "custom/plugins/MyTheme/src/Resources/views/storefront/base.html.twig".to_string()
+            )
+        );
+        // plain twig tags only use the namespace
+        assert_eq!(
+            resolve(
+                base,
+                TemplateReferenceKind::Extends,
+                &theme.join("storefront/page/product.html.twig")
+            ),
+            Some(
+                "vendor/shopware/storefront/Resources/views/storefront/base.html.twig".to_string()
+            )
+        );
+        assert_eq!(
+            resolve(
+                "@Storefront/storefront/component/price.html.twig",
+                TemplateReferenceKind::Include,
+                &theme.join("storefront/page/product.html.twig")
+            ),
+            None
+        );
+        // names without a namespace use __main__
+        assert_eq!(
+            resolve(
+                "layout.html.twig",
+                TemplateReferenceKind::Embed,
+                &theme.join("storefront/page/product.html.twig")
+            ),
+            Some("templates/layout.html.twig".to_string())
+        );
+        // unknown namespaces are searched relative to the template
+        assert_eq!(
+            resolve(
+                "@Unknown/storefront/component/price.html.twig",
+                TemplateReferenceKind::Include,
+                &theme.join("storefront/page/product.html.twig")
+            ),
+            Some(
+                "custom/plugins/MyTheme/src/Resources/views/storefront/component/price.html.twig"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn resolve_relative_names_only_inside_the_project() {
+        let temp = temp_dir();
+        let dir = fs::canonicalize(temp.path()).unwrap();
+        write(&dir.join("outside.html.twig"));
+        write(&dir.join("project/layout.html.twig"));
+        let project = Project::new(&Templates::default(), &dir.join("project/../project"));
+        let resolve = |name: &str| {
+            project.resolve(
+                name,
+                TemplateReferenceKind::Include,
+                &dir.join("project/views/page.html.twig"),
+            )
+        };
+
+        assert_eq!(
+            resolve("layout.html.twig"),
+            Some(dir.join("project/layout.html.twig"))
+        );
+        assert_eq!(resolve("outside.html.twig"), None);
+    }
+
+    #[test]
+    fn resolve_probing_reports_missing_candidates() {
+        let temp = temp_dir();
+        let dir = fs::canonicalize(temp.path()).unwrap();
+        write(&dir.join("storefront/page.html.twig"));
+        let project = Project::new(
+            &Templates {
+                namespaces: BTreeMap::from([
+                    ("Storefront".to_string(), vec!["storefront".to_string()]),
+                    ("MyTheme".to_string(), vec!["theme".to_string()]),
+                ]),
+                bundles: vec!["MyTheme".to_string(), "Storefront".to_string()],
+            },
+            &dir,
+        );
+
+        let mut probed = vec![];
+        let found = project.resolve_probing(
+            "@Storefront/page.html.twig",
+            TemplateReferenceKind::ShopwareInclude,
+            &dir.join("theme/index.html.twig"),
+            |candidate| probed.push(candidate.strip_prefix(&dir).unwrap().to_owned()),
+        );
+
+        // creating the template in the theme would change the result
+        assert_eq!(found, Some(dir.join("storefront/page.html.twig")));
+        assert_eq!(
+            probed,
+            vec![
+                Path::new("theme/page.html.twig"),
+                Path::new("storefront/page.html.twig")
+            ]
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use ludtwig_parser::syntax::untyped::{SyntaxElement, SyntaxKind, SyntaxNode, SyntaxToken};
+
+use crate::check::query::{element_range, element_text, render_template};
+use crate::check::rule::{
+    CheckResult, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext, Severity,
+};
+
+/// Separates the pattern from the replacement on the command line.
+pub const ARROW: &str = "=>";
+
+/// Structural search and replace (`ludtwig ssr`): a rule which suggests to replace the code matching
+/// the pattern with the replacement.
+///
+/// The pattern is twig / html code (like `include $tpl with $args`), where `$name` placeholders match any
+/// expression or node and whitespace is ignored. Twig tags can be written without the `{% %}` and expressions
+/// without the `{{ }}`, which then also match expressions inside of other tags.
+#[derive(Debug)]
+pub struct SsrRule {
+    pattern: PatternElement,
+    /// The replacement (wrapped like the pattern) with the `$name` placeholders
+    replacement: String,
+}
+
+/// The syntax tree of the pattern without whitespace.
+#[derive(Debug, PartialEq, Eq)]
+enum PatternElement {
+    Placeholder(String),
+    Token {
+        kind: SyntaxKind,
+        text: String,
+    },
+    Node {
+        kind: SyntaxKind,
+        children: Vec<PatternElement>,
+    },
+}
+
+/// How the pattern was completed to valid twig / html code.
+#[derive(Debug, Clone, Copy)]
+enum Wrapping {
+    None,
+    /// `{% pattern %}`
+    Tag,
+    /// `{{ pattern }}` where only the expression inside is matched
+    Expression,
+}
+
+impl SsrRule {
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, String> {
+        let mut placeholders: Vec<String> = vec![];
+        // placeholders are replaced by words which are valid in any place (like html tag names and twig names)
+        let code = render_template(pattern, |name| {
+            let index = placeholders
+                .iter()
+                .position(|p| p == name)
+                .unwrap_or_else(|| {
+                    placeholders.push(name.to_string());
+                    placeholders.len() - 1
+                });
+            format!("ssrplaceholder{index}")
+        });
+
+        let wrappings: &[Wrapping] = if code.trim_start().starts_with(['<', '{']) {
+            &[Wrapping::None]
+        } else {
+            &[Wrapping::Tag, Wrapping::Expression]
+        };
+        let (wrapping, pattern_root) = wrappings
+            .iter()
+            .find_map(|wrapping| Some((*wrapping, parse_pattern(&code, *wrapping)?)))
+            .ok_or_else(|| {
+                "the pattern is not a single valid twig tag, expression or html element".to_string()
+            })?;
+
+        let pattern = PatternElement::new(&pattern_root, &placeholders)?;
+        if matches!(pattern, PatternElement::Placeholder(_)) {
+            return Err("the pattern must contain more than a placeholder".to_string());
+        }
+
+        let mut unknown = None;
+        render_template(replacement, |name| {
+            if !placeholders.iter().any(|p| p == name) {
+                unknown.get_or_insert_with(|| name.to_string());
+            }
+            String::new()
+        });
+        if let Some(name) = unknown {
+            return Err(format!(
+                "the replacement uses ${name}, which is not in the pattern"
+            ));
+        }
+
+        let replacement = match wrapping {
+            Wrapping::Tag => format!("{{% {} %}}", replacement.trim()),
+            Wrapping::None | Wrapping::Expression => replacement.trim().to_string(),
+        };
+        Ok(Self {
+            pattern,
+            replacement,
+        })
+    }
+
+    /// The text of the elements the placeholders matched, if the pattern matches the element.
+    fn matches(&self, element: &SyntaxElement) -> Option<Vec<(String, SyntaxElement)>> {
+        let mut bindings = vec![];
+        self.pattern
+            .matches(element, &mut bindings)
+            .then_some(bindings)
+    }
+
+    fn check_element(&self, element: &SyntaxElement) -> Option<Vec<CheckResult>> {
+        let bindings = self.matches(element)?;
+        // only the outermost match is replaced, the ones inside are matched again after replacing it
+        if element
+            .parent()
+            .into_iter()
+            .flat_map(|parent| parent.ancestors())
+            .any(|ancestor| self.matches(&ancestor.into()).is_some())
+        {
+            return None;
+        }
+
+        let replacement = render_template(&self.replacement, |name| {
+            bindings
+                .iter()
+                .find(|(bound, _)| bound == name)
+                .map(|(_, element)| element_text(element))
+                .unwrap_or_default()
+        });
+        let range = element_range(element);
+        if replacement == element_text(element) {
+            return None;
+        }
+
+        let result = self
+            .create_result(Severity::Info, "Code matches the pattern")
+            .primary_note(range, "matched here")
+            .suggestion(range, replacement, "Replace it with");
+        Some(vec![result])
+    }
+}
+
+impl Rule for SsrRule {
+    fn name(&self) -> &'static str {
+        "ssr"
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "Replaces the code matching the pattern of 'ludtwig ssr'.",
+            rationale: "Refactorings of many templates are tedious and error-prone by hand.",
+            category: RuleCategory::Ludtwig,
+            fixable: true,
+            config_keys: &[],
+            bad_example: "",
+            good_example: "",
+        }
+    }
+
+    fn check_node(&self, node: SyntaxNode, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        self.check_element(&node.into())
+    }
+
+    fn check_token(&self, token: SyntaxToken, _ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        self.check_element(&token.into())
+    }
+}
+
+/// Parse the (wrapped) pattern and find the element which is matched. `None` if the pattern has syntax errors
+/// or isn't a single element.
+fn parse_pattern(code: &str, wrapping: Wrapping) -> Option<SyntaxElement> {
+    let wrapped = match wrapping {
+        Wrapping::None => code.trim().to_string(),
+        Wrapping::Tag => format!("{{% {} %}}", code.trim()),
+        Wrapping::Expression => format!("{{{{ {} }}}}", code.trim()),
+    };
+    let parse = ludtwig_parser::parse(&wrapped);
+    if !parse.errors.is_empty() {
+        return None;
+    }
+
+    let root = SyntaxNode::new_root(parse.green_node);
+    let mut elements = significant_children(&root);
+    let element = elements.pop().filter(|_| elements.is_empty())?;
+    match wrapping {
+        Wrapping::None | Wrapping::Tag => Some(element),
+        // {{ expression }}
+        Wrapping::Expression => significant_children(element.as_node()?)
+            .into_iter()
+            .find(|child| child.kind() == SyntaxKind::TWIG_EXPRESSION)?
+            .as_node()?
+            .children()
+            .next()
+            .map(SyntaxElement::from),
+    }
+}
+
+/// The children of the node without whitespace and line breaks.
+fn significant_children(node: &SyntaxNode) -> Vec<SyntaxElement> {
+    node.children_with_tokens()
+        .filter(|child| !child.kind().is_trivia())
+        .collect()
+}
+
+impl PatternElement {
+    fn new(element: &SyntaxElement, placeholders: &[String]) -> Result<Self, String> {
+        let text = element_text(element);
+        if let Some(index) = text
+            .strip_prefix("ssrplaceholder")
+            .and_then(|index| index.parse::<usize>().ok())
+        {
+            // the pattern itself can contain the words which replace the placeholders
+            let Some(name) = placeholders.get(index) else {
+                return Err(format!("the pattern must not contain '{text}'"));
+            };
+            return Ok(PatternElement::Placeholder(name.clone()));
+        }
+
+        Ok(match element {
+            SyntaxElement::Node(node) => PatternElement::Node {
+                kind: node.kind(),
+                children: significant_children(node)
+                    .iter()
+                    .map(|child| PatternElement::new(child, placeholders))
+                    .collect::<Result<_, _>>()?,
+            },
+            SyntaxElement::Token(token) => PatternElement::Token {
+                kind: token.kind(),
+                text: token.text().to_string(),
+            },
+        })
+    }
+
+    fn matches(
+        &self,
+        element: &SyntaxElement,
+        bindings: &mut Vec<(String, SyntaxElement)>,
+    ) -> bool {
+        match self {
+            PatternElement::Placeholder(name) => {
+                if let Some((_, bound)) = bindings.iter().find(|(bound, _)| bound == name) {
+                    // the same placeholder must match the same code each time
+                    return normalized_text(bound) == normalized_text(element);
+                }
+                bindings.push((name.clone(), element.clone()));
+                true
+            }
+            PatternElement::Token { kind, text } => element
+                .as_token()
+                .is_some_and(|token| token.kind() == *kind && token.text() == text),
+            PatternElement::Node { kind, children } => {
+                let Some(node) = element.as_node().filter(|node| node.kind() == *kind) else {
+                    return false;
+                };
+                let element_children = significant_children(node);
+                element_children.len() == children.len()
+                    && children
+                        .iter()
+                        .zip(&element_children)
+                        .all(|(pattern, child)| pattern.matches(child, bindings))
+            }
+        }
+    }
+}
+
+/// The tokens of the element without whitespace, separated by a single space.
+fn normalized_text(element: &SyntaxElement) -> String {
+    match element {
+        SyntaxElement::Node(node) => node
+            .descendants_with_tokens()
+            .filter_map(SyntaxElement::into_token)
+            .filter(|token| !token.kind().is_trivia())
+            .map(|token| token.text().to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+        SyntaxElement::Token(token) => token.text().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::{Arc, OnceLock};
+
+    use expect_test::expect;
+
+    use crate::config::{ActiveConfig, Config, ConfigResolver, DEFAULT_CONFIG_PATH};
+    use crate::linter::Linter;
+
+    use super::SsrRule;
+
+    /// The source code after replacing everything which matches the pattern.
+    fn replace(pattern: &str, replacement: &str, source_code: &str) -> String {
+        let rule = SsrRule::new(pattern, replacement).unwrap();
+        let active = ActiveConfig {
+            path: None,
+            config: Config::new(DEFAULT_CONFIG_PATH).unwrap(),
+            rule_definitions: vec![Arc::new(rule)],
+            project: OnceLock::new(),
+        };
+        let linter = Linter::with_resolver(ConfigResolver::new(active, false, false));
+        linter
+            .fix_source(Path::new("a.html.twig"), source_code)
+            .unwrap()
+            .source_code
+    }
+
+    #[test]
+    fn ssr_replaces_tags_ignoring_whitespace() {
+        expect![[r"
+            {% block content %}
+                {% sw_include '@Storefront/a.html.twig' with { foo: 1 } %}
+                {% sw_include 'b.html.twig' with vars %}
+                {% include 'c.html.twig' %}
+            {% endblock %}
+        "]]
+        .assert_eq(&replace(
+            "include $tpl with $args",
+            "sw_include $tpl with $args",
+            "{% block content %}\n    {% include '@Storefront/a.html.twig' with { foo: 1 } %}\n    {%include   'b.html.twig'   with\n vars%}\n    {% include 'c.html.twig' %}\n{% endblock %}\n",
+        ));
+    }
+
+    #[test]
+    fn ssr_replaces_expressions_and_html() {
+        expect![[r"
+            {{ title }} {% if a.b %}{{ c }}{% endif %}
+        "]]
+        .assert_eq(&replace(
+            "$value|raw",
+            "$value",
+            "{{ title|raw|raw }} {% if a.b|raw %}{{ c }}{% endif %}\n",
+        ));
+        expect![[r#"
+            <section class="a"><p>{{ text }}</p></section>
+        "#]]
+        .assert_eq(&replace(
+            "<div class=$class>$body</div>",
+            "<section class=$class>$body</section>",
+            "<div class=\"a\"><p>{{ text }}</p></div>\n",
+        ));
+    }
+
+    #[test]
+    fn ssr_placeholders_used_twice_match_the_same_code() {
+        expect![[r"
+            {{ true }} {{ a == b }}
+        "]]
+        .assert_eq(&replace(
+            "$x == $x",
+            "true",
+            "{{ a.b  ==  a.b }} {{ a == b }}\n",
+        ));
+    }
+
+    #[test]
+    fn ssr_invalid_patterns() {
+        let error =
+            |pattern: &str, replacement: &str| SsrRule::new(pattern, replacement).unwrap_err();
+        expect!["the pattern is not a single valid twig tag, expression or html element"]
+            .assert_eq(&error("include $tpl with", "x"));
+        expect!["the pattern must contain more than a placeholder"].assert_eq(&error("$a", "b"));
+        expect!["the replacement uses $args, which is not in the pattern"]
+            .assert_eq(&error("include $tpl", "sw_include $tpl with $args"));
+        expect!["the pattern must not contain 'ssrplaceholder3'"]
+            .assert_eq(&error("$a == ssrplaceholder3", "b"));
+    }
+}
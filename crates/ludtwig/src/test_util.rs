@@ -0,0 +1,9 @@
+use tempfile::TempDir;
+
+/// A new empty directory for the files of a test, which is removed with its content when it is dropped.
+pub fn temp_dir() -> TempDir {
+    tempfile::Builder::new()
+        .prefix("ludtwig-test-")
+        .tempdir()
+        .expect("can't create a temporary directory")
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use ludtwig_parser::ParseTimings;
+
+use crate::check::rule::Rule;
+
+/// How many of the slowest rules and files are reported.
+const SLOWEST_COUNT: usize = 10;
+
+/// Where the time of a run is spent (`--timings`), summed over all files and threads.
+#[derive(Debug, Default)]
+pub struct Timings {
+    data: Mutex<TimingsData>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct TimingsData {
+    phases: PhaseTimings,
+    rules: BTreeMap<String, RuleTimings>,
+    /// Total processing time of each file
+    files: Vec<(PathBuf, Duration)>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct PhaseTimings {
+    read: Duration,
+    lex: Duration,
+    parse: Duration,
+    /// Running the rules, including the traversal of the syntax tree
+    check: Duration,
+    /// Applying suggestions, including parsing and checking the fixed source code again
+    /// (which is not part of `check`)
+    fix: Duration,
+    fix_iterations: usize,
+    write: Duration,
+}
+
+/// Time spent in the `check_root`, `check_node` and `check_token` methods of a single rule.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RuleTimings {
+    pub root: Duration,
+    pub node: Duration,
+    pub token: Duration,
+    /// Time of all methods while checking the fixed source code again
+    pub fix: Duration,
+}
+
+impl RuleTimings {
+    fn total(&self) -> Duration {
+        self.root + self.node + self.token + self.fix
+    }
+}
+
+/// Why the rules run: to check a file or to check it again after suggestions were applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulePhase {
+    Check,
+    Fix,
+}
+
+/// Run `f` and add its duration to `total` (if there is one).
+pub fn timed<T>(total: Option<&mut Duration>, f: impl FnOnce() -> T) -> T {
+    match total {
+        Some(total) => {
+            let start = Instant::now();
+            let result = f();
+            *total += start.elapsed();
+            result
+        }
+        None => f(),
+    }
+}
+
+impl Timings {
+    fn update(&self, f: impl FnOnce(&mut TimingsData)) {
+        f(&mut self.data.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    pub fn record_read(&self, duration: Duration) {
+        self.update(|data| data.phases.read += duration);
+    }
+
+    pub fn record_parse(&self, timings: ParseTimings) {
+        self.update(|data| {
+            data.phases.lex += timings.lex;
+            data.phases.parse += timings.parse;
+        });
+    }
+
+    pub fn record_check(&self, duration: Duration) {
+        self.update(|data| data.phases.check += duration);
+    }
+
+    pub fn record_fix(&self, duration: Duration, iterations: usize) {
+        self.update(|data| {
+            data.phases.fix += duration;
+            data.phases.fix_iterations += iterations;
+        });
+    }
+
+    pub fn record_write(&self, duration: Duration) {
+        self.update(|data| data.phases.write += duration);
+    }
+
+    /// Add the timings of the rules (in the same order as `rule_definitions`) of a single check.
+    /// The time of checking fixed source code again only counts for the fix of each rule.
+    pub fn record_rules(
+        &self,
+        rule_definitions: &[Arc<dyn Rule>],
+        timings: &[RuleTimings],
+        phase: RulePhase,
+    ) {
+        self.update(|data| {
+            for (rule, timings) in rule_definitions.iter().zip(timings) {
+                let total = data.rules.entry(rule.name().to_string()).or_default();
+                match phase {
+                    RulePhase::Check => {
+                        total.root += timings.root;
+                        total.node += timings.node;
+                        total.token += timings.token;
+                    }
+                    RulePhase::Fix => total.fix += timings.total(),
+                }
+            }
+        });
+    }
+
+    pub fn record_file(&self, path: PathBuf, duration: Duration) {
+        self.update(|data| data.files.push((path, duration)));
+    }
+
+    /// Tables of the time per phase and of the slowest rules and files.
+    pub fn report(&self, wall_time: Duration) -> String {
+        self.data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .report(wall_time)
+    }
+}
+
+impl TimingsData {
+    fn report(&self, wall_time: Duration) -> String {
+        let time = |duration: Duration| format!("{duration:.2?}");
+        let mut report = format!(
+            "\nTimings (summed over all threads, {} wall time)\n\n",
+            time(wall_time)
+        );
+
+        let phases = &self.phases;
+        let fix_label = format!("Fixing ({} iterations)", phases.fix_iterations);
+        let phase_rows = [
+            ("Reading files", phases.read),
+            ("Lexing", phases.lex),
+            ("Parsing", phases.parse),
+            ("Checking rules", phases.check),
+            (fix_label.as_str(), phases.fix),
+            ("Writing files", phases.write),
+        ];
+        writeln!(report, "{:<24}  {:>10}", "Phase", "Time").unwrap();
+        for (name, duration) in phase_rows {
+            writeln!(report, "{name:<24}  {:>10}", time(duration)).unwrap();
+        }
+
+        let mut rules: Vec<_> = self.rules.iter().collect();
+        rules
+            .sort_by(|(a_name, a), (b_name, b)| b.total().cmp(&a.total()).then(a_name.cmp(b_name)));
+        if !rules.is_empty() {
+            let width = rules
+                .iter()
+                .take(SLOWEST_COUNT)
+                .map(|(name, _)| name.len())
+                .chain(["Slowest rules".len()])
+                .max()
+                .unwrap_or_default();
+            writeln!(
+                report,
+                "\n{:<width$}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+                "Slowest rules", "Root", "Node", "Token", "Fix", "Total"
+            )
+            .unwrap();
+            for (name, timings) in rules.into_iter().take(SLOWEST_COUNT) {
+                writeln!(
+                    report,
+                    "{name:<width$}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+                    time(timings.root),
+                    time(timings.node),
+                    time(timings.token),
+                    time(timings.fix),
+                    time(timings.total())
+                )
+                .unwrap();
+            }
+        }
+
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by(|(a_path, a), (b_path, b)| b.cmp(a).then(a_path.cmp(b_path)));
+        if !files.is_empty() {
+            let width = files
+                .iter()
+                .take(SLOWEST_COUNT)
+                .map(|(path, _)| path.to_string_lossy().chars().count())
+                .chain(["Slowest files".len()])
+                .max()
+                .unwrap_or_default();
+            writeln!(report, "\n{:<width$}  {:>10}", "Slowest files", "Time").unwrap();
+            for (path, duration) in files.into_iter().take(SLOWEST_COUNT) {
+                writeln!(
+                    report,
+                    "{:<width$}  {:>10}",
+                    path.to_string_lossy(),
+                    time(*duration)
+                )
+                .unwrap();
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use expect_test::expect;
+    use ludtwig_parser::ParseTimings;
+
+    use crate::check::rules::RULE_DEFINITIONS;
+
+    use super::{RulePhase, RuleTimings, Timings};
+
+    #[test]
+    fn timings_report_slowest_rules_and_files() {
+        let ms = Duration::from_millis;
+        let timings = Timings::default();
+        timings.record_read(ms(2));
+        timings.record_parse(ParseTimings {
+            lex: ms(1),
+            parse: ms(3),
+        });
+        timings.record_check(ms(9));
+        timings.record_fix(ms(4), 2);
+        timings.update(|data| {
+            data.rules.insert(
+                "rule-a".to_string(),
+                RuleTimings {
+                    root: ms(1),
+                    ..RuleTimings::default()
+                },
+            );
+            data.rules.insert(
+                "rule-b".to_string(),
+                RuleTimings {
+                    node: ms(2),
+                    token: ms(3),
+                    ..RuleTimings::default()
+                },
+            );
+        });
+        timings.record_file(PathBuf::from("a.html.twig"), ms(5));
+        timings.record_file(PathBuf::from("long/b.html.twig"), ms(12));
+
+        expect![[r"
+
+            Timings (summed over all threads, 20.00ms wall time)
+
+            Phase                           Time
+            Reading files                 2.00ms
+            Lexing                        1.00ms
+            Parsing                       3.00ms
+            Checking rules                9.00ms
+            Fixing (2 iterations)         4.00ms
+            Writing files                 0.00ns
+
+            Slowest rules        Root        Node       Token         Fix       Total
+            rule-b             0.00ns      2.00ms      3.00ms      0.00ns      5.00ms
+            rule-a             1.00ms      0.00ns      0.00ns      0.00ns      1.00ms
+
+            Slowest files           Time
+            long/b.html.twig     12.00ms
+            a.html.twig           5.00ms
+        "]]
+        .assert_eq(&timings.report(ms(20)));
+    }
+
+    #[test]
+    fn rules_checking_fixed_source_code_count_as_fix() {
+        let ms = Duration::from_millis;
+        let rule = &RULE_DEFINITIONS[0];
+        let timings = Timings::default();
+        let check = RuleTimings {
+            root: ms(1),
+            ..RuleTimings::default()
+        };
+        let fix = RuleTimings {
+            node: ms(2),
+            token: ms(1),
+            ..RuleTimings::default()
+        };
+        timings.record_rules(std::slice::from_ref(rule), &[check], RulePhase::Check);
+        timings.record_rules(std::slice::from_ref(rule), &[fix], RulePhase::Fix);
+
+        let data = timings.data.lock().unwrap();
+        let recorded = data.rules[rule.name()];
+        assert_eq!(
+            (recorded.root, recorded.node, recorded.token, recorded.fix),
+            (ms(1), Duration::ZERO, Duration::ZERO, ms(3))
+        );
+        assert_eq!(recorded.total(), ms(4));
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, PoisonError};
+use std::time::Duration;
+
+use codespan_reporting::term::termcolor::{Buffer, BufferWriter};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+
+use crate::error::ErrorKind;
+use crate::output::{
+    error, exit_code, exit_code_after_output, is_quiet, is_stdout_reserved, stderr_color_choice,
+    stdout_color_choice, write_diagnostics, FileDiagnostic, OutputOptions, ProcessingEvent,
+    Summary, TERMINAL_LOCK,
+};
+use crate::{input_walker, process, CliContext, CliSharedData};
+
+/// How long to wait for further events after a change, because editors often write a file in multiple steps.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(100);
+
+/// Files which change which files are found by the walker.
+const IGNORE_FILE_NAMES: [&str; 3] = [".ludtwig-ignore", ".gitignore", ".ignore"];
+
+/// The output of the last check of a single file.
+#[derive(Default)]
+struct FileReport {
+    /// Hash of the checked file content, to skip events which didn't change it
+    content_hash: Option<blake3::Hash>,
+    /// Canonical paths of the config file and other files (like script rules) the results depend on
+    dependencies: HashSet<PathBuf>,
+    summary: Summary,
+    diagnostics: Vec<FileDiagnostic>,
+    stdout: Vec<Buffer>,
+    /// Processing errors (like a file which can't be read)
+    errors: Vec<String>,
+}
+
+/// The reports of all watched files, which are replaced whenever a file changes.
+struct WatchState {
+    /// The paths given by the user together with their canonical path
+    roots: Vec<(PathBuf, PathBuf)>,
+    /// Watched files (as found by the walker) by their canonical path, which is used in file system events
+    files: HashMap<PathBuf, PathBuf>,
+    reports: BTreeMap<PathBuf, FileReport>,
+    /// Error of the last reload of the config files, which is shown until they are valid again
+    config_error: Option<String>,
+}
+
+/// Check all files in the paths and keep checking the files which change (`--watch`) until the process is stopped.
+pub fn watch(paths: &[PathBuf], data: &Arc<CliSharedData>, options: &OutputOptions) -> i32 {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(w) => w,
+        Err(e) => {
+            error!("Error: can't watch for file changes: {e}");
+            return exit_code::PROCESSING_ERROR;
+        }
+    };
+    for path in paths {
+        if let Err(e) = watcher.watch(path, RecursiveMode::Recursive) {
+            error!("Error: can't watch {}: {e}", path.display());
+            return exit_code::PROCESSING_ERROR;
+        }
+    }
+
+    let mut state = WatchState::new(paths);
+    let mut outside_dirs = HashSet::new();
+    state.update(None, data);
+    state.watch_outside_dirs(&mut watcher, data, &mut outside_dirs);
+    // stop watching if the output can't be printed anymore (like a closed pipe), nobody would see it
+    if let Err(e) = state.redraw(options) {
+        return exit_code_after_output(Err(e), exit_code::SUCCESS);
+    }
+
+    while let Ok(event) = rx.recv() {
+        let mut changed = BTreeSet::new();
+        collect_changed_paths(event, &mut changed);
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE_DURATION) {
+            collect_changed_paths(event, &mut changed);
+        }
+
+        if !changed.is_empty() && state.update(Some(&changed), data) {
+            state.watch_outside_dirs(&mut watcher, data, &mut outside_dirs);
+            if let Err(e) = state.redraw(options) {
+                return exit_code_after_output(Err(e), exit_code::SUCCESS);
+            }
+        }
+    }
+
+    0
+}
+
+/// Add the paths of events which may change the content of files (reading files creates events, too).
+fn collect_changed_paths(event: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>) {
+    let Ok(event) = event else {
+        return;
+    };
+    match event.kind {
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => {}
+        _ => changed.extend(event.paths.into_iter().map(canonical)),
+    }
+}
+
+/// The canonical path of an existing file, which is also used in file system events.
+fn canonical(path: PathBuf) -> PathBuf {
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// All template files in the paths, which are not ignored (like in a normal run).
+fn template_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    input_walker(paths)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|t| !t.is_dir()))
+        .map(ignore::DirEntry::into_path)
+        .collect()
+}
+
+/// Config files or other files they extend, which may change the config of any file.
+fn is_config_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "toml")
+}
+
+impl WatchState {
+    fn new(paths: &[PathBuf]) -> Self {
+        Self {
+            roots: paths
+                .iter()
+                .map(|path| (path.clone(), canonical(path.clone())))
+                .collect(),
+            files: HashMap::new(),
+            reports: BTreeMap::new(),
+            config_error: None,
+        }
+    }
+
+    /// Check the changed files (all if `changed` is `None`) and the files which depend on them.
+    /// Returns true if any report changed.
+    fn update(&mut self, changed: Option<&BTreeSet<PathBuf>>, data: &Arc<CliSharedData>) -> bool {
+        let Some(changed) = changed else {
+            self.find_all_files();
+            return self.check(self.files.values().cloned().collect(), data);
+        };
+
+        let mut dirty = false;
+        let mut to_check = BTreeSet::new();
+        if changed.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| IGNORE_FILE_NAMES.iter().any(|ignore| name == *ignore))
+        }) {
+            // other files may be ignored now, so the walker has to look at all of them again
+            self.find_all_files();
+            dirty |= self.forget_unwatched_reports();
+            to_check.extend(self.files.values().cloned());
+        } else {
+            for path in changed {
+                if !path.exists() {
+                    dirty |= self.forget_deleted(path);
+                } else if let Some(file) = self.files.get(path) {
+                    to_check.insert(file.clone());
+                } else {
+                    for (canonical, file) in self.find_files_below(path) {
+                        to_check.insert(file.clone());
+                        self.files.insert(canonical, file);
+                    }
+                }
+            }
+        }
+
+        // the config files and script rules are cached until they are reloaded
+        let is_dependency =
+            |path: &PathBuf| self.reports.values().any(|r| r.dependencies.contains(path));
+        let recheck_all = changed
+            .iter()
+            .any(|path| is_config_file(path) && !is_dependency(path));
+        if recheck_all
+            || changed
+                .iter()
+                .any(|path| !self.files.contains_key(path) && is_dependency(path))
+        {
+            self.config_error = data.config_resolver.reload().err().map(|e| e.to_string());
+            dirty = true;
+        }
+
+        let dependents: Vec<PathBuf> = self
+            .reports
+            .iter()
+            .filter(|(_, report)| {
+                recheck_all || report.dependencies.iter().any(|d| changed.contains(d))
+            })
+            .map(|(file, _)| file.clone())
+            .collect();
+        for file in &dependents {
+            // check them again even if their content didn't change
+            self.reports.remove(file);
+        }
+        to_check.extend(dependents);
+
+        self.check(to_check, data) || dirty
+    }
+
+    /// Check the files whose content changed since their last check.
+    /// Returns true if any report changed.
+    fn check(&mut self, files: BTreeSet<PathBuf>, data: &Arc<CliSharedData>) -> bool {
+        let to_check: Vec<(PathBuf, Option<blake3::Hash>)> = files
+            .into_iter()
+            .map(|path| {
+                let content_hash = fs::read(&path).ok().map(|c| blake3::hash(&c));
+                (path, content_hash)
+            })
+            .filter(|(path, hash)| {
+                self.reports
+                    .get(path)
+                    .map_or(true, |report| report.content_hash != *hash)
+            })
+            .collect();
+
+        let reports: Vec<(PathBuf, FileReport)> = to_check
+            .into_par_iter()
+            .map(|(path, content_hash)| {
+                let report = check_file(path.clone(), content_hash, data);
+                (path, report)
+            })
+            .collect();
+        let dirty = !reports.is_empty();
+        self.reports.extend(reports);
+
+        dirty
+    }
+
+    fn find_all_files(&mut self) {
+        let paths: Vec<PathBuf> = self.roots.iter().map(|(path, _)| path.clone()).collect();
+        self.files = template_files(&paths)
+            .into_iter()
+            .map(|path| (canonical(path.clone()), path))
+            .collect();
+    }
+
+    /// Forget the reports of files which are no longer watched (like newly ignored ones).
+    /// Returns true if any report was removed.
+    fn forget_unwatched_reports(&mut self) -> bool {
+        let watched: HashSet<&PathBuf> = self.files.values().collect();
+        let report_count = self.reports.len();
+        self.reports.retain(|path, _| watched.contains(path));
+        report_count != self.reports.len()
+    }
+
+    /// Forget a deleted file or all files in a deleted directory.
+    /// Returns true if any report was removed.
+    fn forget_deleted(&mut self, path: &Path) -> bool {
+        if let Some(file) = self.files.remove(path) {
+            return self.reports.remove(&file).is_some();
+        }
+
+        let mut dirty = false;
+        let reports = &mut self.reports;
+        self.files.retain(|canonical, file| {
+            let deleted = canonical.starts_with(path);
+            if deleted {
+                dirty |= reports.remove(file).is_some();
+            }
+            !deleted
+        });
+        dirty
+    }
+
+    /// The template files at or below the (canonical) path, if it is inside the watched paths.
+    /// Only the directories leading to the path are walked, so the ignore files apply like in a full walk.
+    fn find_files_below(&self, path: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let Some((root, relative)) = self.roots.iter().find_map(|(root, canonical_root)| {
+            Some((root, path.strip_prefix(canonical_root).ok()?))
+        }) else {
+            return vec![];
+        };
+        let target = if relative.as_os_str().is_empty() {
+            root.clone()
+        } else {
+            root.join(relative)
+        };
+
+        input_walker(std::slice::from_ref(root))
+            .filter_entry(move |entry| {
+                target.starts_with(entry.path()) || entry.path().starts_with(&target)
+            })
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_some_and(|t| !t.is_dir()))
+            .map(|entry| {
+                let file = entry.into_path();
+                (canonical(file.clone()), file)
+            })
+            .collect()
+    }
+
+    /// Also watch the directories of the files outside the watched paths which the reports depend on
+    /// (like the config file), because they would not create any events otherwise.
+    /// The directories are watched instead of the files, because editors often replace a file when saving it.
+    fn watch_outside_dirs(
+        &self,
+        watcher: &mut impl Watcher,
+        data: &CliSharedData,
+        watched_dirs: &mut HashSet<PathBuf>,
+    ) {
+        let fallback_path = data.config_resolver.fallback().path.clone().map(canonical);
+        let outside_files = self
+            .reports
+            .values()
+            .flat_map(|report| &report.dependencies)
+            .chain(&fallback_path)
+            .filter(|path| {
+                !self
+                    .roots
+                    .iter()
+                    .any(|(_, canonical_root)| path.starts_with(canonical_root))
+            });
+
+        for file in outside_files {
+            let Some(dir) = file.parent() else {
+                continue;
+            };
+            // the directory may not exist (yet), then it is tried again after the next change
+            if !watched_dirs.contains(dir)
+                && watcher.watch(dir, RecursiveMode::NonRecursive).is_ok()
+            {
+                watched_dirs.insert(dir.to_path_buf());
+            }
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        let mut summary = Summary::default();
+        for report in self.reports.values() {
+            summary += report.summary.clone();
+        }
+        if self.config_error.is_some() {
+            summary.record(&ProcessingEvent::Error(ErrorKind::Configuration));
+        }
+        summary
+    }
+
+    /// Clear the terminal and print the reports of all files followed by the summary.
+    fn redraw(&self, options: &OutputOptions) -> io::Result<()> {
+        let _terminal = TERMINAL_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let stderr_writer = BufferWriter::stderr(stderr_color_choice());
+        let stdout_writer = BufferWriter::stdout(stdout_color_choice());
+
+        if io::stdout().is_terminal() {
+            // clear the screen and move the cursor to the top left corner
+            let mut stdout = io::stdout();
+            stdout.write_all(b"\x1B[2J\x1B[H")?;
+            stdout.flush()?;
+        }
+
+        for report in self.reports.values() {
+            for buffer in &report.stdout {
+                stdout_writer.print(buffer)?;
+            }
+        }
+
+        if !options.summary_only {
+            let mut buffer = stderr_writer.buffer();
+            let diagnostics = self
+                .reports
+                .values()
+                .flat_map(|report| &report.diagnostics)
+                .collect();
+            write_diagnostics(&mut buffer, diagnostics, options)?;
+            stderr_writer.print(&buffer)?;
+        }
+
+        for error in self
+            .config_error
+            .iter()
+            .chain(self.reports.values().flat_map(|report| &report.errors))
+        {
+            writeln!(io::stderr(), "Error: {error}")?;
+        }
+
+        let mut stdout = io::stdout();
+        if !options.quiet {
+            stdout.write_all(self.summary().message().as_bytes())?;
+        }
+        if !is_quiet() {
+            let message = "Watching for changes (press Ctrl+C to stop)...";
+            if is_stdout_reserved() {
+                writeln!(io::stderr(), "{message}")?;
+            } else {
+                writeln!(stdout, "{message}")?;
+            }
+        }
+        stdout.flush()
+    }
+}
+
+/// Check a single file and collect all of its output.
+fn check_file(
+    path: PathBuf,
+    content_hash: Option<blake3::Hash>,
+    data: &Arc<CliSharedData>,
+) -> FileReport {
+    let (tx, rx) = mpsc::channel();
+    let cli_context = CliContext {
+        output_tx: tx,
+        data: Arc::clone(data),
+    };
+
+    let mut report = FileReport {
+        content_hash,
+        ..FileReport::default()
+    };
+    if let Ok(config) = data.config_resolver.resolve(&path) {
+        report
+            .dependencies
+            .extend(config.path.clone().map(canonical));
+    }
+    if let Err(e) = process::process_file(path, cli_context) {
+        report.summary.record(&ProcessingEvent::Error(e.kind()));
+        report.errors.push(e.to_string());
+    }
+
+    // all senders are dropped after processing the file
+    for event in rx {
+        report.summary.record(&event);
+        match event {
+            ProcessingEvent::Diagnostic(diagnostic) => report.diagnostics.push(diagnostic),
+            ProcessingEvent::OutputStdoutMessage(_, buffer) => report.stdout.push(buffer),
+            ProcessingEvent::Dependencies(paths) => {
+                report.dependencies.extend(paths.into_iter().map(canonical));
+            }
+            _ => {}
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use crate::config::{ActiveConfig, Config, ConfigResolver, CONFIG_FILE_NAME};
+    use crate::test_util::temp_dir;
+    use crate::CliSharedData;
+
+    use super::{template_files, WatchState};
+
+    fn cli_data(discover: bool) -> Arc<CliSharedData> {
+        let fallback = ActiveConfig::new(
+            Config::new(crate::config::DEFAULT_CONFIG_PATH).unwrap(),
+            None,
+        )
+        .unwrap();
+        Arc::new(CliSharedData {
+            fix: false,
+            check: false,
+            diff: false,
+            fix_only: vec![],
+            fix_unsafe: false,
+            verify_fixes: false,
+            interactive: None,
+            inspect: false,
+            cache: None,
+            changed_files: None,
+            changed_lines_only: false,
+            timings: None,
+            config_resolver: ConfigResolver::new(fallback, discover, false),
+        })
+    }
+
+    fn changed(paths: &[&Path]) -> BTreeSet<PathBuf> {
+        paths
+            .iter()
+            .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()))
+            .collect()
+    }
+
+    #[test]
+    fn template_files_respect_ludtwig_ignore() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.html.twig"), "").unwrap();
+        fs::write(dir.join("sub/b.html"), "").unwrap();
+        fs::write(dir.join("sub/c.html.twig"), "").unwrap();
+        fs::write(dir.join("d.txt"), "").unwrap();
+        fs::write(dir.join(".ludtwig-ignore"), "c.html.twig\n").unwrap();
+
+        let mut files: Vec<PathBuf> = template_files(&[dir.to_path_buf()])
+            .into_iter()
+            .map(|path| path.strip_prefix(dir).unwrap().to_path_buf())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("a.html.twig"), PathBuf::from("sub/b.html")]
+        );
+    }
+
+    #[test]
+    fn watch_state_replaces_reports_of_changed_files() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let a = dir.join("a.html.twig");
+        let b = dir.join("b.html.twig");
+        fs::write(&a, "<div>\n").unwrap();
+        fs::write(&b, "<p></p>\n").unwrap();
+        let data = cli_data(false);
+
+        let mut state = WatchState::new(&[dir.to_path_buf()]);
+        assert!(state.update(None, &data));
+        let summary = state.summary();
+        assert_eq!(summary.file_count, 2);
+        assert_eq!(summary.counts.errors, 1);
+
+        // events which didn't change the content are skipped
+        assert!(!state.update(Some(&changed(&[&a])), &data));
+
+        fs::write(&a, "<div></div>\n").unwrap();
+        assert!(state.update(Some(&changed(&[&a])), &data));
+        let summary = state.summary();
+        assert_eq!(summary.file_count, 2);
+        assert_eq!(summary.counts.errors, 0);
+
+        let deleted = changed(&[&b]);
+        fs::remove_file(&b).unwrap();
+        assert!(state.update(Some(&deleted), &data));
+        assert_eq!(state.summary().file_count, 1);
+    }
+
+    #[test]
+    fn watch_state_finds_new_files_without_ignored_ones() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        fs::write(dir.join(".ludtwig-ignore"), "ignored.html.twig\n").unwrap();
+        let data = cli_data(false);
+        let mut state = WatchState::new(&[dir.to_path_buf()]);
+        state.update(None, &data);
+        assert_eq!(state.summary().file_count, 0);
+
+        let new = dir.join("sub/new.html.twig");
+        let ignored = dir.join("sub/ignored.html.twig");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(&new, "<div>\n").unwrap();
+        fs::write(&ignored, "<div>\n").unwrap();
+        assert!(state.update(Some(&changed(&[&new, &ignored])), &data));
+        assert_eq!(state.summary().file_count, 1);
+        assert_eq!(state.files.values().collect::<Vec<_>>(), vec![&new]);
+
+        // deleting the directory forgets all files in it
+        let deleted = changed(&[&dir.join("sub")]);
+        fs::remove_dir_all(dir.join("sub")).unwrap();
+        assert!(state.update(Some(&deleted), &data));
+        assert_eq!(state.summary().file_count, 0);
+        assert!(state.files.is_empty());
+    }
+
+    #[test]
+    fn watch_state_rechecks_files_when_their_config_changes() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let template = dir.join("a.html.twig");
+        let config = dir.join(CONFIG_FILE_NAME);
+        fs::write(&template, "<div>\n  <span></span>\n</div>\n").unwrap();
+        fs::write(&config, "[format]\nindentation-count = 2\n").unwrap();
+        let data = cli_data(true);
+        let mut state = WatchState::new(&[dir.to_path_buf()]);
+        state.update(None, &data);
+        assert_eq!(state.summary().counts.total(), 0);
+
+        fs::write(&config, "[format]\nindentation-count = 4\n").unwrap();
+        assert!(state.update(Some(&changed(&[&config])), &data));
+        assert_eq!(state.summary().counts.total(), 1);
+
+        fs::write(&config, "[format]\nindentation-count = \"four\"\n").unwrap();
+        assert!(state.update(Some(&changed(&[&config])), &data));
+        assert_eq!(state.summary().configuration_errors, 1);
+    }
+
+    #[test]
+    fn watch_state_rechecks_files_when_a_script_rule_changes() {
+        let temp = temp_dir();
+        let dir = temp.path();
+        let template = dir.join("templates/a.html.twig");
+        let script = dir.join("rules/no-root.rhai");
+        fs::create_dir_all(dir.join("templates")).unwrap();
+        fs::create_dir_all(dir.join("rules")).unwrap();
+        fs::write(&template, "<div></div>\n").unwrap();
+        fs::write(
+            dir.join(CONFIG_FILE_NAME),
+            "[general]\nactive-rules = []\nscript-rules = [\"rules/no-root.rhai\"]\n",
+        )
+        .unwrap();
+        fs::write(&script, "fn check_root(node) { }").unwrap();
+        let data = cli_data(true);
+        // the script is outside the watched path, its events come from watching its directory
+        let mut state = WatchState::new(&[dir.join("templates")]);
+        state.update(None, &data);
+        assert_eq!(state.summary().counts.total(), 0);
+
+        fs::write(
+            &script,
+            "fn check_root(node) { result(\"warning\", \"no root\") }",
+        )
+        .unwrap();
+        assert!(state.update(Some(&changed(&[&script])), &data));
+        assert_eq!(state.summary().counts.warnings, 1);
+    }
+}
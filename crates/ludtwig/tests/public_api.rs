@@ -0,0 +1,113 @@
+//! Uses ludtwig only through its public API, like other crates depending on it.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use ludtwig::{
+    AppliedFix, CheckResult, Config, CustomRule, CustomRuleSeverity, FileProcessingError, Format,
+    General, IndentationMode, Linter, Rule, RuleCategory, RuleExt, RuleMetadata, RuleRunContext,
+    Severity, RULE_DEFINITIONS,
+};
+use ludtwig_parser::syntax::untyped::{SyntaxKind, SyntaxNode};
+
+/// A rule of another crate, which reports every HTML tag.
+struct NoHtmlTags;
+
+impl Rule for NoHtmlTags {
+    fn name(&self) -> &'static str {
+        "no-html-tags"
+    }
+
+    fn metadata(&self) -> RuleMetadata<'_> {
+        RuleMetadata {
+            description: "HTML tags are not allowed",
+            rationale: "",
+            category: RuleCategory::Custom,
+            fixable: false,
+            config_keys: &[],
+            bad_example: "<div></div>",
+            good_example: "{{ content }}",
+        }
+    }
+
+    fn check_node(&self, node: SyntaxNode, ctx: &RuleRunContext) -> Option<Vec<CheckResult>> {
+        if node.kind() != SyntaxKind::HTML_TAG {
+            return None;
+        }
+
+        let severity = match ctx.config().format.indentation_mode {
+            IndentationMode::Space => Severity::Warning,
+            IndentationMode::Tab => Severity::Error,
+        };
+        let result = self
+            .create_result(severity, "HTML tag found")
+            .primary_note(node.text_range(), "remove this tag");
+        Some(vec![result])
+    }
+}
+
+/// A config without a file, so the config in the current directory isn't used.
+fn default_config() -> Config {
+    Config::new("not-existing/ludtwig-config.toml").unwrap()
+}
+
+#[test]
+fn rules_can_be_implemented_and_described() {
+    let rules: Vec<Arc<dyn Rule>> = RULE_DEFINITIONS
+        .iter()
+        .cloned()
+        .chain([Arc::new(NoHtmlTags) as Arc<dyn Rule>])
+        .collect();
+
+    let metadata: Vec<(&str, RuleMetadata)> = rules
+        .iter()
+        .map(|rule| (rule.name(), rule.metadata()))
+        .collect();
+    assert!(metadata.iter().any(|(name, _)| *name == "indentation"));
+    let (name, own) = metadata.last().unwrap();
+    assert_eq!(*name, "no-html-tags");
+    assert_eq!(own.category, RuleCategory::Custom);
+}
+
+#[test]
+fn config_can_be_changed_before_linting() {
+    let mut config = default_config();
+    let general: &mut General = &mut config.general;
+    general.active_rules = vec!["indentation".to_string()];
+    let format: &mut Format = &mut config.format;
+    format.indentation_mode = IndentationMode::Tab;
+    format.indentation_count = 1;
+
+    let linter = Linter::new(config).unwrap();
+    let fixed = linter
+        .fix_source(Path::new("a.html.twig"), "<div>\n<span></span>\n</div>\n")
+        .unwrap();
+    assert_eq!(fixed.source_code, "<div>\n\t<span></span>\n</div>\n");
+}
+
+#[test]
+fn fix_errors_can_be_inspected() {
+    let mut config = default_config();
+    config.general.active_rules = vec![];
+    // never converges, because every replacement is matched again
+    config.custom_rules = vec![CustomRule {
+        name: "grow".to_string(),
+        query: "TWIG_LITERAL_NAME @name".to_string(),
+        message: "grow".to_string(),
+        severity: CustomRuleSeverity::Warning,
+        replacement: Some("$name$name".to_string()),
+    }];
+
+    let linter = Linter::new(config).unwrap();
+    let Err(FileProcessingError::MaxApplyIteration {
+        last_iterations, ..
+    }) = linter.fix_source(Path::new("a.html.twig"), "{{ a }}\n")
+    else {
+        panic!("fixing should not converge");
+    };
+    let last: &AppliedFix = &last_iterations.last().unwrap()[0];
+    assert_eq!(
+        (last.rule_name.as_str(), last.line, last.column),
+        ("grow", 1, 4)
+    );
+}